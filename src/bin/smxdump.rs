@@ -0,0 +1,451 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+
+extern crate smxdasm;
+
+use smxdasm::file::SMXFile;
+use smxdasm::include::IncludeGenerator;
+use smxdasm::listing::{V1Listing, OperandStyle};
+use smxdasm::v1disassembler::DecodeMode;
+
+const USAGE: &str = "Usage: smxdump [options] <file.smx>
+
+Prints the contents of a SourcePawn plugin. With no section options, every
+section except --include is printed.
+
+Options:
+    --header        Print the file header
+    --sections      Print the section table
+    --natives       Print the .natives table
+    --publics       Print the .publics table
+    --pubvars       Print the .pubvars table
+    --tags          Print the .tags table
+    --rtti          Print the rtti.* tables
+    --debug         Print the .dbg.* tables
+    --globals       Print every global with its type and initial value
+    --disasm        Print the disassembly of every function
+    --include       Print SourcePawn declarations generated from RTTI
+    --raw           Print operands as raw values
+    --both          Print operands as names and raw values
+    --best-effort   Keep disassembling past invalid instructions
+    -h, --help      Print this message";
+
+#[derive(Default)]
+struct Options {
+    header: bool,
+    sections: bool,
+    natives: bool,
+    publics: bool,
+    pubvars: bool,
+    tags: bool,
+    rtti: bool,
+    debug: bool,
+    globals: bool,
+    disasm: bool,
+    include: bool,
+    style: OperandStyle,
+    mode: DecodeMode,
+    path: Option<String>,
+}
+
+impl Options {
+    fn parse() -> Result<Options, String> {
+        let mut options = Options::default();
+
+        for arg in env::args().skip(1) {
+            match arg.as_ref() {
+                "--header" => options.header = true,
+                "--sections" => options.sections = true,
+                "--natives" => options.natives = true,
+                "--publics" => options.publics = true,
+                "--pubvars" => options.pubvars = true,
+                "--tags" => options.tags = true,
+                "--rtti" => options.rtti = true,
+                "--debug" => options.debug = true,
+                "--globals" => options.globals = true,
+                "--disasm" => options.disasm = true,
+                "--include" => options.include = true,
+                "--raw" => options.style = OperandStyle::Raw,
+                "--both" => options.style = OperandStyle::Both,
+                "--best-effort" => options.mode = DecodeMode::BestEffort,
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+                _ if options.path.is_some() => return Err(format!("Unexpected argument: {}", arg)),
+                _ => options.path = Some(arg),
+            }
+        }
+
+        if options.path.is_none() {
+            return Err("No input file given".into());
+        }
+
+        if !(options.header || options.sections || options.natives || options.publics || options.pubvars
+            || options.tags || options.rtti || options.debug || options.globals || options.disasm || options.include)
+        {
+            options.header = true;
+            options.sections = true;
+            options.natives = true;
+            options.publics = true;
+            options.pubvars = true;
+            options.tags = true;
+            options.rtti = true;
+            options.debug = true;
+            options.globals = true;
+            options.disasm = true;
+        }
+
+        Ok(options)
+    }
+}
+
+fn print_header(f: &SMXFile) {
+    println!("========== Header ==========");
+    println!("Magic: {:#x}", f.header.magic);
+    println!("Version: {:#x}", f.header.version);
+    print!("Compression Type: {}", f.header.compression_type);
+    println!("Disk Size: {}", f.header.disk_size);
+    println!("Image Size: {}", f.header.image_size);
+    println!("Section Count: {}", f.header.section_count);
+    println!("String Table Offset: {}", f.header.string_table_offset);
+    println!("Data Offset: {}", f.header.data_offset);
+    println!("Debug Packed: {}", f.header.debug_packed);
+
+    if let Some(data) = &f.data {
+        let header = data.header();
+        println!("Data Size: {}", header.data_size);
+        println!("Memory Size: {}", header.memory_size);
+    }
+
+    if let Some(code) = &f.codev1 {
+        let header = code.header();
+        println!("Code Size: {}", header.code_size);
+        println!("Cell Size: {}", header.cell_size);
+        println!("Code Version: {}", header.code_version);
+        println!("Code Flags: {:?}", header.flags);
+        println!("Main Offset: {:#x}", header.main_offset);
+    }
+
+    println!();
+}
+
+fn print_sections(f: &SMXFile) {
+    println!("========== Sections ==========");
+    println!("{:<24} {:>10} {:>10}", "Name", "Offset", "Size");
+
+    for section in &f.header.sections {
+        println!("{:<24} {:>#10x} {:>10}", section.name, section.data_offset, section.size);
+    }
+
+    println!();
+}
+
+fn print_natives(f: &SMXFile) {
+    println!("========== Natives ==========");
+
+    if let Some(natives) = &f.natives {
+        for (i, native) in natives.entries().iter().enumerate() {
+            println!("[{}] {}", i, native.name);
+        }
+    }
+
+    println!();
+}
+
+fn print_publics(f: &SMXFile) {
+    println!("========== Publics ==========");
+
+    if let Some(publics) = &f.publics {
+        for (i, pubfun) in publics.entries_ref().iter().enumerate() {
+            println!("[{}] {:#010x} {}", i, pubfun.address, pubfun.name);
+        }
+    }
+
+    println!();
+}
+
+fn print_pubvars(f: &SMXFile) {
+    println!("========== Pubvars ==========");
+
+    if let Some(pubvars) = &f.pubvars {
+        for (i, pubvar) in pubvars.entries().iter().enumerate() {
+            println!("[{}] {:#010x} {}", i, pubvar.address, pubvar.name);
+        }
+    }
+
+    println!();
+}
+
+fn print_tags(f: &SMXFile) {
+    println!("========== Tags ==========");
+
+    if let Some(tags) = &f.tags {
+        for tag in &tags.entries() {
+            println!("{:>6} {:#010x} {}", tag.id(), tag.flags(), tag.name());
+        }
+    }
+
+    println!();
+}
+
+fn print_rtti(f: &SMXFile) {
+    let rtti = f.rtti_data.as_ref();
+
+    if let Some(methods) = &f.rtti_methods {
+        println!("========== rtti.methods ==========");
+        for method in methods.methods_ref() {
+            let signature = rtti.and_then(|r| r.function_type_from_offset(method.signature).ok()).map(|t| t.to_string()).unwrap_or_default();
+            println!("{:#010x}-{:#010x} {} {}", method.pcode_start, method.pcode_end, method.name, signature);
+        }
+        println!();
+    }
+
+    if let Some(natives) = &f.rtti_natives {
+        println!("========== rtti.natives ==========");
+        for native in &natives.natives() {
+            let signature = rtti.and_then(|r| r.function_type_from_offset(native.signature).ok()).map(|t| t.to_string()).unwrap_or_default();
+            println!("{} {}", native.name, signature);
+        }
+        println!();
+    }
+
+    if let Some(enums) = &f.rtti_enums {
+        println!("========== rtti.enums ==========");
+        for e in &enums.enums() {
+            println!("{}", e);
+        }
+        println!();
+    }
+
+    if let Some(typedefs) = &f.rtti_typedefs {
+        println!("========== rtti.typedefs ==========");
+        for typedef in &typedefs.typedefs() {
+            let signature = rtti.and_then(|r| r.type_from_id(typedef.type_id).ok()).map(|t| t.to_string()).unwrap_or_default();
+            println!("{} = {}", typedef.name, signature);
+        }
+        println!();
+    }
+
+    if let Some(typesets) = &f.rtti_typesets {
+        println!("========== rtti.typesets ==========");
+        for typeset in &typesets.typesets() {
+            let types = rtti.and_then(|r| r.typeset_types_from_offset(typeset.signature).ok()).unwrap_or_default();
+            println!("{}", typeset.name);
+            for t in &types {
+                println!("    {}", t);
+            }
+        }
+        println!();
+    }
+
+    if let Some(enum_structs) = &f.rtti_enum_structs {
+        println!("========== rtti.enumstructs ==========");
+        for es in &enum_structs.entries() {
+            println!("{} (first field {}, size {})", es.name, es.first_field, es.size);
+        }
+        println!();
+    }
+
+    if let Some(fields) = &f.rtti_enum_struct_fields {
+        println!("========== rtti.enumstruct_fields ==========");
+        for field in &fields.entries() {
+            let t = rtti.and_then(|r| r.type_from_id(field.type_id).ok()).map(|t| t.to_string()).unwrap_or_default();
+            println!("{:>6} {} {}", field.offset, t, field.name);
+        }
+        println!();
+    }
+
+    if let Some(classdefs) = &f.rtti_classdefs {
+        println!("========== rtti.classdefs ==========");
+        for def in &classdefs.defs() {
+            println!("{} (flags {:#x}, first field {})", def.name, def.flags, def.first_field);
+        }
+        println!();
+    }
+
+    if let Some(fields) = &f.rtti_fields {
+        println!("========== rtti.fields ==========");
+        for field in &fields.fields() {
+            let t = rtti.and_then(|r| r.type_from_id(field.type_id).ok()).map(|t| t.to_string()).unwrap_or_default();
+            println!("{} {} (flags {:#x})", t, field.name, field.flags);
+        }
+        println!();
+    }
+}
+
+fn print_debug(f: &SMXFile) {
+    if let Some(info) = &f.debug_info {
+        println!("========== .dbg.info ==========");
+        println!("File Count: {}", info.file_count());
+        println!("Line Count: {}", info.line_count());
+        println!("Symbol Count: {}", info.symbol_count());
+        println!("Array Count: {}", info.array_count());
+        println!();
+    }
+
+    if let Some(files) = &f.debug_files {
+        println!("========== .dbg.files ==========");
+        for file in &files.entries() {
+            println!("{:#010x} {}", file.address, file.name);
+        }
+        println!();
+    }
+
+    if let Some(lines) = &f.debug_lines {
+        println!("========== .dbg.lines ==========");
+        for line in &lines.entries() {
+            println!("{:#010x} {}", line.address, line.line + 1);
+        }
+        println!();
+    }
+
+    if let Some(globals) = &f.debug_globals {
+        println!("========== .dbg.globals ==========");
+        for global in &globals.symbol_entries() {
+            let name = f.names.as_ref().unwrap().string_at(global.name_offset).unwrap_or_default();
+            print!("{:#010x} {} {}", global.address, name, global.scope);
+        }
+        println!();
+    }
+
+    if let Some(locals) = &f.debug_locals {
+        println!("========== .dbg.locals ==========");
+        for local in &locals.symbol_entries() {
+            let name = f.names.as_ref().unwrap().string_at(local.name_offset).unwrap_or_default();
+            print!("{:#010x}-{:#010x} {:>6} {} {}", local.code_start, local.code_end, local.address, name, local.scope);
+        }
+        println!();
+    }
+
+    if let Some(symbols) = &f.debug_symbols {
+        println!("========== .dbg.symbols ==========");
+        for sym in symbols.entries_ref() {
+            let dims: String = sym.dims.iter().map(|dim| format!("[{}]", dim.size)).collect();
+            print!("{:#010x}-{:#010x} {:>6} {}{} {:?} {}", sym.code_start, sym.code_end, sym.address, sym.name, dims, sym.ident, sym.scope);
+        }
+        println!();
+    }
+
+    if let Some(natives) = &f.debug_natives {
+        println!("========== .dbg.natives ==========");
+        for native in natives.entries_ref() {
+            let args: Vec<String> = native.args.iter().map(|arg| {
+                let dims: String = arg.dims.iter().map(|_| "[]").collect();
+                format!("{}{}", arg.name, dims)
+            }).collect();
+            println!("{:>4} {}({})", native.index, native.name, args.join(", "));
+        }
+        println!();
+    }
+}
+
+fn print_globals(f: &SMXFile) {
+    let globals = match f.globals() {
+        Ok(globals) => globals,
+        Err(e) => {
+            eprintln!("Failed to decode globals: {}", e);
+            return;
+        },
+    };
+
+    println!("========== Globals ==========");
+    for global in &globals {
+        println!("{:#010x} {} = {}", global.address, IncludeGenerator::declare(&global.ty, &global.name), global.value);
+    }
+    println!();
+}
+
+fn print_disasm(f: &SMXFile, listing: &V1Listing) {
+    println!("========== Disassembly ==========");
+
+    for function in f.functions().values() {
+        println!("{}", listing.render_function(function));
+    }
+}
+
+fn print_include(f: &SMXFile) {
+    match IncludeGenerator::new(f).generate() {
+        Ok(text) => print!("{}", text),
+        Err(e) => eprintln!("Failed to generate include: {}", e),
+    }
+}
+
+fn main() {
+    let options = match Options::parse() {
+        Ok(options) => options,
+        Err(msg) => {
+            if !msg.is_empty() {
+                eprintln!("{}\n", msg);
+            }
+
+            eprintln!("{}", USAGE);
+            process::exit(if msg.is_empty() { 0 } else { 2 });
+        },
+    };
+
+    let path = options.path.clone().unwrap();
+
+    let mut data = Vec::new();
+
+    if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    }
+
+    let p = match SMXFile::new_with_mode(data, options.mode.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        },
+    };
+
+    let listing = V1Listing::new(p.clone(), options.style.clone());
+
+    let f = &*p;
+
+    if options.header {
+        print_header(f);
+    }
+
+    if options.sections {
+        print_sections(f);
+    }
+
+    if options.natives {
+        print_natives(f);
+    }
+
+    if options.publics {
+        print_publics(f);
+    }
+
+    if options.pubvars {
+        print_pubvars(f);
+    }
+
+    if options.tags {
+        print_tags(f);
+    }
+
+    if options.rtti {
+        print_rtti(f);
+    }
+
+    if options.debug {
+        print_debug(f);
+    }
+
+    if options.globals {
+        print_globals(f);
+    }
+
+    if options.disasm {
+        print_disasm(f, &listing);
+    }
+
+    if options.include {
+        print_include(f);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::v1disassembler::{V1Function, V1Instruction, V1Param};
+use crate::v1opcodes::V1OPCode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKind {
+    // Execution falls into the next block.
+    Fallthrough,
+
+    // Unconditional JUMP.
+    Jump,
+
+    // Taken branch of a conditional J* instruction.
+    Branch,
+
+    // A case (or the default) of a SWITCH.
+    Switch,
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub target: usize,
+
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone)]
+pub struct BasicBlock {
+    pub id: usize,
+
+    // Address of the first instruction.
+    pub start: i32,
+
+    // Address one past the last instruction.
+    pub end: i32,
+
+    pub instructions: Vec<V1Instruction>,
+
+    pub successors: Vec<Edge>,
+
+    pub predecessors: Vec<usize>,
+}
+
+impl BasicBlock {
+    pub fn last(&self) -> &V1Instruction {
+        self.instructions.last().unwrap()
+    }
+}
+
+pub struct ControlFlowGraph {
+    function_address: i32,
+
+    blocks: Vec<BasicBlock>,
+
+    // Immediate dominator of each block, None for the entry and for blocks
+    // unreachable from it.
+    idoms: Vec<Option<usize>>,
+
+    loop_headers: BTreeSet<usize>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(function: &V1Function) -> Self {
+        let mut cfg = Self {
+            function_address: function.address,
+            blocks: ControlFlowGraph::split_blocks(function),
+            idoms: Vec::new(),
+            loop_headers: BTreeSet::new(),
+        };
+
+        cfg.compute_edges(function);
+        cfg.compute_dominators();
+        cfg.compute_loop_headers();
+
+        cfg
+    }
+
+    pub fn function_address(&self) -> i32 {
+        self.function_address
+    }
+
+    pub fn blocks(&self) -> &Vec<BasicBlock> {
+        &self.blocks
+    }
+
+    pub fn entry(&self) -> Option<&BasicBlock> {
+        self.blocks.first()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    // Returns the block containing the instruction at |addr|.
+    pub fn block_at(&self, addr: i32) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|block| addr >= block.start && addr < block.end)
+    }
+
+    pub fn immediate_dominator(&self, id: usize) -> Option<usize> {
+        self.idoms[id]
+    }
+
+    // Returns true if every path from the entry to |b| goes through |a|.
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+
+        let mut current = b;
+
+        loop {
+            if current == a {
+                return true;
+            }
+
+            match self.idoms[current] {
+                Some(idom) => current = idom,
+                None => return false,
+            }
+        }
+    }
+
+    pub fn is_reachable(&self, id: usize) -> bool {
+        id == 0 || self.idoms[id].is_some()
+    }
+
+    // Blocks that are the target of a back edge.
+    pub fn loop_headers(&self) -> &BTreeSet<usize> {
+        &self.loop_headers
+    }
+
+    pub fn is_conditional_jump(op: &V1OPCode) -> bool {
+        matches!(op,
+            V1OPCode::JZER | V1OPCode::JNZ | V1OPCode::JEQ | V1OPCode::JNEQ |
+            V1OPCode::JSLESS | V1OPCode::JSLEQ | V1OPCode::JSGRTR | V1OPCode::JSGEQ)
+    }
+
+    // Instructions after which execution never falls through.
+    pub fn is_terminator(op: &V1OPCode) -> bool {
+        matches!(op, V1OPCode::JUMP | V1OPCode::SWITCH | V1OPCode::CASETBL | V1OPCode::RETN | V1OPCode::HALT)
+    }
+
+    fn split_blocks(function: &V1Function) -> Vec<BasicBlock> {
+        let mut leaders: BTreeSet<i32> = BTreeSet::new();
+
+        if let Some(first) = function.instructions.first() {
+            leaders.insert(first.address);
+        }
+
+        for (i, insn) in function.instructions.iter().enumerate() {
+            let op = &insn.info.opcode;
+
+            if ControlFlowGraph::is_terminator(op) || ControlFlowGraph::is_conditional_jump(op) {
+                if let Some(next) = function.instructions.get(i + 1) {
+                    leaders.insert(next.address);
+                }
+            }
+
+            for (j, param) in insn.info.params.iter().enumerate() {
+                if let V1Param::Jump = param {
+                    leaders.insert(insn.params[j]);
+                }
+            }
+
+            if *op == V1OPCode::CASETBL {
+                leaders.insert(insn.params[1]);
+
+                for c in 0..insn.params[0] as usize {
+                    leaders.insert(insn.params[2 + c * 2 + 1]);
+                }
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::with_capacity(leaders.len());
+
+        for insn in &function.instructions {
+            if leaders.contains(&insn.address) || blocks.is_empty() {
+                blocks.push(BasicBlock {
+                    id: blocks.len(),
+                    start: insn.address,
+                    end: insn.address,
+                    instructions: Vec::new(),
+                    successors: Vec::new(),
+                    predecessors: Vec::new(),
+                });
+            }
+
+            blocks.last_mut().unwrap().instructions.push(insn.clone());
+        }
+
+        for i in 0..blocks.len() {
+            blocks[i].end = match blocks.get(i + 1) {
+                Some(next) => next.start,
+                None => function.code_end,
+            };
+        }
+
+        blocks
+    }
+
+    fn compute_edges(&mut self, function: &V1Function) {
+        let starts: BTreeMap<i32, usize> = self.blocks.iter().map(|block| (block.start, block.id)).collect();
+
+        let casetbls: BTreeMap<i32, &V1Instruction> = function.instructions.iter()
+            .filter(|insn| insn.info.opcode == V1OPCode::CASETBL)
+            .map(|insn| (insn.address, insn))
+            .collect();
+
+        for id in 0..self.blocks.len() {
+            let mut successors: Vec<Edge> = Vec::new();
+
+            {
+                let last = self.blocks[id].last();
+                let op = &last.info.opcode;
+
+                let mut add = |target: i32, kind: EdgeKind| {
+                    if let Some(&block) = starts.get(&target) {
+                        if !successors.iter().any(|e| e.target == block && e.kind == kind) {
+                            successors.push(Edge { target: block, kind });
+                        }
+                    }
+                };
+
+                if *op == V1OPCode::JUMP {
+                    add(last.params[0], EdgeKind::Jump);
+                } else if ControlFlowGraph::is_conditional_jump(op) {
+                    add(last.params[0], EdgeKind::Branch);
+                } else if *op == V1OPCode::SWITCH {
+                    if let Some(table) = casetbls.get(&last.params[0]) {
+                        add(table.params[1], EdgeKind::Switch);
+
+                        for c in 0..table.params[0] as usize {
+                            add(table.params[2 + c * 2 + 1], EdgeKind::Switch);
+                        }
+                    }
+                }
+
+                if !ControlFlowGraph::is_terminator(op) && id + 1 < self.blocks.len() {
+                    successors.push(Edge { target: id + 1, kind: EdgeKind::Fallthrough });
+                }
+            }
+
+            for edge in &successors {
+                if !self.blocks[edge.target].predecessors.contains(&id) {
+                    self.blocks[edge.target].predecessors.push(id);
+                }
+            }
+
+            self.blocks[id].successors = successors;
+        }
+    }
+
+    fn reverse_postorder(&self) -> Vec<usize> {
+        let mut visited: Vec<bool> = vec![false; self.blocks.len()];
+        let mut order: Vec<usize> = Vec::with_capacity(self.blocks.len());
+
+        if self.blocks.is_empty() {
+            return order;
+        }
+
+        // Iterative DFS; each stack entry is a block and its next successor index.
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        visited[0] = true;
+
+        while let Some((block, next)) = stack.pop() {
+            if next < self.blocks[block].successors.len() {
+                stack.push((block, next + 1));
+
+                let target = self.blocks[block].successors[next].target;
+
+                if !visited[target] {
+                    visited[target] = true;
+                    stack.push((target, 0));
+                }
+            } else {
+                order.push(block);
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    // Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm".
+    fn compute_dominators(&mut self) {
+        self.idoms = vec![None; self.blocks.len()];
+
+        if self.blocks.is_empty() {
+            return;
+        }
+
+        let order = self.reverse_postorder();
+
+        let mut rpo_index: Vec<usize> = vec![usize::MAX; self.blocks.len()];
+
+        for (i, &block) in order.iter().enumerate() {
+            rpo_index[block] = i;
+        }
+
+        let mut doms: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        doms[0] = Some(0);
+
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for &block in order.iter().skip(1) {
+                let mut new_idom: Option<usize> = None;
+
+                for &pred in &self.blocks[block].predecessors {
+                    if doms[pred].is_none() {
+                        continue;
+                    }
+
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(current) => {
+                            let mut a = pred;
+                            let mut b = current;
+
+                            while a != b {
+                                while rpo_index[a] > rpo_index[b] {
+                                    a = doms[a].unwrap();
+                                }
+
+                                while rpo_index[b] > rpo_index[a] {
+                                    b = doms[b].unwrap();
+                                }
+                            }
+
+                            a
+                        },
+                    });
+                }
+
+                if new_idom.is_some() && doms[block] != new_idom {
+                    doms[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        doms[0] = None;
+
+        self.idoms = doms;
+    }
+
+    fn compute_loop_headers(&mut self) {
+        let mut headers: BTreeSet<usize> = BTreeSet::new();
+
+        for block in &self.blocks {
+            for edge in &block.successors {
+                if self.dominates(edge.target, block.id) {
+                    headers.insert(edge.target);
+                }
+            }
+        }
+
+        self.loop_headers = headers;
+    }
+}
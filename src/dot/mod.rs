@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::cfg::{ControlFlowGraph, EdgeKind};
+use crate::file::SMXFile;
+use crate::listing::V1Listing;
+use crate::v1disassembler::{V1Function, V1FunctionKind};
+use crate::v1opcodes::V1OPCode;
+
+// Escapes a string for use inside a quoted DOT label.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '{' | '}' | '<' | '>' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' => escaped.push_str("\\l"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// Renders the basic blocks of a function as a DOT digraph, with the
+// instruction listing inside each node.
+pub fn cfg_to_dot(function: &V1Function, listing: &V1Listing) -> String {
+    let cfg = ControlFlowGraph::new(function);
+
+    let mut out = String::new();
+
+    out.push_str(&format!("digraph \"{}\" {{\n", escape(&function.name)));
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    for block in cfg.blocks() {
+        let mut label = String::new();
+
+        if block.id == 0 {
+            label.push_str(&format!("{}:\n", function.name));
+        } else {
+            label.push_str(&format!("{}:\n", V1Listing::label_name(block.start)));
+        }
+
+        for insn in &block.instructions {
+            label.push_str(&listing.render_instruction(insn));
+            label.push('\n');
+        }
+
+        let mut attributes = format!("label=\"{}\"", escape(&label));
+
+        if cfg.loop_headers().contains(&block.id) {
+            attributes.push_str(", style=bold");
+        }
+
+        if !cfg.is_reachable(block.id) {
+            attributes.push_str(", style=dashed");
+        }
+
+        out.push_str(&format!("    b{} [{}];\n", block.id, attributes));
+    }
+
+    for block in cfg.blocks() {
+        for edge in &block.successors {
+            let attributes = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => "",
+                EdgeKind::Branch => " [label=\"taken\", color=green]",
+                EdgeKind::Switch => " [label=\"case\", color=blue]",
+            };
+
+            out.push_str(&format!("    b{} -> b{}{};\n", block.id, edge.target, attributes));
+        }
+    }
+
+    out.push_str("}\n");
+
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallTarget {
+    // Code address of a function.
+    Function(i32),
+
+    // Index into the .natives table.
+    Native(i32),
+}
+
+// Whole-plugin call graph built from CALL and SYSREQ operands. Natives are
+// leaf nodes.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    functions: BTreeMap<i32, (String, V1FunctionKind)>,
+
+    natives: BTreeMap<i32, String>,
+
+    edges: BTreeSet<(i32, CallTarget)>,
+}
+
+impl CallGraph {
+    pub fn new(file: &SMXFile) -> Self {
+        let mut graph = Self::default();
+
+        if let Some(publics) = &file.publics {
+            for pubfun in publics.entries_ref() {
+                graph.functions.insert(pubfun.address as i32, (pubfun.name.clone(), V1FunctionKind::Public));
+            }
+        }
+
+        if let Some(called_functions) = &file.called_functions {
+            for fun in called_functions.entries_ref() {
+                graph.functions.entry(fun.address as i32).or_insert((fun.name.clone(), V1FunctionKind::Called));
+            }
+        }
+
+        for function in file.functions().values() {
+            for insn in &function.instructions {
+                match insn.info.opcode {
+                    V1OPCode::CALL => {
+                        graph.edges.insert((function.address, CallTarget::Function(insn.params[0])));
+                    },
+                    V1OPCode::SYSREQ_C | V1OPCode::SYSREQ_N => {
+                        let index = insn.params[0];
+
+                        let name = match &file.natives {
+                            Some(natives) if index >= 0 && (index as usize) < natives.size() => natives.get_entry(index as usize).name,
+                            _ => format!("native_{}", index),
+                        };
+
+                        graph.natives.insert(index, name);
+                        graph.edges.insert((function.address, CallTarget::Native(index)));
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn functions(&self) -> &BTreeMap<i32, (String, V1FunctionKind)> {
+        &self.functions
+    }
+
+    pub fn natives(&self) -> &BTreeMap<i32, String> {
+        &self.natives
+    }
+
+    pub fn edges(&self) -> &BTreeSet<(i32, CallTarget)> {
+        &self.edges
+    }
+
+    pub fn callees(&self, addr: i32) -> Vec<CallTarget> {
+        self.edges.iter().filter(|(from, _)| *from == addr).map(|(_, to)| to.clone()).collect()
+    }
+
+    pub fn callers(&self, target: &CallTarget) -> Vec<i32> {
+        self.edges.iter().filter(|(_, to)| to == target).map(|(from, _)| *from).collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("digraph callgraph {\n");
+        out.push_str("    node [fontname=\"monospace\"];\n");
+
+        for (addr, (name, kind)) in &self.functions {
+            let shape = match kind {
+                V1FunctionKind::Public => "box, style=bold",
+                V1FunctionKind::Called => "box",
+            };
+
+            out.push_str(&format!("    f{:x} [label=\"{}\", shape={}];\n", addr, escape(name), shape));
+        }
+
+        for (index, name) in &self.natives {
+            out.push_str(&format!("    n{} [label=\"{}\", shape=ellipse, style=dashed];\n", index, escape(name)));
+        }
+
+        for (from, to) in &self.edges {
+            match to {
+                CallTarget::Function(addr) => out.push_str(&format!("    f{:x} -> f{:x};\n", from, addr)),
+                CallTarget::Native(index) => out.push_str(&format!("    f{:x} -> n{};\n", from, index)),
+            }
+        }
+
+        out.push_str("}\n");
+
+        out
+    }
+}
@@ -0,0 +1,898 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::file::SMXFile;
+use crate::sections::SMXCodeV1Section;
+use crate::v1disassembler::V1Instruction;
+use crate::v1opcodes::V1OPCode;
+use crate::errors::{Result, Error};
+
+// Bytes kept free between the heap and the stack, as in the SourcePawn VM.
+const STACK_MARGIN: i32 = 64;
+
+// Largest DataHeader::memory_size the emulator will allocate.
+const MAX_MEMORY_SIZE: u32 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Registers {
+    pub pri: i32,
+
+    pub alt: i32,
+
+    // Frame pointer. The caller's FRM is at FRM, the return address at
+    // FRM + 4, the argument count at FRM + 8 and the arguments from FRM + 12.
+    pub frm: i32,
+
+    // Stack pointer. The stack grows down from the end of memory.
+    pub stk: i32,
+
+    // Heap pointer. The heap grows up from the end of .data.
+    pub hea: i32,
+
+    // Address of the instruction being executed.
+    pub cip: i32,
+}
+
+// Bytes stored by an instruction or a native.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryWrite {
+    pub address: i32,
+
+    pub bytes: Vec<u8>,
+}
+
+// The plugin's memory: the .data image, then the heap, then the stack.
+#[derive(Clone)]
+pub struct Memory {
+    bytes: Vec<u8>,
+
+    // Writes since the last traced instruction, while tracing.
+    journal: Option<Vec<MemoryWrite>>,
+}
+
+impl Memory {
+    fn new(data: &[u8], memory_size: usize) -> Self {
+        let mut bytes = data.to_vec();
+
+        bytes.resize(memory_size.max(data.len()), 0);
+
+        Self {
+            bytes,
+            journal: None,
+        }
+    }
+
+    pub fn size(&self) -> i32 {
+        self.bytes.len() as i32
+    }
+
+    pub fn read_cell(&self, addr: i32) -> Result<i32> {
+        let cell = self.read_bytes(addr, 4)?;
+
+        Ok(i32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]]))
+    }
+
+    pub fn write_cell(&mut self, addr: i32, value: i32) -> Result<()> {
+        self.write_bytes(addr, &value.to_le_bytes())
+    }
+
+    pub fn read_bytes(&self, addr: i32, len: usize) -> Result<&[u8]> {
+        let start = self.check(addr, len)?;
+
+        Ok(&self.bytes[start..start + len])
+    }
+
+    pub fn write_bytes(&mut self, addr: i32, bytes: &[u8]) -> Result<()> {
+        let start = self.check(addr, bytes.len())?;
+
+        self.bytes[start..start + bytes.len()].copy_from_slice(bytes);
+
+        if let Some(journal) = &mut self.journal {
+            journal.push(MemoryWrite {
+                address: addr,
+                bytes: bytes.to_vec(),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Reads the packed, null-terminated string at |addr|.
+    pub fn read_string(&self, addr: i32) -> Result<String> {
+        let start = self.check(addr, 1)?;
+
+        let bytes = &self.bytes[start..];
+
+        let end = match bytes.iter().position(|&byte| byte == 0) {
+            Some(end) => end,
+            None => return Err(Error::InvalidAddress { address: self.size() }),
+        };
+
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    // Writes |s| as a packed string into a buffer of |maxlength| bytes at
+    // |addr|, truncating it to fit along with the null terminator. Returns the
+    // number of bytes written, not counting the terminator.
+    pub fn write_string(&mut self, addr: i32, s: &str, maxlength: usize) -> Result<usize> {
+        if maxlength == 0 {
+            return Ok(0)
+        }
+
+        let mut len = s.len().min(maxlength - 1);
+
+        // Don't split a character.
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        self.write_bytes(addr, &s.as_bytes()[..len])?;
+        self.write_bytes(addr + len as i32, &[0])?;
+
+        Ok(len)
+    }
+
+    fn check(&self, addr: i32, len: usize) -> Result<usize> {
+        if addr < 0 || addr as usize + len > self.bytes.len() {
+            return Err(Error::InvalidAddress { address: addr })
+        }
+
+        Ok(addr as usize)
+    }
+}
+
+// A native invoked by SYSREQ.C or SYSREQ.N.
+pub struct NativeCall<'a> {
+    // Name in the .natives table.
+    pub name: &'a str,
+
+    // Index in the .natives table.
+    pub index: i32,
+
+    // Address of the SYSREQ instruction.
+    pub address: i32,
+
+    // Argument cells, first argument first. Arrays and by-reference arguments
+    // are addresses in memory.
+    pub args: &'a [i32],
+}
+
+// Implements the natives called by the emulated plugin. The returned value is
+// stored in PRI.
+pub trait NativeHandler {
+    fn invoke(&mut self, memory: &mut Memory, call: &NativeCall) -> Result<i32>;
+}
+
+// Observes every instruction executed by a traced call. |registers| hold the
+// values after the instruction ran, and |writes| the memory it stored, including
+// writes made by natives.
+pub trait Tracer {
+    fn step(&mut self, insn: &V1Instruction, registers: &Registers, writes: &[MemoryWrite]);
+
+    // Called instead of step() for the instruction that ends the run with
+    // |error|. |registers| and |writes| hold whatever it did before failing.
+    fn trap(&mut self, insn: &V1Instruction, registers: &Registers, writes: &[MemoryWrite], error: &Error) {
+        let _ = error;
+
+        self.step(insn, registers, writes);
+    }
+}
+
+// Executes functions of a plugin offline. Globals keep their values between
+// calls until reset() reloads .data.
+pub struct Emulator<'a> {
+    file: &'a SMXFile<'a>,
+    code: &'a SMXCodeV1Section<'a>,
+    natives: Vec<String>,
+
+    // Every decoded instruction, keyed by address. PROCs are not included.
+    instructions: Arc<HashMap<i32, V1Instruction>>,
+
+    data: &'a [u8],
+    data_size: i32,
+    memory_size: i32,
+    memory: Memory,
+    registers: Registers,
+
+    // Heap sizes pushed by GENARRAY and TRACKER.PUSH.C.
+    trackers: Vec<i32>,
+
+    // Address of the instruction to execute after the current one.
+    next: i32,
+
+    limit: Option<u64>,
+    executed: u64,
+}
+
+impl<'a> Emulator<'a> {
+    pub fn new(file: &'a SMXFile<'a>) -> Result<Self> {
+        let code = match &file.codev1 {
+            Some(code) => code.as_ref(),
+            None => return Err(Error::Other("Missing .code section")),
+        };
+
+        let (data, header) = match &file.data {
+            Some(data) => (data.data(), data.header()),
+            None => return Err(Error::Other("Missing .data section")),
+        };
+
+        if header.data_size > header.memory_size || header.memory_size > MAX_MEMORY_SIZE {
+            return Err(Error::InvalidSize)
+        }
+
+        let (data_size, memory_size) = (header.data_size as i32, header.memory_size as i32);
+
+        let natives = match &file.natives {
+            Some(natives) => natives.entries().into_iter().map(|native| native.name).collect(),
+            None => Vec::new(),
+        };
+
+        let mut instructions: HashMap<i32, V1Instruction> = HashMap::new();
+
+        for function in code.sweep().functions {
+            for insn in function.instructions {
+                instructions.insert(insn.address, insn);
+            }
+        }
+
+        let mut emulator = Self {
+            file,
+            code,
+            natives,
+            instructions: Arc::new(instructions),
+            data,
+            data_size,
+            memory_size,
+            memory: Memory::new(data, memory_size as usize),
+            registers: Registers::default(),
+            trackers: Vec::new(),
+            next: 0,
+            limit: None,
+            executed: 0,
+        };
+
+        emulator.reset();
+
+        Ok(emulator)
+    }
+
+    // Reloads .data and empties the stack and the heap.
+    pub fn reset(&mut self) {
+        self.memory = Memory::new(self.data, self.memory_size as usize);
+
+        self.registers = Registers {
+            stk: self.memory.size(),
+            hea: self.data_size,
+            ..Registers::default()
+        };
+
+        self.trackers.clear();
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.registers
+    }
+
+    // Stops each call with a trap after |limit| instructions, e.g. to break
+    // out of an infinite loop.
+    pub fn set_instruction_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit;
+    }
+
+    // Number of instructions executed since the emulator was created.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    // Allocates |cells| zeroed cells on the heap, e.g. for an array argument.
+    // The allocation lasts until reset().
+    pub fn alloc(&mut self, cells: i32) -> Result<i32> {
+        let addr = self.registers.hea;
+
+        self.grow_heap(cells.wrapping_mul(4))?;
+
+        for i in 0..cells {
+            self.memory.write_cell(addr + i * 4, 0)?;
+        }
+
+        Ok(addr)
+    }
+
+    // Allocates a char buffer of |size| bytes on the heap holding |s|.
+    pub fn alloc_string(&mut self, s: &str, size: usize) -> Result<i32> {
+        let addr = self.alloc((size as i32 + 3) / 4)?;
+
+        self.memory.write_string(addr, s, size)?;
+
+        Ok(addr)
+    }
+
+    // Looks up a function by its .publics name or its RTTI name.
+    pub fn find_function(&self, name: &str) -> Option<i32> {
+        if let Some(publics) = &self.file.publics {
+            if let Some(public) = publics.entries_ref().iter().find(|public| public.name == name) {
+                return Some(public.address as i32);
+            }
+        }
+
+        if let Some(methods) = &self.file.rtti_methods {
+            if let Some(method) = methods.methods_ref().iter().find(|method| method.name == name) {
+                return Some(method.pcode_start);
+            }
+        }
+
+        self.file.functions().values().find(|function| function.name == name).map(|function| function.address)
+    }
+
+    pub fn call_function(&mut self, name: &str, args: &[i32], natives: &mut dyn NativeHandler) -> Result<i32> {
+        match self.find_function(name) {
+            Some(addr) => self.call(addr, args, natives),
+            None => Err(Error::Other("Unknown function")),
+        }
+    }
+
+    // Calls the function whose PROC is at |addr| and returns its PRI. The
+    // stack and the heap are unwound afterwards, even if the call fails.
+    pub fn call(&mut self, addr: i32, args: &[i32], natives: &mut dyn NativeHandler) -> Result<i32> {
+        self.call_internal(addr, args, natives, None)
+    }
+
+    // Like call(), reporting each executed instruction to |tracer|.
+    pub fn call_traced(&mut self, addr: i32, args: &[i32], natives: &mut dyn NativeHandler, tracer: &mut dyn Tracer) -> Result<i32> {
+        self.call_internal(addr, args, natives, Some(tracer))
+    }
+
+    fn call_internal(&mut self, addr: i32, args: &[i32], natives: &mut dyn NativeHandler, tracer: Option<&mut dyn Tracer>) -> Result<i32> {
+        if !self.code.is_proc_at(addr) {
+            return Err(Error::Trap { address: addr, reason: "Not a function" })
+        }
+
+        let saved = self.registers;
+        let trackers = self.trackers.len();
+
+        let result = self.enter(addr, args).and_then(|_| self.run(natives, tracer));
+
+        self.memory.journal = None;
+
+        self.registers = Registers {
+            pri: self.registers.pri,
+            alt: self.registers.alt,
+            ..saved
+        };
+
+        self.trackers.truncate(trackers);
+
+        result
+    }
+
+    fn enter(&mut self, addr: i32, args: &[i32]) -> Result<()> {
+        for &arg in args.iter().rev() {
+            self.push(arg)?;
+        }
+
+        self.push(args.len() as i32)?;
+
+        // Return address. Returning from the outermost frame ends the run
+        // instead.
+        self.push(0)?;
+        self.proc(addr)
+    }
+
+    // Runs the body of the function whose PROC is at |addr|.
+    fn proc(&mut self, addr: i32) -> Result<()> {
+        self.push(self.registers.frm)?;
+
+        self.registers.frm = self.registers.stk;
+        self.registers.cip = addr + 4;
+
+        Ok(())
+    }
+
+    fn run(&mut self, natives: &mut dyn NativeHandler, mut tracer: Option<&mut dyn Tracer>) -> Result<i32> {
+        let instructions = Arc::clone(&self.instructions);
+        let start = self.executed;
+
+        if tracer.is_some() {
+            self.memory.journal = Some(Vec::new());
+        }
+
+        let mut depth = 0;
+
+        loop {
+            let cip = self.registers.cip;
+
+            if self.limit.is_some_and(|limit| self.executed - start >= limit) {
+                return Err(Error::Trap { address: cip, reason: "Instruction limit reached" })
+            }
+
+            let insn = match instructions.get(&cip) {
+                Some(insn) => insn,
+                None => return Err(Error::Trap { address: cip, reason: "Invalid instruction address" }),
+            };
+
+            self.executed += 1;
+
+            let result = self.step(insn, natives, &mut depth);
+
+            if let Some(tracer) = tracer.as_deref_mut() {
+                let writes = self.memory.journal.as_mut().map(std::mem::take).unwrap_or_default();
+
+                match &result {
+                    Ok(()) => tracer.step(insn, &self.registers, &writes),
+                    Err(error) => tracer.trap(insn, &self.registers, &writes, error),
+                }
+            }
+
+            result?;
+
+            // Returned from the outermost frame.
+            if depth < 0 {
+                return Ok(self.registers.pri)
+            }
+        }
+    }
+
+    // Runs |insn| and moves cip past it. |depth| tracks calls and returns.
+    fn step(&mut self, insn: &V1Instruction, natives: &mut dyn NativeHandler, depth: &mut i32) -> Result<()> {
+        let cip = self.registers.cip;
+
+        match insn.info.opcode {
+            V1OPCode::CALL => {
+                self.push(cip + 8)?;
+                self.proc(insn.params[0])?;
+
+                *depth += 1;
+            },
+            V1OPCode::RETN => {
+                self.registers.frm = self.pop()?;
+
+                let ret = self.pop()?;
+                let argc = self.pop()?;
+
+                self.grow_stack(cip, argc.wrapping_mul(4))?;
+                self.registers.cip = ret;
+
+                *depth -= 1;
+            },
+            _ => {
+                self.next = cip + 4 * (insn.params.len() as i32 + 1);
+
+                self.execute(insn, natives)?;
+
+                self.registers.cip = self.next;
+            },
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, insn: &V1Instruction, natives: &mut dyn NativeHandler) -> Result<()> {
+        let params = &insn.params;
+        let r = &mut self.registers;
+        let m = &mut self.memory;
+
+        match insn.info.opcode {
+            V1OPCode::NOP | V1OPCode::BREAK => (),
+            V1OPCode::LOAD_PRI => r.pri = m.read_cell(params[0])?,
+            V1OPCode::LOAD_ALT => r.alt = m.read_cell(params[0])?,
+            V1OPCode::LOAD_S_PRI => r.pri = m.read_cell(r.frm.wrapping_add(params[0]))?,
+            V1OPCode::LOAD_S_ALT => r.alt = m.read_cell(r.frm.wrapping_add(params[0]))?,
+            V1OPCode::LOAD_BOTH => {
+                r.pri = m.read_cell(params[0])?;
+                r.alt = m.read_cell(params[1])?;
+            },
+            V1OPCode::LOAD_S_BOTH => {
+                r.pri = m.read_cell(r.frm.wrapping_add(params[0]))?;
+                r.alt = m.read_cell(r.frm.wrapping_add(params[1]))?;
+            },
+            V1OPCode::LREF_S_PRI => r.pri = m.read_cell(m.read_cell(r.frm.wrapping_add(params[0]))?)?,
+            V1OPCode::LREF_S_ALT => r.alt = m.read_cell(m.read_cell(r.frm.wrapping_add(params[0]))?)?,
+            V1OPCode::LOAD_I => r.pri = m.read_cell(r.pri)?,
+            V1OPCode::LODB_I => r.pri = Emulator::read_sized(m, r.pri, params[0], insn.address)?,
+            V1OPCode::CONST_PRI | V1OPCode::LDGFN_PRI => r.pri = params[0],
+            V1OPCode::CONST_ALT => r.alt = params[0],
+            V1OPCode::ADDR_PRI => r.pri = r.frm.wrapping_add(params[0]),
+            V1OPCode::ADDR_ALT => r.alt = r.frm.wrapping_add(params[0]),
+            V1OPCode::STOR_PRI => m.write_cell(params[0], r.pri)?,
+            V1OPCode::STOR_ALT => m.write_cell(params[0], r.alt)?,
+            V1OPCode::STOR_S_PRI => m.write_cell(r.frm.wrapping_add(params[0]), r.pri)?,
+            V1OPCode::STOR_S_ALT => m.write_cell(r.frm.wrapping_add(params[0]), r.alt)?,
+            V1OPCode::SREF_S_PRI => m.write_cell(m.read_cell(r.frm.wrapping_add(params[0]))?, r.pri)?,
+            V1OPCode::SREF_S_ALT => m.write_cell(m.read_cell(r.frm.wrapping_add(params[0]))?, r.alt)?,
+            V1OPCode::STOR_I => m.write_cell(r.alt, r.pri)?,
+            V1OPCode::STRB_I => Emulator::write_sized(m, r.alt, r.pri, params[0], insn.address)?,
+            V1OPCode::CONST => m.write_cell(params[0], params[1])?,
+            V1OPCode::CONST_S => m.write_cell(r.frm.wrapping_add(params[0]), params[1])?,
+            V1OPCode::LIDX => r.pri = m.read_cell(r.alt.wrapping_add(r.pri.wrapping_mul(4)))?,
+            V1OPCode::LIDX_B => r.pri = m.read_cell(r.alt.wrapping_add(r.pri.wrapping_shl(params[0] as u32)))?,
+            V1OPCode::IDXADDR => r.pri = r.alt.wrapping_add(r.pri.wrapping_mul(4)),
+            V1OPCode::IDXADDR_B => r.pri = r.alt.wrapping_add(r.pri.wrapping_shl(params[0] as u32)),
+            V1OPCode::MOVE_PRI => r.pri = r.alt,
+            V1OPCode::MOVE_ALT => r.alt = r.pri,
+            V1OPCode::XCHG => std::mem::swap(&mut r.pri, &mut r.alt),
+            V1OPCode::PUSH_PRI => self.push(self.registers.pri)?,
+            V1OPCode::PUSH_ALT => self.push(self.registers.alt)?,
+            V1OPCode::PUSH_C | V1OPCode::PUSH2_C | V1OPCode::PUSH3_C | V1OPCode::PUSH4_C | V1OPCode::PUSH5_C => {
+                for &value in params {
+                    self.push(value)?;
+                }
+            },
+            V1OPCode::PUSH | V1OPCode::PUSH2 | V1OPCode::PUSH3 | V1OPCode::PUSH4 | V1OPCode::PUSH5 => {
+                for &addr in params {
+                    self.push(self.memory.read_cell(addr)?)?;
+                }
+            },
+            V1OPCode::PUSH_S | V1OPCode::PUSH2_S | V1OPCode::PUSH3_S | V1OPCode::PUSH4_S | V1OPCode::PUSH5_S => {
+                for &offset in params {
+                    self.push(self.memory.read_cell(self.registers.frm.wrapping_add(offset))?)?;
+                }
+            },
+            V1OPCode::PUSH_ADR | V1OPCode::PUSH2_ADR | V1OPCode::PUSH3_ADR | V1OPCode::PUSH4_ADR | V1OPCode::PUSH5_ADR => {
+                for &offset in params {
+                    self.push(self.registers.frm.wrapping_add(offset))?;
+                }
+            },
+            V1OPCode::POP_PRI => self.registers.pri = self.pop()?,
+            V1OPCode::POP_ALT => self.registers.alt = self.pop()?,
+            V1OPCode::SWAP_PRI => {
+                let top = m.read_cell(r.stk)?;
+
+                m.write_cell(r.stk, r.pri)?;
+                r.pri = top;
+            },
+            V1OPCode::SWAP_ALT => {
+                let top = m.read_cell(r.stk)?;
+
+                m.write_cell(r.stk, r.alt)?;
+                r.alt = top;
+            },
+            V1OPCode::STACK => self.grow_stack(insn.address, params[0])?,
+            V1OPCode::HEAP => {
+                self.registers.alt = self.registers.hea;
+                self.grow_heap(params[0])?;
+            },
+            V1OPCode::JUMP => self.next = params[0],
+            V1OPCode::JZER => Emulator::branch(&mut self.next, r.pri == 0, params[0]),
+            V1OPCode::JNZ => Emulator::branch(&mut self.next, r.pri != 0, params[0]),
+            V1OPCode::JEQ => Emulator::branch(&mut self.next, r.pri == r.alt, params[0]),
+            V1OPCode::JNEQ => Emulator::branch(&mut self.next, r.pri != r.alt, params[0]),
+            V1OPCode::JSLESS => Emulator::branch(&mut self.next, r.pri < r.alt, params[0]),
+            V1OPCode::JSLEQ => Emulator::branch(&mut self.next, r.pri <= r.alt, params[0]),
+            V1OPCode::JSGRTR => Emulator::branch(&mut self.next, r.pri > r.alt, params[0]),
+            V1OPCode::JSGEQ => Emulator::branch(&mut self.next, r.pri >= r.alt, params[0]),
+            V1OPCode::SWITCH => self.next = self.switch_target(insn.address, params[0])?,
+            V1OPCode::SHL => r.pri = r.pri.wrapping_shl(r.alt as u32),
+            V1OPCode::SHR => r.pri = (r.pri as u32).wrapping_shr(r.alt as u32) as i32,
+            V1OPCode::SSHR => r.pri = r.pri.wrapping_shr(r.alt as u32),
+            V1OPCode::SHL_C_PRI => r.pri = r.pri.wrapping_shl(params[0] as u32),
+            V1OPCode::SHL_C_ALT => r.alt = r.alt.wrapping_shl(params[0] as u32),
+            V1OPCode::SHR_C_PRI => r.pri = (r.pri as u32).wrapping_shr(params[0] as u32) as i32,
+            V1OPCode::SHR_C_ALT => r.alt = (r.alt as u32).wrapping_shr(params[0] as u32) as i32,
+            V1OPCode::SMUL => r.pri = r.pri.wrapping_mul(r.alt),
+            V1OPCode::SMUL_C => r.pri = r.pri.wrapping_mul(params[0]),
+            V1OPCode::SDIV | V1OPCode::SDIV_ALT => {
+                let (dividend, divisor) = match insn.info.opcode {
+                    V1OPCode::SDIV => (r.pri, r.alt),
+                    _ => (r.alt, r.pri),
+                };
+
+                if divisor == 0 {
+                    return Err(Error::Trap { address: insn.address, reason: "Division by zero" })
+                }
+
+                r.pri = dividend.wrapping_div(divisor);
+                r.alt = dividend.wrapping_rem(divisor);
+            },
+            V1OPCode::ADD => r.pri = r.pri.wrapping_add(r.alt),
+            V1OPCode::ADD_C => r.pri = r.pri.wrapping_add(params[0]),
+            V1OPCode::SUB => r.pri = r.pri.wrapping_sub(r.alt),
+            V1OPCode::SUB_ALT => r.pri = r.alt.wrapping_sub(r.pri),
+            V1OPCode::AND => r.pri &= r.alt,
+            V1OPCode::OR => r.pri |= r.alt,
+            V1OPCode::XOR => r.pri ^= r.alt,
+            V1OPCode::NOT => r.pri = (r.pri == 0) as i32,
+            V1OPCode::NEG => r.pri = r.pri.wrapping_neg(),
+            V1OPCode::INVERT => r.pri = !r.pri,
+            V1OPCode::ZERO_PRI => r.pri = 0,
+            V1OPCode::ZERO_ALT => r.alt = 0,
+            V1OPCode::ZERO => m.write_cell(params[0], 0)?,
+            V1OPCode::ZERO_S => m.write_cell(r.frm.wrapping_add(params[0]), 0)?,
+            V1OPCode::EQ => r.pri = (r.pri == r.alt) as i32,
+            V1OPCode::NEQ => r.pri = (r.pri != r.alt) as i32,
+            V1OPCode::SLESS => r.pri = (r.pri < r.alt) as i32,
+            V1OPCode::SLEQ => r.pri = (r.pri <= r.alt) as i32,
+            V1OPCode::SGRTR => r.pri = (r.pri > r.alt) as i32,
+            V1OPCode::SGEQ => r.pri = (r.pri >= r.alt) as i32,
+            V1OPCode::EQ_C_PRI => r.pri = (r.pri == params[0]) as i32,
+            V1OPCode::EQ_C_ALT => r.pri = (r.alt == params[0]) as i32,
+            V1OPCode::INC_PRI => r.pri = r.pri.wrapping_add(1),
+            V1OPCode::INC_ALT => r.alt = r.alt.wrapping_add(1),
+            V1OPCode::INC => Emulator::add_cell(m, params[0], 1)?,
+            V1OPCode::INC_S => Emulator::add_cell(m, r.frm.wrapping_add(params[0]), 1)?,
+            V1OPCode::INC_I => Emulator::add_cell(m, r.pri, 1)?,
+            V1OPCode::DEC_PRI => r.pri = r.pri.wrapping_sub(1),
+            V1OPCode::DEC_ALT => r.alt = r.alt.wrapping_sub(1),
+            V1OPCode::DEC => Emulator::add_cell(m, params[0], -1)?,
+            V1OPCode::DEC_S => Emulator::add_cell(m, r.frm.wrapping_add(params[0]), -1)?,
+            V1OPCode::DEC_I => Emulator::add_cell(m, r.pri, -1)?,
+            V1OPCode::MOVS => {
+                let bytes = m.read_bytes(r.pri, params[0].max(0) as usize)?.to_vec();
+
+                m.write_bytes(r.alt, &bytes)?;
+            },
+            V1OPCode::FILL => {
+                for offset in (0..params[0]).step_by(4) {
+                    m.write_cell(r.alt.wrapping_add(offset), r.pri)?;
+                }
+            },
+            V1OPCode::BOUNDS => {
+                if r.pri as u32 > params[0] as u32 {
+                    return Err(Error::Trap { address: insn.address, reason: "Array index out of bounds" })
+                }
+            },
+            V1OPCode::HALT => return Err(Error::Trap { address: insn.address, reason: "Halt" }),
+            V1OPCode::SYSREQ_C => {
+                let argc = self.memory.read_cell(self.registers.stk)?;
+
+                self.registers.pri = self.native(insn.address, params[0], self.registers.stk.wrapping_add(4), argc, natives)?;
+            },
+            V1OPCode::SYSREQ_N => {
+                self.registers.pri = self.native(insn.address, params[0], self.registers.stk, params[1], natives)?;
+                self.grow_stack(insn.address, params[1].wrapping_mul(4))?;
+            },
+            V1OPCode::TRACKER_PUSH_C => self.trackers.push(params[0].wrapping_mul(4)),
+            V1OPCode::TRACKER_POP_SETHEAP => {
+                let amount = match self.trackers.pop() {
+                    Some(amount) => amount,
+                    None => return Err(Error::Trap { address: insn.address, reason: "Tracker stack underflow" }),
+                };
+
+                let hea = self.registers.hea.wrapping_sub(amount);
+
+                if hea < self.data_size || hea > self.registers.stk - STACK_MARGIN {
+                    return Err(Error::Trap { address: insn.address, reason: "Heap underflow" })
+                }
+
+                self.registers.hea = hea;
+            },
+            V1OPCode::GENARRAY => self.generate_array(insn.address, params[0], false)?,
+            V1OPCode::GENARRAY_Z => self.generate_array(insn.address, params[0], true)?,
+            V1OPCode::STRADJUST_PRI => r.pri = r.pri.wrapping_add(4) >> 2,
+            V1OPCode::FABS => r.pri &= 0x7fffffff,
+            V1OPCode::FLOAT => r.pri = (r.pri as f32).to_bits() as i32,
+            V1OPCode::FLOATADD => r.pri = Emulator::float_op(r, |a, b| a + b),
+            V1OPCode::FLOATSUB => r.pri = Emulator::float_op(r, |a, b| a - b),
+            V1OPCode::FLOATMUL => r.pri = Emulator::float_op(r, |a, b| a * b),
+            V1OPCode::FLOATDIV => r.pri = Emulator::float_op(r, |a, b| a / b),
+            V1OPCode::RND_TO_NEAREST => r.pri = Emulator::float(r.pri).round() as i32,
+            V1OPCode::RND_TO_FLOOR => r.pri = Emulator::float(r.pri).floor() as i32,
+            V1OPCode::RND_TO_CEIL => r.pri = Emulator::float(r.pri).ceil() as i32,
+            V1OPCode::RND_TO_ZERO => r.pri = Emulator::float(r.pri).trunc() as i32,
+            V1OPCode::FLOATCMP => {
+                let (a, b) = (Emulator::float(r.pri), Emulator::float(r.alt));
+
+                r.pri = if a > b { 1 } else if a < b { -1 } else { 0 };
+            },
+            V1OPCode::FLOAT_GT => r.pri = (Emulator::float(r.pri) > Emulator::float(r.alt)) as i32,
+            V1OPCode::FLOAT_GE => r.pri = (Emulator::float(r.pri) >= Emulator::float(r.alt)) as i32,
+            V1OPCode::FLOAT_LT => r.pri = (Emulator::float(r.pri) < Emulator::float(r.alt)) as i32,
+            V1OPCode::FLOAT_LE => r.pri = (Emulator::float(r.pri) <= Emulator::float(r.alt)) as i32,
+            V1OPCode::FLOAT_NE => r.pri = (Emulator::float(r.pri) != Emulator::float(r.alt)) as i32,
+            V1OPCode::FLOAT_EQ => r.pri = (Emulator::float(r.pri) == Emulator::float(r.alt)) as i32,
+            V1OPCode::FLOAT_NOT => r.pri = (Emulator::float(r.pri) == 0.0) as i32,
+            _ => return Err(Error::InvalidOpcode { address: insn.address, value: insn.info.opcode.clone() as i32 }),
+        }
+
+        Ok(())
+    }
+
+    // HEA stays between the end of .data and STK - STACK_MARGIN, and STK
+    // between HEA + STACK_MARGIN and the end of memory, so only the amounts
+    // below can overflow.
+    fn push(&mut self, value: i32) -> Result<()> {
+        let stk = self.registers.stk - 4;
+
+        if stk < self.registers.hea + STACK_MARGIN {
+            return Err(Error::Trap { address: self.registers.cip, reason: "Stack overflow" })
+        }
+
+        self.memory.write_cell(stk, value)?;
+        self.registers.stk = stk;
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i32> {
+        if self.registers.stk + 4 > self.memory_size {
+            return Err(Error::Trap { address: self.registers.cip, reason: "Stack underflow" })
+        }
+
+        let value = self.memory.read_cell(self.registers.stk)?;
+
+        self.registers.stk += 4;
+
+        Ok(value)
+    }
+
+    fn grow_stack(&mut self, address: i32, amount: i32) -> Result<()> {
+        let stk = self.registers.stk.wrapping_add(amount);
+
+        if stk < self.registers.hea + STACK_MARGIN {
+            return Err(Error::Trap { address, reason: "Stack overflow" })
+        }
+
+        if stk > self.memory_size {
+            return Err(Error::Trap { address, reason: "Stack underflow" })
+        }
+
+        self.registers.stk = stk;
+
+        Ok(())
+    }
+
+    fn grow_heap(&mut self, amount: i32) -> Result<()> {
+        let hea = self.registers.hea.wrapping_add(amount);
+
+        if hea < self.data_size || hea > self.registers.stk - STACK_MARGIN {
+            return Err(Error::Trap { address: self.registers.cip, reason: "Heap low" })
+        }
+
+        self.registers.hea = hea;
+
+        Ok(())
+    }
+
+    fn native(&mut self, address: i32, index: i32, argv: i32, argc: i32, natives: &mut dyn NativeHandler) -> Result<i32> {
+        let name = match self.natives.get(index as usize) {
+            Some(name) => name.as_str(),
+            None => return Err(Error::Trap { address, reason: "Invalid native index" }),
+        };
+
+        // |argc| comes from the plugin, so don't preallocate for it.
+        let mut args: Vec<i32> = Vec::new();
+
+        for i in 0..argc {
+            args.push(self.memory.read_cell(argv.wrapping_add(i.wrapping_mul(4)))?);
+        }
+
+        natives.invoke(&mut self.memory, &NativeCall {
+            name,
+            index,
+            address,
+            args: &args,
+        })
+    }
+
+    // Finds the CASETBL at |table| and returns the target for PRI.
+    fn switch_target(&self, address: i32, table: i32) -> Result<i32> {
+        let casetbl = match self.instructions.get(&table) {
+            Some(insn) if insn.info.opcode == V1OPCode::CASETBL => insn,
+            _ => return Err(Error::Trap { address, reason: "Invalid case table" }),
+        };
+
+        let target = casetbl.params[2..].chunks(2)
+            .find(|case| case[0] == self.registers.pri)
+            .map_or(casetbl.params[1], |case| case[1]);
+
+        Ok(target)
+    }
+
+    // Allocates an array on the heap with the dimensions on the stack and
+    // replaces them with its address. Each dimension but the last is an
+    // indirection vector holding, for every element, the offset from that
+    // cell to the element.
+    fn generate_array(&mut self, address: i32, argc: i32, zero: bool) -> Result<()> {
+        let mut dims: Vec<i32> = Vec::new();
+
+        for i in 0..argc {
+            dims.push(self.memory.read_cell(self.registers.stk.wrapping_add(i.wrapping_mul(4)))?);
+        }
+
+        // The compiler pushes the outermost dimension first, so the top of
+        // the stack holds the innermost one.
+        dims.reverse();
+
+        if dims.is_empty() || dims.iter().any(|&dim| dim <= 0) {
+            return Err(Error::Trap { address, reason: "Invalid array size" })
+        }
+
+        // Cells needed by each level, innermost data last.
+        let mut levels: Vec<i64> = Vec::with_capacity(dims.len());
+        let mut count: i64 = 1;
+
+        for &dim in &dims {
+            count *= dim as i64;
+
+            if count > self.memory_size as i64 {
+                return Err(Error::Trap { address, reason: "Invalid array size" })
+            }
+
+            levels.push(count);
+        }
+
+        let cells: i64 = levels.iter().sum();
+
+        if cells * 4 > i32::MAX as i64 {
+            return Err(Error::Trap { address, reason: "Invalid array size" })
+        }
+
+        let base = self.registers.hea;
+
+        self.grow_heap((cells * 4) as i32)?;
+
+        if zero {
+            for i in 0..cells as i32 {
+                self.memory.write_cell(base + i * 4, 0)?;
+            }
+        }
+
+        let mut start = base;
+
+        for (level, &count) in levels.iter().enumerate().take(levels.len() - 1) {
+            let next = start + count as i32 * 4;
+            let dim = dims[level + 1];
+
+            for i in 0..count as i32 {
+                let cell = start + i * 4;
+
+                self.memory.write_cell(cell, next + i * dim * 4 - cell)?;
+            }
+
+            start = next;
+        }
+
+        self.trackers.push((cells * 4) as i32);
+
+        self.registers.stk += (argc - 1) * 4;
+        self.memory.write_cell(self.registers.stk, base)?;
+
+        Ok(())
+    }
+
+    fn branch(next: &mut i32, taken: bool, target: i32) {
+        if taken {
+            *next = target;
+        }
+    }
+
+    fn add_cell(m: &mut Memory, addr: i32, amount: i32) -> Result<()> {
+        let value = m.read_cell(addr)?;
+
+        m.write_cell(addr, value.wrapping_add(amount))
+    }
+
+    fn read_sized(m: &Memory, addr: i32, size: i32, address: i32) -> Result<i32> {
+        Ok(match size {
+            1 => m.read_bytes(addr, 1)?[0] as i32,
+            2 => {
+                let bytes = m.read_bytes(addr, 2)?;
+
+                u16::from_le_bytes([bytes[0], bytes[1]]) as i32
+            },
+            4 => m.read_cell(addr)?,
+            _ => return Err(Error::Trap { address, reason: "Invalid access size" }),
+        })
+    }
+
+    fn write_sized(m: &mut Memory, addr: i32, value: i32, size: i32, address: i32) -> Result<()> {
+        match size {
+            1 | 2 | 4 => m.write_bytes(addr, &value.to_le_bytes()[..size as usize]),
+            _ => Err(Error::Trap { address, reason: "Invalid access size" }),
+        }
+    }
+
+    fn float(cell: i32) -> f32 {
+        f32::from_bits(cell as u32)
+    }
+
+    fn float_op(r: &Registers, op: impl Fn(f32, f32) -> f32) -> i32 {
+        op(Emulator::float(r.pri), Emulator::float(r.alt)).to_bits() as i32
+    }
+}
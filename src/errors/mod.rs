@@ -16,6 +16,15 @@ pub enum Error {
     OffsetOverflow,
     SizeOverflow,
 
+    InvalidOpcode { address: i32, value: i32 },
+    TruncatedInstruction { address: i32 },
+    JumpOutOfRange { address: i32, target: i32 },
+
+    // Raised while emulating a plugin.
+    InvalidAddress { address: i32 },
+    Trap { address: i32, reason: &'static str },
+    UnboundNative { name: String, address: i32 },
+
     Other(&'static str),
 }
 
@@ -35,12 +44,19 @@ impl Display for Error {
             Error::InvalidIndex => write!(f, "Invalid index"),
             Error::OffsetOverflow => write!(f, "Offset overflow"),
             Error::SizeOverflow => write!(f, "Size overflow"),
+            Error::InvalidOpcode { address, value } => write!(f, "Invalid opcode {} at {:#x}", value, address),
+            Error::TruncatedInstruction { address } => write!(f, "Truncated instruction at {:#x}", address),
+            Error::JumpOutOfRange { address, target } => write!(f, "Jump target {:#x} out of range at {:#x}", target, address),
+            Error::InvalidAddress { address } => write!(f, "Invalid memory address {:#x}", address),
+            Error::Trap { address, reason } => write!(f, "{} at {:#x}", reason, address),
+            Error::UnboundNative { ref name, address } => write!(f, "Unbound native {} called at {:#x}", name, address),
             Error::Other(msg) => write!(f, "{}", msg),
         }
     }
 }
 
 impl StdError for Error {
+    #[allow(deprecated)]
     fn description(&self) -> &str {
         match *self {
             Error::Io(ref inner) => inner.description(),
@@ -50,6 +66,12 @@ impl StdError for Error {
             Error::InvalidIndex => "Invalid index",
             Error::OffsetOverflow => "Offset overflow",
             Error::SizeOverflow => "Size overflow",
+            Error::InvalidOpcode { .. } => "Invalid opcode",
+            Error::TruncatedInstruction { .. } => "Truncated instruction",
+            Error::JumpOutOfRange { .. } => "Jump out of range",
+            Error::InvalidAddress { .. } => "Invalid memory address",
+            Error::Trap { reason, .. } => reason,
+            Error::UnboundNative { .. } => "Unbound native",
             Error::Other(msg) => msg,
         }
     }
@@ -1,180 +1,452 @@
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::sync::Arc;
+use std::collections::BTreeMap;
 use crate::headers::{SMXHeader, SectionEntry};
 use crate::sections::*;
 use crate::rtti::*;
-use crate::v1disassembler::V1Disassembler;
-use crate::errors::Result;
+use crate::v1disassembler::{V1Disassembler, V1Function, V1FunctionKind, V1Param, DecodeMode};
+use crate::v1opcodes::V1OPCode;
+use crate::v1types::SymbolIdent;
+use crate::globals::{Global, GlobalDecoder};
+use crate::verifier::{self, Diagnostic};
+use crate::errors::{Result, Error};
 
 #[derive(Default)]
-pub struct SMXFile {
-    pub header: Rc<SMXHeader>,
-    pub unknown_sections: Vec<Rc<SectionEntry>>,
-
-    pub names: Option<Rc<RefCell<SMXNameTable>>>,
-    pub debug_names: Option<Rc<RefCell<SMXNameTable>>>,
-    pub natives: Option<Rc<SMXNativeTable>>,
-    pub publics: Option<Rc<SMXPublicTable>>,
-    pub pubvars: Option<Rc<SMXPubvarTable>>,
-    pub tags: Option<Rc<SMXTagTable>>,
-    pub data: Option<Rc<SMXDataSection>>,
-    pub codev1: Option<Rc<SMXCodeV1Section>>,
-    pub called_functions: Option<Rc<RefCell<SMXCalledFunctionsTable>>>,
-
-    pub debug_info: Option<Rc<SMXDebugInfoSection>>,
-    pub debug_files: Option<Rc<SMXDebugFilesTable>>,
-    pub debug_lines: Option<Rc<SMXDebugLinesTable>>,
-
-    pub rtti_data: Option<Rc<SMXRTTIData>>,
-    pub rtti_enums: Option<Rc<SMXRTTIEnumTable>>,
-    pub rtti_enum_structs: Option<Rc<SMXRTTIEnumStructTable>>,
-    pub rtti_enum_struct_fields: Option<Rc<SMXRTTIEnumStructFieldTable>>,
-    pub rtti_classdefs: Option<Rc<SMXRTTIClassDefTable>>,
-    pub rtti_fields:  Option<Rc<SMXRTTIFieldTable>>,
-    pub rtti_methods: Option<Rc<SMXRTTIMethodTable>>,
-    pub rtti_natives: Option<Rc<SMXRTTINativeTable>>,
-    pub rtti_typedefs: Option<Rc<SMXRTTITypedefTable>>,
-    pub rtti_typesets: Option<Rc<SMXRTTITypesetTable>>,
-
-    pub debug_methods: Option<Rc<SMXDebugMethods>>,
-    pub debug_globals: Option<Rc<RefCell<SMXDebugGlobals>>>,
-    pub debug_locals: Option<Rc<SMXDebugLocals>>,
+pub struct SMXFile<'a> {
+    pub header: Arc<SMXHeader<'a>>,
+    pub unknown_sections: Vec<Arc<SectionEntry>>,
+
+    pub names: Option<Arc<SMXNameTable<'a>>>,
+    pub debug_names: Option<Arc<SMXNameTable<'a>>>,
+    pub natives: Option<Arc<SMXNativeTable>>,
+    pub publics: Option<Arc<SMXPublicTable>>,
+    pub pubvars: Option<Arc<SMXPubvarTable>>,
+    pub tags: Option<Arc<SMXTagTable>>,
+    pub data: Option<Arc<SMXDataSection<'a>>>,
+    pub codev1: Option<Arc<SMXCodeV1Section<'a>>>,
+    pub called_functions: Option<Arc<SMXCalledFunctionsTable>>,
+
+    pub debug_info: Option<Arc<SMXDebugInfoSection>>,
+    pub debug_files: Option<Arc<SMXDebugFilesTable>>,
+    pub debug_lines: Option<Arc<SMXDebugLinesTable>>,
+
+    pub rtti_data: Option<Arc<SMXRTTIData<'a>>>,
+    pub rtti_enums: Option<Arc<SMXRTTIEnumTable>>,
+    pub rtti_enum_structs: Option<Arc<SMXRTTIEnumStructTable>>,
+    pub rtti_enum_struct_fields: Option<Arc<SMXRTTIEnumStructFieldTable>>,
+    pub rtti_classdefs: Option<Arc<SMXRTTIClassDefTable>>,
+    pub rtti_fields:  Option<Arc<SMXRTTIFieldTable>>,
+    pub rtti_methods: Option<Arc<SMXRTTIMethodTable>>,
+    pub rtti_natives: Option<Arc<SMXRTTINativeTable>>,
+    pub rtti_typedefs: Option<Arc<SMXRTTITypedefTable>>,
+    pub rtti_typesets: Option<Arc<SMXRTTITypesetTable>>,
+
+    pub debug_methods: Option<Arc<SMXDebugMethods>>,
+    pub debug_globals: Option<Arc<SMXDebugGlobals>>,
+    pub debug_locals: Option<Arc<SMXDebugLocals>>,
+
+    // Legacy debug tables, from compilers before .dbg.globals and
+    // .dbg.locals.
+    pub debug_symbols: Option<Arc<SMXDebugSymbolsTable>>,
+    pub debug_natives: Option<Arc<SMXDebugNativesTable>>,
+
+    functions: BTreeMap<i32, V1Function>,
 }
 
-impl SMXFile {
-    pub fn new<T>(data: T) -> Result<Rc<RefCell<SMXFile>>>
+impl SMXFile<'static> {
+    pub fn new<T>(data: T) -> Result<Arc<SMXFile<'static>>>
+    where
+        T: AsRef<[u8]>,
+    {
+        SMXFile::new_with_mode(data, DecodeMode::Strict)
+    }
+
+    // Parses a file, disassembling its functions with the given decode mode.
+    // In best effort mode, functions that cannot be disassembled at all are
+    // left out of |functions| instead of failing the whole file.
+    pub fn new_with_mode<T>(data: T, mode: DecodeMode) -> Result<Arc<SMXFile<'static>>>
     where
         T: AsRef<[u8]>,
     {
-        let file: Rc<RefCell<SMXFile>> = Rc::new(RefCell::new(Default::default()));
-
-        {
-            {
-                let file_mut = &mut *file.borrow_mut();
-
-                file_mut.header = Rc::new(SMXHeader::new(&data)?);
-                file_mut.unknown_sections = Vec::new();
-                file_mut.called_functions = Some(Rc::new(RefCell::new(SMXCalledFunctionsTable::new())));
-
-                for section in &file_mut.header.sections {
-                    match section.name.as_ref() {
-                        ".names"  => file_mut.names = Some(Rc::new(RefCell::new(SMXNameTable::new(Rc::clone(&file_mut.header), Rc::clone(&section))))),
-                        ".dbg.strings" => file_mut.debug_names = Some(Rc::new(RefCell::new(SMXNameTable::new(Rc::clone(&file_mut.header), Rc::clone(&section))))),
-                        ".dbg.info" => file_mut.debug_info = Some(Rc::new(SMXDebugInfoSection::new(Rc::clone(&file_mut.header), Rc::clone(&section))?)),
-                        _ => (),
+        SMXFile::parse(SMXHeader::new(data)?, mode)
+    }
+}
+
+impl<'a> SMXFile<'a> {
+    // Parses a file that borrows |data| instead of copying it. Tables are read
+    // in place when the file is not compressed; a compressed file still has
+    // to be inflated into an owned image.
+    pub fn from_slice(data: &'a [u8]) -> Result<Arc<SMXFile<'a>>> {
+        SMXFile::from_slice_with_mode(data, DecodeMode::Strict)
+    }
+
+    pub fn from_slice_with_mode(data: &'a [u8], mode: DecodeMode) -> Result<Arc<SMXFile<'a>>> {
+        SMXFile::parse(SMXHeader::from_slice(data)?, mode)
+    }
+
+    fn parse(header: SMXHeader<'a>, mode: DecodeMode) -> Result<Arc<SMXFile<'a>>> {
+        let header = Arc::new(header);
+
+        let mut file = SMXFile {
+            header: Arc::clone(&header),
+            ..Default::default()
+        };
+
+        for section in &header.sections {
+            match section.name.as_ref() {
+                ".names"  => file.names = Some(Arc::new(SMXNameTable::new(Arc::clone(&header), Arc::clone(section)))),
+                ".dbg.strings" => file.debug_names = Some(Arc::new(SMXNameTable::new(Arc::clone(&header), Arc::clone(section)))),
+                ".dbg.info" => file.debug_info = Some(Arc::new(SMXDebugInfoSection::new(Arc::clone(&header), Arc::clone(section))?)),
+                _ => (),
+            }
+        }
+
+        if file.debug_names.is_none() {
+            file.debug_names = file.names.clone();
+        }
+
+        // Sections that need other tables to be parsed first.
+        let mut rtti_data: Option<&Arc<SectionEntry>> = None;
+        let mut debug_locals: Option<&Arc<SectionEntry>> = None;
+        let mut debug_symbols: Option<&Arc<SectionEntry>> = None;
+
+        // After first pass, we have the name tables
+        for section in &header.sections {
+            match section.name.as_ref() {
+                ".names" | ".dbg.strings" | ".dbg.info" => (),
+                ".natives" => file.natives = Some(Arc::new(SMXNativeTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                ".publics" => file.publics = Some(Arc::new(SMXPublicTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                ".pubvars" => file.pubvars = Some(Arc::new(SMXPubvarTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                ".tags" => file.tags = Some(Arc::new(SMXTagTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                ".data" => file.data = Some(Arc::new(SMXDataSection::new(Arc::clone(&header), Arc::clone(section))?)),
+                ".code" => file.codev1 = Some(Arc::new(SMXCodeV1Section::new(Arc::clone(&header), Arc::clone(section))?)),
+                ".dbg.files" => file.debug_files = Some(Arc::new(SMXDebugFilesTable::new(Arc::clone(&header), Arc::clone(section), file.debug_names()?)?)),
+                ".dbg.lines" => file.debug_lines = Some(Arc::new(SMXDebugLinesTable::new(Arc::clone(&header), Arc::clone(section))?)),
+                ".dbg.symbols" => debug_symbols = Some(section),
+                ".dbg.natives" => file.debug_natives = Some(Arc::new(SMXDebugNativesTable::new(Arc::clone(&header), Arc::clone(section), file.debug_names()?)?)),
+                ".dbg.methods" => file.debug_methods = Some(Arc::new(SMXDebugMethods::new(Arc::clone(&header), Arc::clone(section))?)), // names param is excluded as it's not used
+                ".dbg.globals" => file.debug_globals = Some(Arc::new(SMXDebugGlobals::new(Arc::clone(&header), Arc::clone(section))?)),
+                ".dbg.locals" => debug_locals = Some(section),
+                "rtti.data" => rtti_data = Some(section),
+                "rtti.classdefs" => file.rtti_classdefs = Some(Arc::new(SMXRTTIClassDefTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.enumstructs" => file.rtti_enum_structs = Some(Arc::new(SMXRTTIEnumStructTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.enumstruct_fields" => file.rtti_enum_struct_fields = Some(Arc::new(SMXRTTIEnumStructFieldTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.fields" => file.rtti_fields = Some(Arc::new(SMXRTTIFieldTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.methods" => file.rtti_methods = Some(Arc::new(SMXRTTIMethodTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.natives" => file.rtti_natives = Some(Arc::new(SMXRTTINativeTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.enums" => file.rtti_enums = Some(Arc::new(SMXRTTIEnumTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.typedefs" => file.rtti_typedefs = Some(Arc::new(SMXRTTITypedefTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                "rtti.typesets" => file.rtti_typesets = Some(Arc::new(SMXRTTITypesetTable::new(Arc::clone(&header), Arc::clone(section), file.names()?)?)),
+                _ =>  file.unknown_sections.push(Arc::clone(section)),
+            }
+        }
+
+        if let Some(section) = rtti_data {
+            file.rtti_data = Some(Arc::new(SMXRTTIData::new(&file, Arc::clone(&header), Arc::clone(section))));
+        }
+
+        if let Some(section) = debug_locals {
+            let locals = SMXDebugLocals::new(Arc::clone(&header), Arc::clone(section), file.debug_methods.as_deref(), file.rtti_methods.as_deref())?;
+
+            file.debug_locals = Some(Arc::new(locals));
+        }
+
+        if let Some(section) = debug_symbols {
+            let symbols = SMXDebugSymbolsTable::new(Arc::clone(&header), Arc::clone(section), file.debug_names()?, file.tags.as_deref())?;
+
+            file.debug_symbols = Some(Arc::new(symbols));
+        }
+
+        let mut called_functions = SMXCalledFunctionsTable::new();
+
+        if let Some(code) = file.codev1.clone() {
+            let publics: Vec<(i32, String)> = match &file.publics {
+                Some(publics) => publics.entries_ref().iter().map(|p| (p.address as i32, p.name.clone())).collect(),
+                None => Vec::new(),
+            };
+
+            for (address, name) in publics {
+                file.disassemble_function(&code, &mut called_functions, address, name, V1FunctionKind::Public, &mode)?;
+            }
+
+            // Functions that may never be called directly, such as callbacks,
+            // are still listed in rtti.methods.
+            if let Some(methods) = file.rtti_methods.clone() {
+                for method in methods.methods_ref() {
+                    if code.is_proc_at(method.pcode_start) && !file.is_known_function(&called_functions, method.pcode_start) {
+                        called_functions.add_named_function(method.pcode_start as u32, method.name.clone());
                     }
                 }
+            }
 
-                if file_mut.debug_names.is_none() {
-                    file_mut.debug_names = file_mut.names.clone();
+            // Old plugins have neither, but may have legacy function symbols.
+            if let Some(symbols) = file.debug_symbols.clone() {
+                for sym in symbols.entries_ref().iter().filter(|sym| sym.ident == SymbolIdent::Function) {
+                    if code.is_proc_at(sym.address) && !file.is_known_function(&called_functions, sym.address) {
+                        called_functions.add_named_function(sym.address as u32, sym.name.clone());
+                    }
                 }
+            }
+
+            let main_offset = code.header().main_offset;
+
+            if code.is_proc_at(main_offset) && !file.is_known_function(&called_functions, main_offset) {
+                called_functions.add_named_function(main_offset as u32, "main".into());
+            }
+
+            // Disassembling a function may discover new callees, so walk the
+            // table by index until it stops growing.
+            let mut index: usize = 0;
+
+            while index < called_functions.size() {
+                let fun = called_functions.get_entry(index);
+
+                file.disassemble_function(&code, &mut called_functions, fun.address as i32, fun.name, V1FunctionKind::Called, &mode)?;
+
+                index += 1;
+            }
+        }
+
+        file.called_functions = Some(Arc::new(called_functions));
+
+        Ok(Arc::new(file))
+    }
+
+    fn is_known_function(&self, called_functions: &SMXCalledFunctionsTable, addr: i32) -> bool {
+        self.publics.as_ref().is_some_and(|publics| publics.entries_ref().iter().any(|p| p.address == addr as u32))
+            || called_functions.entries_ref().iter().any(|f| f.address == addr as u32)
+    }
+
+    fn names(&self) -> Result<&SMXNameTable<'a>> {
+        match &self.names {
+            Some(names) => Ok(names),
+            None => Err(Error::Other("Missing .names section")),
+        }
+    }
+
+    fn debug_names(&self) -> Result<&SMXNameTable<'a>> {
+        match &self.debug_names {
+            Some(names) => Ok(names),
+            None => Err(Error::Other("Missing .dbg.strings section")),
+        }
+    }
+
+    // Name of the function at |addr| from rtti.methods or the legacy debug
+    // symbols.
+    fn symbol_name(&self, addr: i32) -> Option<String> {
+        if let Some(methods) = &self.rtti_methods {
+            if let Some(method) = methods.methods_ref().iter().find(|m| m.pcode_start == addr) {
+                return Some(method.name.clone())
+            }
+        }
+
+        Some(self.debug_symbols.as_ref()?.find_function(addr)?.name)
+    }
 
-                // After first pass, we have the name tables
-                for section in &file_mut.header.sections {
-                    match section.name.as_ref() {
-                        ".names" | ".dbg.strings" | ".dbg.info" => (),
-                        ".natives" => file_mut.natives = Some(Rc::new(SMXNativeTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        ".publics" => file_mut.publics = Some(Rc::new(SMXPublicTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        ".pubvars" => file_mut.pubvars = Some(Rc::new(SMXPubvarTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        ".tags" => file_mut.tags = Some(Rc::new(SMXTagTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        ".data" => file_mut.data = Some(Rc::new(SMXDataSection::new(Rc::clone(&file_mut.header), Rc::clone(&section))?)),
-                        ".code" => file_mut.codev1 = Some(Rc::new(SMXCodeV1Section::new(Rc::clone(&file_mut.header), Rc::clone(&section))?)),
-                        ".dbg.files" => file_mut.debug_files = Some(Rc::new(SMXDebugFilesTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        ".dbg.lines" => file_mut.debug_lines = Some(Rc::new(SMXDebugLinesTable::new(Rc::clone(&file_mut.header), Rc::clone(&section))?)),
-                        // .dbg.natives and .dbg.symbols is unimplemented due to being legacy
-                        ".dbg.methods" => file_mut.debug_methods = Some(Rc::new(SMXDebugMethods::new(Rc::clone(&file_mut.header), Rc::clone(&section))?)), // names param is excluded as it's not used
-                        ".dbg.globals" => file_mut.debug_globals = Some(Rc::new(RefCell::new(SMXDebugGlobals::new(Rc::clone(&file_mut.header), Rc::clone(&section))?))),
-                        ".dbg.locals" => file_mut.debug_locals = Some(Rc::new(SMXDebugLocals::new(Rc::clone(&file), Rc::clone(&file_mut.header), Rc::clone(&section))?)),
-                        "rtti.data" => file_mut.rtti_data = Some(Rc::new(SMXRTTIData::new(Rc::clone(&file), Rc::clone(&file_mut.header), Rc::clone(&section)))),
-                        "rtti.classdefs" => file_mut.rtti_classdefs = Some(Rc::new(SMXRTTIClassDefTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.enumstructs" => file_mut.rtti_enum_structs = Some(Rc::new(SMXRTTIEnumStructTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.enumstruct_fields" => file_mut.rtti_enum_struct_fields = Some(Rc::new(SMXRTTIEnumStructFieldTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.fields" => file_mut.rtti_fields = Some(Rc::new(SMXRTTIFieldTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.methods" => file_mut.rtti_methods = Some(Rc::new(SMXRTTIMethodTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.natives" => file_mut.rtti_natives = Some(Rc::new(SMXRTTINativeTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.enums" => file_mut.rtti_enums = Some(Rc::new(SMXRTTIEnumTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.typedefs" => file_mut.rtti_typedefs = Some(Rc::new(SMXRTTITypedefTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        "rtti.typesets" => file_mut.rtti_typesets = Some(Rc::new(SMXRTTITypesetTable::new(Rc::clone(&file_mut.header), Rc::clone(&section), Rc::clone(file_mut.names.as_ref().unwrap()))?)),
-                        _ =>  file_mut.unknown_sections.push(Rc::clone(&section)),
+    fn disassemble_function(&mut self, code: &Arc<SMXCodeV1Section<'a>>, called_functions: &mut SMXCalledFunctionsTable, address: i32, name: String, kind: V1FunctionKind, mode: &DecodeMode) -> Result<()> {
+        if self.functions.contains_key(&address) {
+            return Ok(())
+        }
+
+        let function = match V1Disassembler::diassemble_function(&self.header.data, Arc::clone(code), address, name, kind, mode.clone()) {
+            Ok(function) => function,
+            Err(_) if *mode == DecodeMode::BestEffort => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for insn in &function.instructions {
+            if insn.info.opcode == V1OPCode::CASETBL {
+                continue;
+            }
+
+            // Only these load a constant as a value. Other constant operands
+            // are sizes, counts or offsets.
+            let loads_value = matches!(insn.info.opcode,
+                V1OPCode::CONST_PRI | V1OPCode::CONST_ALT | V1OPCode::CONST |
+                V1OPCode::PUSH_C | V1OPCode::PUSH2_C | V1OPCode::PUSH3_C | V1OPCode::PUSH4_C | V1OPCode::PUSH5_C);
+
+            for (param, &value) in insn.info.params.iter().zip(insn.params.iter()) {
+                // Call targets and function addresses loaded with LDGFN.PRI
+                // are always functions. A constant value is taken as a
+                // function reference, e.g. a callback, when it lands exactly
+                // on a PROC.
+                let is_reference = match param {
+                    V1Param::Function => true,
+                    V1Param::Constant => loads_value && code.is_proc_at(value),
+                    _ => false,
+                };
+
+                if is_reference && !self.is_known_function(called_functions, value) {
+                    match self.symbol_name(value) {
+                        Some(name) => called_functions.add_named_function(value as u32, name),
+                        None => called_functions.add_function(value as u32),
                     }
                 }
             }
+        }
 
-            // Legacy debug symbols table is skipped
+        self.functions.insert(address, function);
 
-            if file.borrow_mut().publics.is_some() {
-                for pubfun in file.borrow().publics.as_ref().unwrap().entries_ref() {
-                    V1Disassembler::diassemble(Rc::clone(&file), file.borrow().header.data.clone(), Rc::clone(file.borrow().codev1.as_ref().unwrap()), pubfun.address as i32)?;
-                }
+        Ok(())
+    }
+
+    // Returns every disassembled function, keyed by code address.
+    pub fn functions(&self) -> &BTreeMap<i32, V1Function> {
+        &self.functions
+    }
+
+    pub fn function_at(&self, addr: i32) -> Option<&V1Function> {
+        self.functions.get(&addr)
+    }
+
+    // Returns the source file and 1-based line of the code at |addr|.
+    pub fn source_location(&self, addr: i32) -> Option<(String, u32)> {
+        if addr < 0 {
+            return None
+        }
+
+        let file = self.debug_files.as_ref()?.find_file(addr as u32)?;
+        let line = self.debug_lines.as_ref()?.find_line(addr as u32)?;
+
+        Some((file, line))
+    }
+
+    // Returns the start address of every line table entry for |line| of
+    // |file|. |file| is either the full path stored in .dbg.files or just its
+    // file name.
+    pub fn addresses_for_line(&self, file: &str, line: u32) -> Vec<i32> {
+        let (files, lines) = match (&self.debug_files, &self.debug_lines) {
+            (Some(files), Some(lines)) => (files, lines),
+            _ => return Vec::new(),
+        };
+
+        lines.entries().iter()
+            .filter(|entry| entry.line + 1 == line)
+            .filter(|entry| files.find_file(entry.address).is_some_and(|name| SMXFile::same_file(&name, file)))
+            .map(|entry| entry.address as i32)
+            .collect()
+    }
+
+    // Returns the last component of a path stored in .dbg.files, which may
+    // use either separator.
+    pub fn file_name(path: &str) -> &str {
+        path.rsplit(['/', '\\']).next().unwrap_or(path)
+    }
+
+    fn same_file(path: &str, file: &str) -> bool {
+        path == file || SMXFile::file_name(path) == file
+    }
+
+    // Decodes every global in .dbg.globals with its RTTI type and initial
+    // value from .data.
+    pub fn globals(&self) -> Result<Vec<Global>> {
+        GlobalDecoder::new(self)?.globals()
+    }
+
+    // Validates the code the way the VM does before running it. An empty
+    // list means the plugin passed every check.
+    pub fn verify(&self) -> Vec<Diagnostic> {
+        verifier::verify(self)
+    }
+
+    // Returns the functions found by a linear sweep that are not reachable
+    // from publics, rtti.methods, main or any function reference. They are
+    // named from rtti.methods when possible.
+    pub fn unreachable_functions(&self) -> Vec<V1Function> {
+        let code = match &self.codev1 {
+            Some(code) => code,
+            None => return Vec::new(),
+        };
+
+        let mut functions: Vec<V1Function> = Vec::new();
+
+        for mut function in code.sweep().functions {
+            if self.functions.contains_key(&function.address) {
+                continue;
             }
 
-            if file.borrow().called_functions.is_some() {
-                for fun in file.borrow().called_functions.as_ref().unwrap().borrow().entries_ref() {
-                    V1Disassembler::diassemble(Rc::clone(&file), file.borrow().header.data.clone(), Rc::clone(file.borrow().codev1.as_ref().unwrap()), fun.address as i32)?;
+            if let Some(methods) = &self.rtti_methods {
+                if let Some(method) = methods.methods_ref().iter().find(|m| m.pcode_start == function.address) {
+                    function.name = method.name.clone();
                 }
             }
+
+            functions.push(function);
         }
 
-        Ok(file)
+        functions
     }
 
-    pub fn find_global_name(&mut self, addr: i32) -> Option<String> {
-        if self.debug_globals.is_some() {
-            let sym = self.debug_globals.as_mut().unwrap().borrow_mut().find_global(addr);
+    pub fn find_global_name(&self, addr: i32) -> Option<String> {
+        if let Some(globals) = &self.debug_globals {
+            let sym = globals.find_global(addr);
 
             if let Some(symsome) = sym {
-                return Some(self.names.as_mut().unwrap().borrow_mut().string_at(symsome.name_offset).unwrap());
+                return Some(self.names.as_ref()?.string_at(symsome.name_offset).unwrap());
             }
         }
 
+        if let Some(symbols) = &self.debug_symbols {
+            return symbols.find_global(addr).map(|sym| sym.name);
+        }
+
         None
     }
 
-    pub fn find_local_name(&mut self, code_addr: i32, addr: i32) -> Option<String> {
-        if self.debug_locals.is_some() {
-            let entry = self.debug_locals.as_ref().unwrap().find_local(code_addr, addr);
+    pub fn find_local_name(&self, code_addr: i32, addr: i32) -> Option<String> {
+        if let Some(locals) = &self.debug_locals {
+            let entry = locals.find_local(code_addr, addr);
 
             if let Some(entrysome) = entry {
-                return Some(self.names.as_mut().unwrap().borrow_mut().string_at(entrysome.name_offset).unwrap());
+                return Some(self.names.as_ref()?.string_at(entrysome.name_offset).unwrap());
             }
         }
 
+        if let Some(symbols) = &self.debug_symbols {
+            return symbols.find_local(code_addr, addr).map(|sym| sym.name);
+        }
+
         None
     }
 
     pub fn find_function_name(&self, addr: i32) -> String {
-        if self.publics.is_some() {
-            for pubfun in self.publics.as_ref().unwrap().entries_ref() {
+        if let Some(publics) = &self.publics {
+            for pubfun in publics.entries_ref() {
                 if pubfun.address == addr as u32 {
                     return pubfun.name.clone();
                 }
             }
         }
 
-        if self.called_functions.is_some() {
-            for fun in self.called_functions.as_ref().unwrap().borrow().entries_ref() {
+        if let Some(called_functions) = &self.called_functions {
+            for fun in called_functions.entries_ref() {
                 if fun.address == addr as u32 {
                     return fun.name.clone();
                 }
             }
         }
 
+        if let Some(sym) = self.debug_symbols.as_ref().and_then(|symbols| symbols.find_function(addr)) {
+            return sym.name;
+        }
+
         "unknown".into()
     }
 
     pub fn is_function_at_address(&self, addr: i32) -> bool {
-        // Legacy debug symbols is unimplemented
+        if self.debug_symbols.as_ref().is_some_and(|symbols| symbols.find_function(addr).is_some()) {
+            return true;
+        }
 
-        if self.publics.is_some() {
-            for pubfun in self.publics.as_ref().unwrap().entries_ref() {
+        if let Some(publics) = &self.publics {
+            for pubfun in publics.entries_ref() {
                 if pubfun.address == addr as u32 {
                     return true;
                 }
             }
         }
 
-        if self.called_functions.is_some() {
-            for fun in self.called_functions.as_ref().unwrap().borrow().entries_ref() {
+        if let Some(called_functions) = &self.called_functions {
+            for fun in called_functions.entries_ref() {
                 if fun.address == addr as u32 {
                     return true;
                 }
@@ -0,0 +1,278 @@
+use std::fmt;
+use crate::file::SMXFile;
+use crate::rtti::{SMXRTTIData, QualifiedType, Type, TypeFlags};
+use crate::strings::StringLiterals;
+use crate::errors::{Result, Error};
+
+// The initial value of a global, decoded according to its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+
+    // Ints, and anything else that fits in a cell: enums, handles, chars and
+    // function ids.
+    Int(i32),
+
+    Float(f32),
+
+    // A char array, up to its first null character.
+    String(String),
+
+    Array(Vec<Value>),
+
+    // Fields of a struct or an enum struct, in declaration order.
+    Struct(Vec<(String, Value)>),
+
+    // Outside of the plugin's memory, or a type without a known size.
+    Unknown,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{}", StringLiterals::quote(value)),
+            Value::Array(values) => {
+                let items: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+
+                write!(f, "{{{}}}", items.join(", "))
+            },
+            Value::Struct(fields) => {
+                let items: Vec<String> = fields.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+
+                write!(f, "{{{}}}", items.join(", "))
+            },
+            Value::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+
+    // Address in the data section.
+    pub address: i32,
+
+    // any when the type_id does not decode.
+    pub ty: QualifiedType,
+
+    pub value: Value,
+}
+
+// Reads the initial values of globals out of .data. Multi-dimensional arrays
+// and arrays of enum structs start with an indirection vector: one cell per
+// element, holding the offset from that cell to the element.
+pub struct GlobalDecoder<'a> {
+    file: &'a SMXFile<'a>,
+    rtti: &'a SMXRTTIData<'a>,
+    data: &'a [u8],
+    memory_size: i32,
+}
+
+impl<'a> GlobalDecoder<'a> {
+    pub fn new(file: &'a SMXFile<'a>) -> Result<Self> {
+        let rtti = match &file.rtti_data {
+            Some(rtti) => rtti.as_ref(),
+            None => return Err(Error::Other("Missing rtti.data section")),
+        };
+
+        let (data, memory_size) = match &file.data {
+            Some(data) => (data.data(), data.header().memory_size as i32),
+            None => return Err(Error::Other("Missing .data section")),
+        };
+
+        Ok(Self {
+            file,
+            rtti,
+            data,
+            memory_size,
+        })
+    }
+
+    // Every global in .dbg.globals, in address order. A global whose type
+    // does not decode has an Unknown value.
+    pub fn globals(&self) -> Result<Vec<Global>> {
+        let (symbols, names) = match (&self.file.debug_globals, &self.file.names) {
+            (Some(symbols), Some(names)) => (symbols.symbol_entries(), names),
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut globals: Vec<Global> = Vec::with_capacity(symbols.len());
+
+        for sym in &symbols {
+            let (ty, value) = match self.rtti.type_from_id(sym.type_id) {
+                Ok(ty) => {
+                    let value = self.decode(&ty.ty, sym.address).unwrap_or(Value::Unknown);
+
+                    (ty, value)
+                },
+                Err(_) => (QualifiedType { ty: Type::Any, flags: TypeFlags::empty() }, Value::Unknown),
+            };
+
+            globals.push(Global {
+                name: names.string_at(sym.name_offset)?,
+                address: sym.address,
+                ty,
+                value,
+            });
+        }
+
+        globals.sort_by_key(|global| global.address);
+
+        Ok(globals)
+    }
+
+    // Decodes a value of type |ty| stored in place at |addr|.
+    pub fn decode(&self, ty: &Type, addr: i32) -> Result<Value> {
+        let cell = match self.read_cell(addr) {
+            Some(cell) => cell,
+            None => return Ok(Value::Unknown),
+        };
+
+        Ok(match ty {
+            Type::Bool => Value::Bool(cell != 0),
+            Type::Float => Value::Float(f32::from_bits(cell as u32)),
+            Type::FixedArray(inner, size) => self.decode_array(inner, *size, addr)?,
+            Type::Array(inner) if **inner == Type::Char => Value::String(self.read_string(addr, None)),
+            Type::Array(_) => Value::Unknown,
+            Type::Struct { index, .. } => self.decode_struct(*index, addr)?,
+            Type::EnumStruct { index, .. } => self.decode_enum_struct(*index, addr)?,
+            _ => Value::Int(cell),
+        })
+    }
+
+    fn decode_array(&self, inner: &Type, size: i32, addr: i32) -> Result<Value> {
+        // Char arrays are sized in bytes, rounded up to whole cells.
+        let extent = match inner {
+            Type::Char => (size as i64 + 3) / 4 * 4,
+            _ => size as i64 * 4,
+        };
+
+        if size < 0 || addr as i64 + extent > self.memory_size as i64 {
+            return Ok(Value::Unknown)
+        }
+
+        if *inner == Type::Char {
+            return Ok(Value::String(self.read_string(addr, Some(size as usize))))
+        }
+
+        let indirect = matches!(inner, Type::FixedArray(..) | Type::Array(_) | Type::EnumStruct { .. });
+
+        let mut values: Vec<Value> = Vec::with_capacity(size as usize);
+
+        for i in 0..size {
+            let element = addr + i * 4;
+
+            let value = if indirect {
+                match self.read_cell(element) {
+                    Some(offset) => self.decode(inner, element.wrapping_add(offset))?,
+                    None => Value::Unknown,
+                }
+            } else {
+                self.decode(inner, element)?
+            };
+
+            values.push(value);
+        }
+
+        Ok(Value::Array(values))
+    }
+
+    // Struct fields are one cell each. Strings are stored as the address of
+    // the characters.
+    fn decode_struct(&self, index: i32, addr: i32) -> Result<Value> {
+        let (defs, fields) = match (&self.file.rtti_classdefs, &self.file.rtti_fields) {
+            (Some(defs), Some(fields)) => (defs.defs(), fields.fields()),
+            _ => return Ok(Value::Unknown),
+        };
+
+        let def = match defs.get(index as usize) {
+            Some(def) => def,
+            None => return Err(Error::InvalidIndex),
+        };
+
+        let last = match defs.get(index as usize + 1) {
+            Some(next) => next.first_field as usize,
+            None => fields.len(),
+        };
+
+        let mut values: Vec<(String, Value)> = Vec::new();
+
+        for (i, field) in fields.iter().take(last).skip(def.first_field as usize).enumerate() {
+            let t = self.rtti.type_from_id(field.type_id)?;
+            let slot = addr + i as i32 * 4;
+
+            let value = match (&t.ty, self.read_cell(slot)) {
+                (Type::Array(inner), Some(ptr)) if **inner == Type::Char => Value::String(self.read_string(ptr, None)),
+                (Type::Array(_) | Type::FixedArray(..), _) | (_, None) => Value::Unknown,
+                _ => self.decode(&t.ty, slot)?,
+            };
+
+            values.push((field.name.clone(), value));
+        }
+
+        Ok(Value::Struct(values))
+    }
+
+    // Enum struct fields are stored in place, at a byte offset from the start.
+    fn decode_enum_struct(&self, index: i32, addr: i32) -> Result<Value> {
+        let (enum_structs, fields) = match (&self.file.rtti_enum_structs, &self.file.rtti_enum_struct_fields) {
+            (Some(enum_structs), Some(fields)) => (enum_structs.entries(), fields.entries()),
+            _ => return Ok(Value::Unknown),
+        };
+
+        let es = match enum_structs.get(index as usize) {
+            Some(es) => es,
+            None => return Err(Error::InvalidIndex),
+        };
+
+        let last = match enum_structs.get(index as usize + 1) {
+            Some(next) => next.first_field as usize,
+            None => fields.len(),
+        };
+
+        let mut values: Vec<(String, Value)> = Vec::new();
+
+        for field in fields.iter().take(last).skip(es.first_field as usize) {
+            let t = self.rtti.type_from_id(field.type_id)?;
+
+            values.push((field.name.clone(), self.decode(&t.ty, addr + field.offset)?));
+        }
+
+        Ok(Value::Struct(values))
+    }
+
+    // Cells past the end of .data are zero until the plugin runs.
+    fn read_cell(&self, addr: i32) -> Option<i32> {
+        if addr < 0 || addr % 4 != 0 || addr >= self.memory_size {
+            return None
+        }
+
+        let start = addr as usize;
+
+        match self.data.get(start..start + 4) {
+            Some(cell) => Some(i32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]])),
+            None => Some(0),
+        }
+    }
+
+    // Reads a packed string of at most |size| bytes.
+    fn read_string(&self, addr: i32, size: Option<usize>) -> String {
+        if addr < 0 || addr as usize >= self.data.len() {
+            return String::new()
+        }
+
+        let mut bytes = &self.data[addr as usize..];
+
+        if let Some(size) = size {
+            bytes = &bytes[..size.min(bytes.len())];
+        }
+
+        let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    }
+}
@@ -1,21 +1,19 @@
-use std::rc::Rc;
+use std::sync::Arc;
+use std::borrow::Cow;
 use std::io::{Read, Seek, SeekFrom, Cursor};
 use byteorder::{ReadBytesExt, LittleEndian};
 use flate2::read::ZlibDecoder;
 use std::fmt;
 use crate::errors::{Result, Error};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum CompressionType {
     CompressionNone,
+    #[default]
     CompressionGZ,
     CompressionUnknown,
 }
 
-impl Default for CompressionType {
-    fn default() -> Self { CompressionType::CompressionGZ }
-}
-
 impl From<u8> for CompressionType {
     fn from(byte: u8) -> Self {
         match byte {
@@ -37,7 +35,7 @@ impl fmt::Display for CompressionType {
 }
 
 #[derive(Clone, Default)]
-pub struct SMXHeader {
+pub struct SMXHeader<'a> {
     pub magic: u32,
 
     pub version: u16,
@@ -82,10 +80,11 @@ pub struct SMXHeader {
     // Offset to where compression begins (explained above).
     pub data_offset: i32,
 
-    // The computed data buffer (which contains the header).
-    pub data: Vec<u8>,
+    // The computed data buffer (which contains the header). Borrowed from the
+    // input when it is not compressed.
+    pub data: Cow<'a, [u8]>,
 
-    pub sections: Vec<Rc<SectionEntry>>,
+    pub sections: Vec<Arc<SectionEntry>>,
 
     pub debug_packed: bool,
 }
@@ -113,7 +112,7 @@ impl ReadCString for Cursor<&[u8]> {
     }
 }
 
-impl SMXHeader {
+impl<'a> SMXHeader<'a> {
     // SourcePawn File Format magic number.
     pub const FILE_MAGIC: u32 = 0x5350_4646;
 
@@ -126,16 +125,21 @@ impl SMXHeader {
     // version higher than the current version should be rejected.
     pub const SP1_VERSION_1_0: u16 = 0x0101;
     pub const SP1_VERSION_1_1: u16 = 0x0102;
-    pub const SP1_VERSION_MIN: u16 = SMXHeader::SP1_VERSION_1_0;
-    pub const SP1_VERSION_MAX: u16 = SMXHeader::SP1_VERSION_1_1;
+    pub const SP1_VERSION_MIN: u16 = Self::SP1_VERSION_1_0;
+    pub const SP1_VERSION_MAX: u16 = Self::SP1_VERSION_1_1;
 
     // Size of the header.
-    const HEADER_SIZE: i32 = 24;
+    pub const HEADER_SIZE: i32 = 24;
 
-    pub fn new<T>(data: T) -> Result<SMXHeader>
+    pub fn new<T>(data: T) -> Result<SMXHeader<'static>>
     where
         T: AsRef<[u8]>,
     {
+        Ok(SMXHeader::from_slice(data.as_ref())?.into_owned())
+    }
+
+    // Parses a header without copying the image when it is not compressed.
+    pub fn from_slice(data: &'a [u8]) -> Result<SMXHeader<'a>> {
         let mut data = Cursor::new(data);
 
         let magic = data.read_u32::<LittleEndian>()?;
@@ -174,40 +178,48 @@ impl SMXHeader {
             return Err(Error::InvalidOffset)
         }
 
-        let mut p_data: Vec<u8> = Vec::with_capacity(image_size as usize);
-
-        p_data.extend(&data.get_ref().as_ref()[..SMXHeader::HEADER_SIZE as usize]);
+        let input: &'a [u8] = data.get_ref();
 
-        match compression_type {
+        let image: Cow<'a, [u8]> = match compression_type {
             CompressionType::CompressionNone => {
-                p_data.extend(&data.get_ref().as_ref()[SMXHeader::HEADER_SIZE as usize..image_size as usize]);
+                if image_size as usize > input.len() {
+                    return Err(Error::InvalidSize)
+                }
+
+                Cow::Borrowed(&input[..image_size as usize])
             },
             CompressionType::CompressionGZ => {
-                p_data.extend(&data.get_ref().as_ref()[SMXHeader::HEADER_SIZE as usize..data_offset as usize]);
+                if data_offset as usize > input.len() {
+                    return Err(Error::InvalidOffset)
+                }
+
+                let mut p_data: Vec<u8> = Vec::with_capacity(image_size as usize);
 
-                let mut decoder = ZlibDecoder::new(&data.get_ref().as_ref()[data_offset as usize..]);
+                p_data.extend(&input[..data_offset as usize]);
+
+                let mut decoder = ZlibDecoder::new(&input[data_offset as usize..]);
 
                 decoder.read_to_end(&mut p_data)?;
+
+                Cow::Owned(p_data)
             }
             _ => {
                 return Err(Error::Other("Unknown compression"))
             }
-        }
+        };
 
-        let cloned_data = p_data.clone();
-
-        let mut new_data = Cursor::new(p_data);
+        let mut new_data = Cursor::new(image.as_ref());
 
         new_data.seek(SeekFrom::Start(SMXHeader::HEADER_SIZE as u64))?;
 
-        let mut sections: Vec<Rc<SectionEntry>> = Vec::with_capacity(section_count as usize);
+        let mut sections: Vec<Arc<SectionEntry>> = Vec::with_capacity(section_count as usize);
 
         let mut found_dbg_section: bool = false;
 
         for _ in 0..section_count {
             let name_offset: i32;
 
-            sections.push(Rc::new(SectionEntry{
+            sections.push(Arc::new(SectionEntry {
                 name_offset: {
                     name_offset = new_data.read_i32::<LittleEndian>()?;
 
@@ -236,7 +248,13 @@ impl SMXHeader {
                     size
                 },
                 name: {
-                    let mut cursor = Cursor::new(&cloned_data[string_table_offset as usize + name_offset as usize..]);
+                    let start = string_table_offset as usize + name_offset as usize;
+
+                    if start >= image.len() {
+                        return Err(Error::OffsetOverflow)
+                    }
+
+                    let mut cursor = Cursor::new(&image[start..]);
 
                     let name = cursor.read_cstring()?;
 
@@ -246,7 +264,13 @@ impl SMXHeader {
 
                     name
                 }
-            }))
+            }));
+
+            let section = sections.last().unwrap();
+
+            if section.data_offset as usize + section.size as usize > image.len() {
+                return Err(Error::SizeOverflow)
+            }
         }
 
         Ok(SMXHeader{
@@ -258,12 +282,34 @@ impl SMXHeader {
             section_count,
             string_table_offset,
             data_offset,
-            data: cloned_data,
+            data: image,
             sections,
             debug_packed: (version == SMXHeader::SP1_VERSION_1_0) && !found_dbg_section,
         })
     }
 
+    // Returns true if the image is borrowed from the input.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.data, Cow::Borrowed(_))
+    }
+
+    // Copies a borrowed image so the header no longer depends on the input.
+    pub fn into_owned(self) -> SMXHeader<'static> {
+        SMXHeader {
+            magic: self.magic,
+            version: self.version,
+            compression_type: self.compression_type,
+            disk_size: self.disk_size,
+            image_size: self.image_size,
+            section_count: self.section_count,
+            string_table_offset: self.string_table_offset,
+            data_offset: self.data_offset,
+            data: Cow::Owned(self.data.into_owned()),
+            sections: self.sections,
+            debug_packed: self.debug_packed,
+        }
+    }
+
     // fn string_at(&self, index: usize) -> Result<String> {
     //     let mut data = Cursor::new(&self.data[self.string_table_offset as usize + index..]);
 
@@ -271,7 +317,7 @@ impl SMXHeader {
     // }
 }
 
-impl fmt::Debug for SMXHeader {
+impl fmt::Debug for SMXHeader<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Magic: {}", self.magic)?;
         writeln!(f, "Version: {}", self.version)?;
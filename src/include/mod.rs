@@ -0,0 +1,248 @@
+use crate::file::SMXFile;
+use crate::rtti::{SMXRTTIData, FunctionType, QualifiedType, Type, TypeFlags};
+use crate::errors::{Result, Error};
+
+// Generates SourcePawn include declarations from the RTTI tables of a
+// compiled plugin. Argument names are not stored in RTTI, so arguments are
+// named by position.
+pub struct IncludeGenerator<'a> {
+    file: &'a SMXFile<'a>,
+}
+
+impl<'a> IncludeGenerator<'a> {
+    pub fn new(file: &'a SMXFile<'a>) -> Self {
+        Self {
+            file,
+        }
+    }
+
+    fn rtti(&self) -> Result<&SMXRTTIData<'a>> {
+        match &self.file.rtti_data {
+            Some(rtti) => Ok(rtti.as_ref()),
+            None => Err(Error::Other("Missing rtti.data section")),
+        }
+    }
+
+    // Renders a variable declaration, e.g. "const char[] name" or "int name[4]".
+    pub fn declare(t: &QualifiedType, name: &str) -> String {
+        let mut dims = String::new();
+        let mut inner = &t.ty;
+
+        while let Type::FixedArray(element, size) = inner {
+            dims.push_str(&format!("[{}]", size));
+            inner = element;
+        }
+
+        let mut text = String::new();
+
+        if t.flags.contains(TypeFlags::CONST) {
+            text.push_str("const ");
+        }
+
+        text.push_str(&inner.to_string());
+
+        if t.flags.contains(TypeFlags::BYREF) {
+            text.push('&');
+        }
+
+        text.push(' ');
+        text.push_str(name);
+        text.push_str(&dims);
+
+        text
+    }
+
+    // Renders "<return type> <name>(<args>)".
+    pub fn prototype(name: &str, signature: &FunctionType) -> String {
+        let return_type = match &signature.return_type {
+            Some(t) => t.to_string(),
+            None => "void".into(),
+        };
+
+        let mut args: Vec<String> = Vec::with_capacity(signature.args.len());
+
+        for (i, arg) in signature.args.iter().enumerate() {
+            if signature.variadic && i == signature.args.len() - 1 {
+                args.push(format!("{} ...", arg));
+            } else {
+                args.push(IncludeGenerator::declare(arg, &format!("arg{}", i)));
+            }
+        }
+
+        format!("{} {}({})", return_type, name, args.join(", "))
+    }
+
+    pub fn natives(&self) -> Result<String> {
+        let mut out = String::new();
+
+        if let Some(natives) = &self.file.rtti_natives {
+            for native in &natives.natives() {
+                let signature = self.rtti()?.function_type_from_offset(native.signature)?;
+
+                out.push_str(&format!("native {};\n", IncludeGenerator::prototype(&native.name, &signature)));
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn typedefs(&self) -> Result<String> {
+        let mut out = String::new();
+
+        if let Some(typedefs) = &self.file.rtti_typedefs {
+            for typedef in &typedefs.typedefs() {
+                let t = self.rtti()?.type_from_id(typedef.type_id)?;
+
+                let text = match &t.ty {
+                    Type::Function(signature) => IncludeGenerator::function_type(signature),
+                    _ => t.to_string(),
+                };
+
+                out.push_str(&format!("typedef {} = {};\n", typedef.name, text));
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn typesets(&self) -> Result<String> {
+        let mut out = String::new();
+
+        if let Some(typesets) = &self.file.rtti_typesets {
+            for typeset in &typesets.typesets() {
+                out.push_str(&format!("typeset {}\n{{\n", typeset.name));
+
+                for t in &self.rtti()?.typeset_types_from_offset(typeset.signature)? {
+                    let text = match &t.ty {
+                        Type::Function(signature) => IncludeGenerator::function_type(signature),
+                        _ => t.to_string(),
+                    };
+
+                    out.push_str(&format!("    {};\n", text));
+                }
+
+                out.push_str("};\n\n");
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn enum_structs(&self) -> Result<String> {
+        let mut out = String::new();
+
+        let (enum_structs, fields) = match (&self.file.rtti_enum_structs, &self.file.rtti_enum_struct_fields) {
+            (Some(enum_structs), Some(fields)) => (enum_structs.entries(), fields.entries()),
+            _ => return Ok(out),
+        };
+
+        for (i, es) in enum_structs.iter().enumerate() {
+            let last = match enum_structs.get(i + 1) {
+                Some(next) => next.first_field as usize,
+                None => fields.len(),
+            };
+
+            out.push_str(&format!("enum struct {}\n{{\n", es.name));
+
+            for field in fields.iter().take(last).skip(es.first_field as usize) {
+                let t = self.rtti()?.type_from_id(field.type_id)?;
+
+                out.push_str(&format!("    {};\n", IncludeGenerator::declare(&t, &field.name)));
+            }
+
+            out.push_str("}\n\n");
+        }
+
+        Ok(out)
+    }
+
+    pub fn structs(&self) -> Result<String> {
+        let mut out = String::new();
+
+        let (defs, fields) = match (&self.file.rtti_classdefs, &self.file.rtti_fields) {
+            (Some(defs), Some(fields)) => (defs.defs(), fields.fields()),
+            _ => return Ok(out),
+        };
+
+        for (i, def) in defs.iter().enumerate() {
+            let last = match defs.get(i + 1) {
+                Some(next) => next.first_field as usize,
+                None => fields.len(),
+            };
+
+            out.push_str(&format!("struct {}\n{{\n", def.name));
+
+            for field in fields.iter().take(last).skip(def.first_field as usize) {
+                let t = self.rtti()?.type_from_id(field.type_id)?;
+
+                out.push_str(&format!("    public {};\n", IncludeGenerator::declare(&t, &field.name)));
+            }
+
+            out.push_str("};\n\n");
+        }
+
+        Ok(out)
+    }
+
+    // Forward declarations for every named public function that has an RTTI
+    // signature. Publics named ".<addr>.<name>" are compiler generated and
+    // are skipped.
+    pub fn forwards(&self) -> Result<String> {
+        let mut out = String::new();
+
+        let (publics, methods) = match (&self.file.publics, &self.file.rtti_methods) {
+            (Some(publics), Some(methods)) => (publics, methods),
+            _ => return Ok(out),
+        };
+
+        for pubfun in publics.entries_ref() {
+            if pubfun.name.starts_with('.') {
+                continue;
+            }
+
+            let method = methods.methods_ref().iter().find(|m| m.pcode_start == pubfun.address as i32);
+
+            if let Some(method) = method {
+                let signature = self.rtti()?.function_type_from_offset(method.signature)?;
+
+                out.push_str(&format!("forward {};\n", IncludeGenerator::prototype(&pubfun.name, &signature)));
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn generate(&self) -> Result<String> {
+        let mut out = String::new();
+
+        let parts: [(&str, String); 6] = [
+            ("Type definitions", self.typedefs()?),
+            ("Type sets", self.typesets()?),
+            ("Structs", self.structs()?),
+            ("Enum structs", self.enum_structs()?),
+            ("Natives", self.natives()?),
+            ("Forwards", self.forwards()?),
+        ];
+
+        for (title, text) in parts.iter() {
+            if text.is_empty() {
+                continue;
+            }
+
+            out.push_str(&format!("// {}\n", title));
+            out.push_str(text);
+
+            if !text.ends_with("\n\n") {
+                out.push('\n');
+            }
+        }
+
+        Ok(out)
+    }
+
+    // Renders a function type with positional argument names, as used in
+    // typedef and typeset bodies.
+    fn function_type(signature: &FunctionType) -> String {
+        format!("function {}", IncludeGenerator::prototype("", signature))
+    }
+}
@@ -0,0 +1,293 @@
+use std::sync::{Arc, Mutex, OnceLock};
+use std::collections::BTreeMap;
+use crate::headers::{SMXHeader, SectionEntry};
+use crate::sections::*;
+use crate::rtti::*;
+use crate::v1disassembler::{V1Disassembler, V1Function, V1FunctionKind, DecodeMode};
+use crate::errors::{Result, Error};
+
+// A table that is decoded on first access. None once decoded means the file
+// has no such section.
+type Lazy<T> = OnceLock<Option<Arc<T>>>;
+
+// An SMX file whose section tables are decoded the first time they are
+// accessed and whose functions are disassembled only when requested. Only
+// the container header is parsed up front, so a bad section does not stop
+// the others from being read.
+#[derive(Default)]
+pub struct SMXLazyFile<'a> {
+    header: Arc<SMXHeader<'a>>,
+    mode: DecodeMode,
+
+    names: Lazy<SMXNameTable<'a>>,
+    debug_names: Lazy<SMXNameTable<'a>>,
+    natives: Lazy<SMXNativeTable>,
+    publics: Lazy<SMXPublicTable>,
+    pubvars: Lazy<SMXPubvarTable>,
+    tags: Lazy<SMXTagTable>,
+    data: Lazy<SMXDataSection<'a>>,
+    codev1: Lazy<SMXCodeV1Section<'a>>,
+
+    debug_info: Lazy<SMXDebugInfoSection>,
+    debug_files: Lazy<SMXDebugFilesTable>,
+    debug_lines: Lazy<SMXDebugLinesTable>,
+    debug_methods: Lazy<SMXDebugMethods>,
+    debug_globals: Lazy<SMXDebugGlobals>,
+    debug_locals: Lazy<SMXDebugLocals>,
+    debug_symbols: Lazy<SMXDebugSymbolsTable>,
+    debug_natives: Lazy<SMXDebugNativesTable>,
+
+    rtti_data: Lazy<SMXRTTIData<'a>>,
+    rtti_enums: Lazy<SMXRTTIEnumTable>,
+    rtti_enum_structs: Lazy<SMXRTTIEnumStructTable>,
+    rtti_enum_struct_fields: Lazy<SMXRTTIEnumStructFieldTable>,
+    rtti_classdefs: Lazy<SMXRTTIClassDefTable>,
+    rtti_fields: Lazy<SMXRTTIFieldTable>,
+    rtti_methods: Lazy<SMXRTTIMethodTable>,
+    rtti_natives: Lazy<SMXRTTINativeTable>,
+    rtti_typedefs: Lazy<SMXRTTITypedefTable>,
+    rtti_typesets: Lazy<SMXRTTITypesetTable>,
+
+    functions: Mutex<BTreeMap<i32, Arc<V1Function>>>,
+}
+
+impl SMXLazyFile<'static> {
+    pub fn new<T>(data: T) -> Result<SMXLazyFile<'static>>
+    where
+        T: AsRef<[u8]>,
+    {
+        SMXLazyFile::new_with_mode(data, DecodeMode::Strict)
+    }
+
+    pub fn new_with_mode<T>(data: T, mode: DecodeMode) -> Result<SMXLazyFile<'static>>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(SMXLazyFile::with_header(SMXHeader::new(data)?, mode))
+    }
+}
+
+impl<'a> SMXLazyFile<'a> {
+    pub fn from_slice(data: &'a [u8]) -> Result<SMXLazyFile<'a>> {
+        SMXLazyFile::from_slice_with_mode(data, DecodeMode::Strict)
+    }
+
+    pub fn from_slice_with_mode(data: &'a [u8], mode: DecodeMode) -> Result<SMXLazyFile<'a>> {
+        Ok(SMXLazyFile::with_header(SMXHeader::from_slice(data)?, mode))
+    }
+
+    fn with_header(header: SMXHeader<'a>, mode: DecodeMode) -> Self {
+        Self {
+            header: Arc::new(header),
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn header(&self) -> &Arc<SMXHeader<'a>> {
+        &self.header
+    }
+
+    fn section(&self, name: &str) -> Option<Arc<SectionEntry>> {
+        self.header.sections.iter().find(|section| section.name == name).cloned()
+    }
+
+    // Decodes the named section with |build| unless it was decoded already.
+    // Failures are not cached, so a later call tries again.
+    fn load<T, F>(&self, cell: &Lazy<T>, name: &str, build: F) -> Result<Option<Arc<T>>>
+    where
+        F: FnOnce(Arc<SMXHeader<'a>>, Arc<SectionEntry>) -> Result<T>,
+    {
+        if let Some(table) = cell.get() {
+            return Ok(table.clone());
+        }
+
+        let table = match self.section(name) {
+            Some(section) => Some(Arc::new(build(Arc::clone(&self.header), section)?)),
+            None => None,
+        };
+
+        // Another thread may have decoded it first; both results are equal.
+        Ok(cell.get_or_init(|| table).clone())
+    }
+
+    fn require_names(&self) -> Result<Arc<SMXNameTable<'a>>> {
+        self.names()?.ok_or(Error::Other("Missing .names section"))
+    }
+
+    fn require_debug_names(&self) -> Result<Arc<SMXNameTable<'a>>> {
+        self.debug_names()?.ok_or(Error::Other("Missing .dbg.strings section"))
+    }
+
+    pub fn names(&self) -> Result<Option<Arc<SMXNameTable<'a>>>> {
+        self.load(&self.names, ".names", |header, section| Ok(SMXNameTable::new(header, section)))
+    }
+
+    // Falls back to .names when there is no .dbg.strings section.
+    pub fn debug_names(&self) -> Result<Option<Arc<SMXNameTable<'a>>>> {
+        match self.load(&self.debug_names, ".dbg.strings", |header, section| Ok(SMXNameTable::new(header, section)))? {
+            Some(names) => Ok(Some(names)),
+            None => self.names(),
+        }
+    }
+
+    pub fn natives(&self) -> Result<Option<Arc<SMXNativeTable>>> {
+        self.load(&self.natives, ".natives", |header, section| SMXNativeTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn publics(&self) -> Result<Option<Arc<SMXPublicTable>>> {
+        self.load(&self.publics, ".publics", |header, section| SMXPublicTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn pubvars(&self) -> Result<Option<Arc<SMXPubvarTable>>> {
+        self.load(&self.pubvars, ".pubvars", |header, section| SMXPubvarTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn tags(&self) -> Result<Option<Arc<SMXTagTable>>> {
+        self.load(&self.tags, ".tags", |header, section| SMXTagTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn data(&self) -> Result<Option<Arc<SMXDataSection<'a>>>> {
+        self.load(&self.data, ".data", SMXDataSection::new)
+    }
+
+    pub fn codev1(&self) -> Result<Option<Arc<SMXCodeV1Section<'a>>>> {
+        self.load(&self.codev1, ".code", SMXCodeV1Section::new)
+    }
+
+    pub fn debug_info(&self) -> Result<Option<Arc<SMXDebugInfoSection>>> {
+        self.load(&self.debug_info, ".dbg.info", SMXDebugInfoSection::new)
+    }
+
+    pub fn debug_files(&self) -> Result<Option<Arc<SMXDebugFilesTable>>> {
+        self.load(&self.debug_files, ".dbg.files", |header, section| SMXDebugFilesTable::new(header, section, &*self.require_debug_names()?))
+    }
+
+    pub fn debug_lines(&self) -> Result<Option<Arc<SMXDebugLinesTable>>> {
+        self.load(&self.debug_lines, ".dbg.lines", SMXDebugLinesTable::new)
+    }
+
+    pub fn debug_methods(&self) -> Result<Option<Arc<SMXDebugMethods>>> {
+        self.load(&self.debug_methods, ".dbg.methods", SMXDebugMethods::new)
+    }
+
+    pub fn debug_globals(&self) -> Result<Option<Arc<SMXDebugGlobals>>> {
+        self.load(&self.debug_globals, ".dbg.globals", SMXDebugGlobals::new)
+    }
+
+    pub fn debug_locals(&self) -> Result<Option<Arc<SMXDebugLocals>>> {
+        self.load(&self.debug_locals, ".dbg.locals", |header, section| {
+            SMXDebugLocals::new(header, section, self.debug_methods()?.as_deref(), self.rtti_methods()?.as_deref())
+        })
+    }
+
+    pub fn debug_symbols(&self) -> Result<Option<Arc<SMXDebugSymbolsTable>>> {
+        self.load(&self.debug_symbols, ".dbg.symbols", |header, section| SMXDebugSymbolsTable::new(header, section, &*self.require_debug_names()?, self.tags()?.as_deref()))
+    }
+
+    pub fn debug_natives(&self) -> Result<Option<Arc<SMXDebugNativesTable>>> {
+        self.load(&self.debug_natives, ".dbg.natives", |header, section| SMXDebugNativesTable::new(header, section, &*self.require_debug_names()?))
+    }
+
+    pub fn rtti_data(&self) -> Result<Option<Arc<SMXRTTIData<'a>>>> {
+        self.load(&self.rtti_data, "rtti.data", |header, section| {
+            Ok(SMXRTTIData::with_tables(
+                header,
+                section,
+                self.rtti_enums()?.as_deref(),
+                self.rtti_typedefs()?.as_deref(),
+                self.rtti_typesets()?.as_deref(),
+                self.rtti_classdefs()?.as_deref(),
+                self.rtti_enum_structs()?.as_deref(),
+            ))
+        })
+    }
+
+    pub fn rtti_enums(&self) -> Result<Option<Arc<SMXRTTIEnumTable>>> {
+        self.load(&self.rtti_enums, "rtti.enums", |header, section| SMXRTTIEnumTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_enum_structs(&self) -> Result<Option<Arc<SMXRTTIEnumStructTable>>> {
+        self.load(&self.rtti_enum_structs, "rtti.enumstructs", |header, section| SMXRTTIEnumStructTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_enum_struct_fields(&self) -> Result<Option<Arc<SMXRTTIEnumStructFieldTable>>> {
+        self.load(&self.rtti_enum_struct_fields, "rtti.enumstruct_fields", |header, section| SMXRTTIEnumStructFieldTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_classdefs(&self) -> Result<Option<Arc<SMXRTTIClassDefTable>>> {
+        self.load(&self.rtti_classdefs, "rtti.classdefs", |header, section| SMXRTTIClassDefTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_fields(&self) -> Result<Option<Arc<SMXRTTIFieldTable>>> {
+        self.load(&self.rtti_fields, "rtti.fields", |header, section| SMXRTTIFieldTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_methods(&self) -> Result<Option<Arc<SMXRTTIMethodTable>>> {
+        self.load(&self.rtti_methods, "rtti.methods", |header, section| SMXRTTIMethodTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_natives(&self) -> Result<Option<Arc<SMXRTTINativeTable>>> {
+        self.load(&self.rtti_natives, "rtti.natives", |header, section| SMXRTTINativeTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_typedefs(&self) -> Result<Option<Arc<SMXRTTITypedefTable>>> {
+        self.load(&self.rtti_typedefs, "rtti.typedefs", |header, section| SMXRTTITypedefTable::new(header, section, &*self.require_names()?))
+    }
+
+    pub fn rtti_typesets(&self) -> Result<Option<Arc<SMXRTTITypesetTable>>> {
+        self.load(&self.rtti_typesets, "rtti.typesets", |header, section| SMXRTTITypesetTable::new(header, section, &*self.require_names()?))
+    }
+
+    // Disassembles the function whose PROC is at |addr|, or returns it from
+    // the cache. Functions named by a public are Public, others are Called
+    // and named after their rtti.methods entry or legacy debug symbol when
+    // there is one.
+    pub fn function_at(&self, addr: i32) -> Result<Arc<V1Function>> {
+        if let Some(function) = self.functions.lock().unwrap().get(&addr) {
+            return Ok(Arc::clone(function));
+        }
+
+        let code = self.codev1()?.ok_or(Error::Other("Missing .code section"))?;
+
+        let public = self.publics()?.and_then(|publics| {
+            publics.entries_ref().iter().find(|pubfun| pubfun.address == addr as u32).map(|pubfun| pubfun.name.clone())
+        });
+
+        let method = self.rtti_methods()?.and_then(|methods| {
+            methods.methods_ref().iter().find(|method| method.pcode_start == addr).map(|method| method.name.clone())
+        });
+
+        let method = match method {
+            Some(name) => Some(name),
+            None => self.debug_symbols()?.and_then(|symbols| symbols.find_function(addr)).map(|sym| sym.name),
+        };
+
+        let (name, kind) = match (public, method) {
+            (Some(name), _) => (name, V1FunctionKind::Public),
+            (None, Some(name)) => (name, V1FunctionKind::Called),
+            (None, None) => (format!("sub_{:x}", addr), V1FunctionKind::Called),
+        };
+
+        let function = V1Disassembler::diassemble_function(&self.header.data, code, addr, name, kind, self.mode.clone())?;
+
+        Ok(Arc::clone(self.functions.lock().unwrap().entry(addr).or_insert_with(|| Arc::new(function))))
+    }
+
+    // Disassembles the public function with the given name.
+    pub fn public_function(&self, name: &str) -> Result<Option<Arc<V1Function>>> {
+        let address = self.publics()?.and_then(|publics| {
+            publics.entries_ref().iter().find(|pubfun| pubfun.name == name).map(|pubfun| pubfun.address as i32)
+        });
+
+        match address {
+            Some(address) => Ok(Some(self.function_at(address)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Addresses of the functions disassembled so far.
+    pub fn loaded_functions(&self) -> Vec<i32> {
+        self.functions.lock().unwrap().keys().cloned().collect()
+    }
+}
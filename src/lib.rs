@@ -11,3 +11,16 @@ pub mod rtti;
 pub mod file;
 pub mod v1opcodes;
 pub mod v1disassembler;
+pub mod listing;
+pub mod cfg;
+pub mod dot;
+pub mod include;
+pub mod writer;
+pub mod lazy;
+pub mod xrefs;
+pub mod strings;
+pub mod globals;
+pub mod emulator;
+pub mod trace;
+pub mod natives;
+pub mod verifier;
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+use std::collections::BTreeSet;
+use crate::file::SMXFile;
+use crate::v1disassembler::{V1Function, V1Instruction, V1Param};
+use crate::v1opcodes::V1OPCode;
+use crate::strings::StringLiterals;
+
+// How instruction operands are printed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OperandStyle {
+    // Operands as hexadecimal values only.
+    Raw,
+
+    // Operands resolved to labels, function, native, local and global names
+    // where possible, falling back to the raw value.
+    #[default]
+    Symbolic,
+
+    // Resolved name followed by the raw value, e.g. "PrintToServer(0x3)".
+    Both,
+}
+
+pub struct V1Listing<'a> {
+    file: Arc<SMXFile<'a>>,
+    style: OperandStyle,
+    strings: StringLiterals,
+}
+
+impl<'a> V1Listing<'a> {
+    pub fn new(file: Arc<SMXFile<'a>>, style: OperandStyle) -> Self {
+        let strings = StringLiterals::new(&file);
+
+        Self {
+            file,
+            style,
+            strings,
+        }
+    }
+
+    pub fn style(&self) -> OperandStyle {
+        self.style.clone()
+    }
+
+    // Name given to a jump target in the listing.
+    pub fn label_name(addr: i32) -> String {
+        format!("L_{:x}", addr)
+    }
+
+    // Returns every code address the function jumps to.
+    pub fn jump_targets(function: &V1Function) -> BTreeSet<i32> {
+        let mut targets: BTreeSet<i32> = BTreeSet::new();
+
+        for insn in &function.instructions {
+            if insn.info.opcode == V1OPCode::CASETBL {
+                targets.insert(insn.params[1]);
+
+                for i in 0..insn.params[0] as usize {
+                    targets.insert(insn.params[2 + i * 2 + 1]);
+                }
+
+                continue;
+            }
+
+            if insn.info.opcode == V1OPCode::SWITCH {
+                targets.insert(insn.params[0]);
+                continue;
+            }
+
+            for (i, param) in insn.info.params.iter().enumerate() {
+                if let V1Param::Jump = param {
+                    targets.insert(insn.params[i]);
+                }
+            }
+        }
+
+        targets
+    }
+
+    pub fn render_function(&self, function: &V1Function) -> String {
+        let labels = V1Listing::jump_targets(function);
+
+        let mut lines: Vec<String> = Vec::with_capacity(function.instructions.len() + labels.len() + 2);
+
+        lines.push(format!("{}:", function.name));
+        lines.push(format!("  {:08x}  proc", function.address));
+
+        let mut location: Option<(String, u32)> = None;
+
+        for insn in &function.instructions {
+            if labels.contains(&insn.address) {
+                lines.push(format!("{}:", V1Listing::label_name(insn.address)));
+            }
+
+            // Mark where the source line changes.
+            let current = self.file.source_location(insn.address);
+
+            if current.is_some() && current != location {
+                if let Some((file, line)) = &current {
+                    lines.push(format!("  // {}:{}", SMXFile::file_name(file), line));
+                }
+
+                location = current;
+            }
+
+            lines.push(format!("  {}", self.render_instruction(insn)));
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    pub fn render_instruction(&self, insn: &V1Instruction) -> String {
+        let operands = self.render_operands(insn);
+
+        if operands.is_empty() {
+            return format!("{:08x}  {}", insn.address, insn.info.name);
+        }
+
+        format!("{:08x}  {:<16}{}", insn.address, insn.info.name, operands.join(", "))
+    }
+
+    fn render_operands(&self, insn: &V1Instruction) -> Vec<String> {
+        if insn.info.opcode == V1OPCode::CASETBL {
+            let ncases = insn.params[0] as usize;
+
+            let mut operands: Vec<String> = Vec::with_capacity(ncases + 2);
+
+            operands.push(self.render_constant(insn.params[0]));
+            operands.push(format!("default: {}", self.render_jump(insn.params[1])));
+
+            for i in 0..ncases {
+                let value = insn.params[2 + i * 2];
+                let target = insn.params[2 + i * 2 + 1];
+
+                operands.push(format!("{}: {}", self.render_constant(value), self.render_jump(target)));
+            }
+
+            return operands;
+        }
+
+        if insn.info.opcode == V1OPCode::SWITCH {
+            return vec![self.render_jump(insn.params[0])];
+        }
+
+        insn.info.params.iter().zip(insn.params.iter()).enumerate().map(|(index, (kind, &value))| {
+            match kind {
+                V1Param::Constant => match self.strings.operand(insn.address, index) {
+                    Some(string) => self.render_symbol(Some(StringLiterals::quote(string)), value),
+                    None => self.render_constant(value),
+                },
+                V1Param::Jump => self.render_jump(value),
+                V1Param::Function => {
+                    let name = if self.file.is_function_at_address(value) {
+                        Some(self.file.find_function_name(value))
+                    } else {
+                        None
+                    };
+
+                    self.render_symbol(name, value)
+                },
+                V1Param::Native => {
+                    let name = match &self.file.natives {
+                        Some(natives) if value >= 0 && (value as usize) < natives.size() => Some(natives.get_entry(value as usize).name),
+                        _ => None,
+                    };
+
+                    self.render_symbol(name, value)
+                },
+                V1Param::Stack => {
+                    let name = self.file.find_local_name(insn.address, value);
+
+                    self.render_symbol(name, value)
+                },
+                V1Param::Address => {
+                    let name = self.file.find_global_name(value);
+
+                    self.render_symbol(name, value)
+                },
+            }
+        }).collect()
+    }
+
+    fn render_constant(&self, value: i32) -> String {
+        match self.style {
+            OperandStyle::Raw => V1Listing::hex(value),
+            OperandStyle::Symbolic => value.to_string(),
+            OperandStyle::Both => format!("{}({})", value, V1Listing::hex(value)),
+        }
+    }
+
+    fn render_jump(&self, target: i32) -> String {
+        self.render_symbol(Some(V1Listing::label_name(target)), target)
+    }
+
+    fn render_symbol(&self, name: Option<String>, value: i32) -> String {
+        match (&self.style, name) {
+            (OperandStyle::Raw, _) | (_, None) => V1Listing::hex(value),
+            (OperandStyle::Symbolic, Some(name)) => name,
+            (OperandStyle::Both, Some(name)) => format!("{}({})", name, V1Listing::hex(value)),
+        }
+    }
+
+    fn hex(value: i32) -> String {
+        if value < 0 {
+            return format!("-0x{:x}", -(value as i64));
+        }
+
+        format!("0x{:x}", value)
+    }
+}
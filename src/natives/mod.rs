@@ -0,0 +1,964 @@
+use std::collections::{BTreeMap, HashMap};
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::emulator::{Memory, NativeCall, NativeHandler};
+use crate::file::SMXFile;
+use crate::errors::{Result, Error};
+
+pub type NativeFn = Box<dyn FnMut(&mut NativeState, &mut Memory, &NativeCall) -> Result<i32>>;
+
+type CoreFn = fn(&mut NativeState, &mut Memory, &NativeCall) -> Result<i32>;
+
+// Largest width or precision a format specifier may ask for.
+const MAX_FORMAT_WIDTH: usize = 0x1_0000;
+
+// Largest ArrayList, in cells, CreateArray may allocate up front.
+const MAX_ARRAY_CELLS: i64 = 0x100_0000;
+
+// Reference implementations of common SourceMod natives. Methodmap natives
+// are also bound under their pre-methodmap names, e.g. CreateTrie.
+const CORE_NATIVES: &[(&str, CoreFn)] = &[
+    ("strlen", strlen),
+    ("strcmp", strcmp),
+    ("strncmp", strncmp),
+    ("StrEqual", str_equal),
+    ("StrContains", str_contains),
+    ("strcopy", strcopy),
+    ("TrimString", trim_string),
+    ("ReplaceString", replace_string),
+    ("StringToInt", string_to_int),
+    ("IntToString", int_to_string),
+    ("StringToFloat", string_to_float),
+    ("FloatToString", float_to_string),
+    ("IsCharUpper", is_char_upper),
+    ("IsCharLower", is_char_lower),
+    ("IsCharAlpha", is_char_alpha),
+    ("IsCharNumeric", is_char_numeric),
+    ("IsCharSpace", is_char_space),
+    ("IsCharMB", is_char_mb),
+    ("CharToUpper", char_to_upper),
+    ("CharToLower", char_to_lower),
+    ("Format", format_native),
+    ("FormatEx", format_native),
+    ("PrintToServer", print_to_server),
+    ("LogMessage", print_to_server),
+    ("LogError", print_to_server),
+    ("float", float),
+    ("FloatAdd", float_add),
+    ("FloatSub", float_sub),
+    ("FloatMul", float_mul),
+    ("FloatDiv", float_div),
+    ("FloatAbs", float_abs),
+    ("FloatCompare", float_compare),
+    ("RoundToNearest", round_to_nearest),
+    ("RoundToFloor", round_to_floor),
+    ("RoundToCeil", round_to_ceil),
+    ("RoundToZero", round_to_zero),
+    ("__FLOAT_GT__", float_gt),
+    ("__FLOAT_GE__", float_ge),
+    ("__FLOAT_LT__", float_lt),
+    ("__FLOAT_LE__", float_le),
+    ("__FLOAT_EQ__", float_eq),
+    ("__FLOAT_NE__", float_ne),
+    ("__FLOAT_NOT__", float_not),
+    ("GetEngineTime", get_engine_time),
+    ("GetGameTime", get_engine_time),
+    ("GetTime", get_time),
+    ("CloseHandle", close_handle),
+    ("ArrayList.ArrayList", array_create),
+    ("CreateArray", array_create),
+    ("ArrayList.Length.get", array_length),
+    ("GetArraySize", array_length),
+    ("ArrayList.Push", array_push),
+    ("PushArrayCell", array_push),
+    ("ArrayList.PushString", array_push_string),
+    ("PushArrayString", array_push_string),
+    ("ArrayList.Get", array_get),
+    ("GetArrayCell", array_get),
+    ("ArrayList.GetString", array_get_string),
+    ("GetArrayString", array_get_string),
+    ("ArrayList.Set", array_set),
+    ("SetArrayCell", array_set),
+    ("ArrayList.SetString", array_set_string),
+    ("SetArrayString", array_set_string),
+    ("ArrayList.Erase", array_erase),
+    ("RemoveFromArray", array_erase),
+    ("ArrayList.Clear", array_clear),
+    ("ClearArray", array_clear),
+    ("ArrayList.FindValue", array_find_value),
+    ("FindValueInArray", array_find_value),
+    ("ArrayList.FindString", array_find_string),
+    ("FindStringInArray", array_find_string),
+    ("StringMap.StringMap", map_create),
+    ("CreateTrie", map_create),
+    ("StringMap.Size.get", map_size),
+    ("GetTrieSize", map_size),
+    ("StringMap.SetValue", map_set_value),
+    ("SetTrieValue", map_set_value),
+    ("StringMap.GetValue", map_get_value),
+    ("GetTrieValue", map_get_value),
+    ("StringMap.SetString", map_set_string),
+    ("SetTrieString", map_set_string),
+    ("StringMap.GetString", map_get_string),
+    ("GetTrieString", map_get_string),
+    ("StringMap.ContainsKey", map_contains_key),
+    ("StringMap.Remove", map_remove),
+    ("RemoveFromTrie", map_remove),
+    ("StringMap.Clear", map_clear),
+    ("ClearTrie", map_clear),
+];
+
+enum MapValue {
+    Cell(i32),
+    String(String),
+}
+
+struct ArrayList {
+    blocksize: usize,
+    items: Vec<Vec<i32>>,
+}
+
+enum HandleObject {
+    ArrayList(ArrayList),
+    StringMap(HashMap<String, MapValue>),
+}
+
+// State shared by the natives of a library: open handles and the text printed
+// to the server console.
+pub struct NativeState {
+    handles: BTreeMap<i32, HandleObject>,
+    next_handle: i32,
+    output: Vec<String>,
+    start: Instant,
+}
+
+impl NativeState {
+    fn new() -> Self {
+        Self {
+            handles: BTreeMap::new(),
+            next_handle: 1,
+            output: Vec::new(),
+            start: Instant::now(),
+        }
+    }
+
+    // Lines printed by PrintToServer, LogMessage and LogError.
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    pub fn print(&mut self, line: String) {
+        self.output.push(line);
+    }
+
+    // Number of handles not closed yet.
+    pub fn open_handles(&self) -> usize {
+        self.handles.len()
+    }
+
+    fn create(&mut self, object: HandleObject) -> i32 {
+        let handle = self.next_handle;
+
+        self.next_handle += 1;
+        self.handles.insert(handle, object);
+
+        handle
+    }
+
+    fn array(&mut self, call: &NativeCall) -> Result<&mut ArrayList> {
+        match self.handles.get_mut(&arg(call, 0)?) {
+            Some(HandleObject::ArrayList(array)) => Ok(array),
+            _ => Err(trap(call, "Invalid handle")),
+        }
+    }
+
+    fn map(&mut self, call: &NativeCall) -> Result<&mut HashMap<String, MapValue>> {
+        match self.handles.get_mut(&arg(call, 0)?) {
+            Some(HandleObject::StringMap(map)) => Ok(map),
+            _ => Err(trap(call, "Invalid handle")),
+        }
+    }
+}
+
+// A set of natives for the emulator, looked up by their .natives name.
+// Calling a native that is not bound fails with Error::UnboundNative.
+pub struct NativeLibrary {
+    natives: HashMap<String, NativeFn>,
+    state: NativeState,
+}
+
+impl Default for NativeLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NativeLibrary {
+    // An empty library.
+    pub fn new() -> Self {
+        Self {
+            natives: HashMap::new(),
+            state: NativeState::new(),
+        }
+    }
+
+    // A library with the core natives bound.
+    pub fn core() -> Self {
+        let mut library = Self::new();
+
+        for &(name, native) in CORE_NATIVES {
+            library.bind(name, native);
+        }
+
+        library
+    }
+
+    // Binds |name| to |native|, replacing any previous binding.
+    pub fn bind<F>(&mut self, name: &str, native: F)
+        where F: FnMut(&mut NativeState, &mut Memory, &NativeCall) -> Result<i32> + 'static
+    {
+        self.natives.insert(name.into(), Box::new(native));
+    }
+
+    pub fn unbind(&mut self, name: &str) {
+        self.natives.remove(name);
+    }
+
+    pub fn is_bound(&self, name: &str) -> bool {
+        self.natives.contains_key(name)
+    }
+
+    // Natives in the .natives table of |file| without a binding.
+    pub fn unbound(&self, file: &SMXFile) -> Vec<String> {
+        match &file.natives {
+            Some(natives) => natives.entries().into_iter()
+                .map(|native| native.name)
+                .filter(|name| !self.is_bound(name))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &NativeState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut NativeState {
+        &mut self.state
+    }
+}
+
+impl NativeHandler for NativeLibrary {
+    fn invoke(&mut self, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+        match self.natives.get_mut(call.name) {
+            Some(native) => native(&mut self.state, memory, call),
+            None => Err(Error::UnboundNative { name: call.name.into(), address: call.address }),
+        }
+    }
+}
+
+// Formats the SourceMod format string in argument |index| with the variadic
+// arguments after it, which are passed by reference.
+pub fn format(memory: &Memory, call: &NativeCall, index: usize) -> Result<String> {
+    let fmt = memory.read_string(arg(call, index)?)?;
+
+    let mut args = call.args.iter().skip(index + 1);
+    let mut next = || args.next().copied().ok_or_else(|| trap(call, "Not enough format arguments"));
+
+    let mut text = String::with_capacity(fmt.len());
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+
+        let mut left = false;
+        let mut zero = false;
+
+        loop {
+            match chars.peek() {
+                Some('-') => left = true,
+                Some('0') => zero = true,
+                _ => break,
+            }
+
+            chars.next();
+        }
+
+        let width = digits(&mut chars).unwrap_or(0);
+
+        let precision = match chars.peek() {
+            Some('.') => {
+                chars.next();
+                Some(digits(&mut chars).unwrap_or(0))
+            },
+            _ => None,
+        };
+
+        if width > MAX_FORMAT_WIDTH || precision.is_some_and(|precision| precision > MAX_FORMAT_WIDTH) {
+            return Err(trap(call, "Format width too large"))
+        }
+
+        let spec = match chars.next() {
+            Some(spec) => spec,
+            None => break,
+        };
+
+        let value = match spec {
+            '%' => {
+                text.push('%');
+                continue;
+            },
+            'd' | 'i' => memory.read_cell(next()?)?.to_string(),
+            'u' => (memory.read_cell(next()?)? as u32).to_string(),
+            'b' => format!("{:b}", memory.read_cell(next()?)?),
+            'x' => format!("{:x}", memory.read_cell(next()?)?),
+            'X' => format!("{:X}", memory.read_cell(next()?)?),
+            'c' => char::from_u32(memory.read_cell(next()?)? as u32).unwrap_or('?').to_string(),
+            'f' => format!("{:.*}", precision.unwrap_or(6), to_float(memory.read_cell(next()?)?)),
+            's' => {
+                let s = memory.read_string(next()?)?;
+
+                match precision {
+                    Some(precision) => truncate(&s, precision).into(),
+                    None => s,
+                }
+            },
+            _ => return Err(trap(call, "Unsupported format specifier")),
+        };
+
+        let padding = width.saturating_sub(value.chars().count());
+
+        if left {
+            text.push_str(&value);
+            text.extend(std::iter::repeat_n(' ', padding));
+        } else if zero && spec != 's' && spec != 'c' {
+            // Zeros go after the sign.
+            let digits = match value.strip_prefix('-') {
+                Some(digits) => {
+                    text.push('-');
+                    digits
+                },
+                None => &value,
+            };
+
+            text.extend(std::iter::repeat_n('0', padding));
+            text.push_str(digits);
+        } else {
+            text.extend(std::iter::repeat_n(' ', padding));
+            text.push_str(&value);
+        }
+    }
+
+    Ok(text)
+}
+
+fn arg(call: &NativeCall, index: usize) -> Result<i32> {
+    match call.args.get(index) {
+        Some(&value) => Ok(value),
+        None => Err(trap(call, "Missing native argument")),
+    }
+}
+
+fn arg_or(call: &NativeCall, index: usize, default: i32) -> i32 {
+    call.args.get(index).copied().unwrap_or(default)
+}
+
+fn trap(call: &NativeCall, reason: &'static str) -> Error {
+    Error::Trap { address: call.address, reason }
+}
+
+fn digits(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut value: Option<usize> = None;
+
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        value = Some(value.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize));
+        chars.next();
+    }
+
+    value
+}
+
+// Cuts |s| to at most |len| bytes without splitting a character.
+fn truncate(s: &str, len: usize) -> &str {
+    let mut len = len.min(s.len());
+
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    &s[..len]
+}
+
+fn to_float(cell: i32) -> f32 {
+    f32::from_bits(cell as u32)
+}
+
+fn from_float(value: f32) -> i32 {
+    value.to_bits() as i32
+}
+
+// Packs |s| into |cells| cells, keeping room for the null terminator.
+fn pack(s: &str, cells: usize) -> Vec<i32> {
+    let mut bytes = truncate(s, (cells * 4).saturating_sub(1)).as_bytes().to_vec();
+
+    bytes.resize(cells * 4, 0);
+
+    bytes.chunks_exact(4).map(|cell| i32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]])).collect()
+}
+
+fn unpack(cells: &[i32]) -> String {
+    let bytes: Vec<u8> = cells.iter().flat_map(|cell| cell.to_le_bytes()).collect();
+
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn compare(a: &str, b: &str, case_sensitive: bool) -> i32 {
+    let ordering = match case_sensitive {
+        true => a.cmp(b),
+        false => a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()),
+    };
+
+    ordering as i32
+}
+
+fn strlen(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(memory.read_string(arg(call, 0)?)?.len() as i32)
+}
+
+fn strcmp(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let a = memory.read_string(arg(call, 0)?)?;
+    let b = memory.read_string(arg(call, 1)?)?;
+
+    Ok(compare(&a, &b, arg_or(call, 2, 1) != 0))
+}
+
+fn strncmp(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let a = memory.read_string(arg(call, 0)?)?;
+    let b = memory.read_string(arg(call, 1)?)?;
+    let num = arg(call, 2)?.max(0) as usize;
+
+    Ok(compare(truncate(&a, num), truncate(&b, num), arg_or(call, 3, 1) != 0))
+}
+
+fn str_equal(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok((strcmp(state, memory, call)? == 0) as i32)
+}
+
+fn str_contains(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let mut s = memory.read_string(arg(call, 0)?)?;
+    let mut substr = memory.read_string(arg(call, 1)?)?;
+
+    if arg_or(call, 2, 1) == 0 {
+        s = s.to_ascii_lowercase();
+        substr = substr.to_ascii_lowercase();
+    }
+
+    Ok(s.find(&substr).map_or(-1, |index| index as i32))
+}
+
+fn strcopy(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let source = memory.read_string(arg(call, 2)?)?;
+
+    Ok(memory.write_string(arg(call, 0)?, &source, arg(call, 1)?.max(0) as usize)? as i32)
+}
+
+fn trim_string(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let s = memory.read_string(arg(call, 0)?)?;
+    let trimmed = s.trim();
+
+    Ok(memory.write_string(arg(call, 0)?, trimmed, trimmed.len() + 1)? as i32)
+}
+
+fn replace_string(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let text = memory.read_string(arg(call, 0)?)?;
+    let search = memory.read_string(arg(call, 2)?)?;
+    let replace = memory.read_string(arg(call, 3)?)?;
+    let case_sensitive = arg_or(call, 4, 1) != 0;
+
+    if search.is_empty() {
+        return Err(trap(call, "Cannot replace searches of empty strings"))
+    }
+
+    // Lowercasing ASCII keeps byte offsets, so matches map back onto |text|.
+    let haystack = match case_sensitive {
+        true => text.clone(),
+        false => text.to_ascii_lowercase(),
+    };
+
+    let needle = match case_sensitive {
+        true => search,
+        false => search.to_ascii_lowercase(),
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut count = 0;
+    let mut last = 0;
+
+    for (index, _) in haystack.match_indices(&needle) {
+        result.push_str(&text[last..index]);
+        result.push_str(&replace);
+
+        last = index + needle.len();
+        count += 1;
+    }
+
+    result.push_str(&text[last..]);
+
+    memory.write_string(arg(call, 0)?, &result, arg(call, 1)?.max(0) as usize)?;
+
+    Ok(count)
+}
+
+fn string_to_int(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let s = memory.read_string(arg(call, 0)?)?;
+    let base = arg_or(call, 1, 10);
+
+    if !(2..=36).contains(&base) {
+        return Err(trap(call, "Invalid base"))
+    }
+
+    // Parses a leading integer, like strtol.
+    let s = s.trim_start();
+
+    let (negative, digits) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let digits = match base {
+        16 => digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")).unwrap_or(digits),
+        _ => digits,
+    };
+
+    let mut value: i32 = 0;
+
+    for digit in digits.chars().map_while(|c| c.to_digit(base as u32)) {
+        value = value.wrapping_mul(base).wrapping_add(digit as i32);
+    }
+
+    Ok(if negative { value.wrapping_neg() } else { value })
+}
+
+fn int_to_string(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let s = arg(call, 0)?.to_string();
+
+    Ok(memory.write_string(arg(call, 1)?, &s, arg(call, 2)?.max(0) as usize)? as i32)
+}
+
+fn string_to_float(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let s = memory.read_string(arg(call, 0)?)?;
+    let s = s.trim_start();
+
+    // Parses the longest leading number, like atof.
+    let end = s.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c))).unwrap_or(s.len());
+
+    let value = (0..=end).rev().find_map(|len| s[..len].parse::<f32>().ok()).unwrap_or(0.0);
+
+    Ok(from_float(value))
+}
+
+fn float_to_string(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let s = format!("{:.6}", to_float(arg(call, 0)?));
+
+    Ok(memory.write_string(arg(call, 1)?, &s, arg(call, 2)?.max(0) as usize)? as i32)
+}
+
+fn char_arg(call: &NativeCall) -> Result<u8> {
+    Ok(arg(call, 0)? as u8)
+}
+
+fn is_char_upper(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.is_ascii_uppercase() as i32)
+}
+
+fn is_char_lower(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.is_ascii_lowercase() as i32)
+}
+
+fn is_char_alpha(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.is_ascii_alphabetic() as i32)
+}
+
+fn is_char_numeric(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.is_ascii_digit() as i32)
+}
+
+fn is_char_space(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.is_ascii_whitespace() as i32)
+}
+
+// Returns the length of the UTF-8 sequence starting with the byte, or 0 for
+// a single byte character.
+fn is_char_mb(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let byte = char_arg(call)?;
+
+    Ok(match byte.leading_ones() {
+        2..=4 => byte.leading_ones() as i32,
+        _ => 0,
+    })
+}
+
+fn char_to_upper(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.to_ascii_uppercase() as i32)
+}
+
+fn char_to_lower(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(char_arg(call)?.to_ascii_lowercase() as i32)
+}
+
+fn format_native(_: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let text = format(memory, call, 2)?;
+
+    Ok(memory.write_string(arg(call, 0)?, &text, arg(call, 1)?.max(0) as usize)? as i32)
+}
+
+fn print_to_server(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let text = format(memory, call, 0)?;
+
+    state.print(text);
+
+    Ok(0)
+}
+
+fn float_args(call: &NativeCall) -> Result<(f32, f32)> {
+    Ok((to_float(arg(call, 0)?), to_float(arg(call, 1)?)))
+}
+
+fn float(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(from_float(arg(call, 0)? as f32))
+}
+
+fn float_add(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok(from_float(a + b))
+}
+
+fn float_sub(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok(from_float(a - b))
+}
+
+fn float_mul(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok(from_float(a * b))
+}
+
+fn float_div(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok(from_float(a / b))
+}
+
+fn float_abs(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(from_float(to_float(arg(call, 0)?).abs()))
+}
+
+fn float_compare(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok(if a > b { 1 } else if a < b { -1 } else { 0 })
+}
+
+// Halves round up, as in SourceMod, not away from zero.
+fn round_to_nearest(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok((to_float(arg(call, 0)?) + 0.5).floor() as i32)
+}
+
+fn round_to_floor(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(to_float(arg(call, 0)?).floor() as i32)
+}
+
+fn round_to_ceil(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(to_float(arg(call, 0)?).ceil() as i32)
+}
+
+fn round_to_zero(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(to_float(arg(call, 0)?).trunc() as i32)
+}
+
+fn float_gt(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok((a > b) as i32)
+}
+
+fn float_ge(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok((a >= b) as i32)
+}
+
+fn float_lt(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok((a < b) as i32)
+}
+
+fn float_le(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok((a <= b) as i32)
+}
+
+fn float_eq(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok((a == b) as i32)
+}
+
+fn float_ne(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let (a, b) = float_args(call)?;
+
+    Ok((a != b) as i32)
+}
+
+fn float_not(_: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok((to_float(arg(call, 0)?) == 0.0) as i32)
+}
+
+// Seconds since the library was created.
+fn get_engine_time(state: &mut NativeState, _: &mut Memory, _: &NativeCall) -> Result<i32> {
+    Ok(from_float(state.start.elapsed().as_secs_f32()))
+}
+
+fn get_time(_: &mut NativeState, _: &mut Memory, _: &NativeCall) -> Result<i32> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs());
+
+    Ok(now as i32)
+}
+
+fn close_handle(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    match state.handles.remove(&arg(call, 0)?) {
+        Some(_) => Ok(1),
+        None => Err(trap(call, "Invalid handle")),
+    }
+}
+
+fn array_create(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let blocksize = arg_or(call, 0, 1);
+    let startsize = arg_or(call, 1, 0);
+
+    if blocksize < 1 || startsize < 0 || blocksize as i64 * (startsize as i64).max(1) > MAX_ARRAY_CELLS {
+        return Err(trap(call, "Invalid array size"))
+    }
+
+    Ok(state.create(HandleObject::ArrayList(ArrayList {
+        blocksize: blocksize as usize,
+        items: vec![vec![0; blocksize as usize]; startsize as usize],
+    })))
+}
+
+fn array_item<'a>(array: &'a mut ArrayList, call: &NativeCall) -> Result<&'a mut Vec<i32>> {
+    match array.items.get_mut(arg(call, 1)?.max(-1) as usize) {
+        Some(item) => Ok(item),
+        None => Err(trap(call, "Invalid index")),
+    }
+}
+
+fn array_length(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(state.array(call)?.items.len() as i32)
+}
+
+fn array_push(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = arg(call, 1)?;
+    let array = state.array(call)?;
+
+    let mut item = vec![0; array.blocksize];
+
+    item[0] = value;
+    array.items.push(item);
+
+    Ok(array.items.len() as i32 - 1)
+}
+
+fn array_push_string(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = memory.read_string(arg(call, 1)?)?;
+    let array = state.array(call)?;
+
+    array.items.push(pack(&value, array.blocksize));
+
+    Ok(array.items.len() as i32 - 1)
+}
+
+// With |asChar| set, the block is a byte offset into the item.
+fn array_get(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let block = arg_or(call, 2, 0);
+    let as_char = arg_or(call, 3, 0) != 0;
+    let item = array_item(state.array(call)?, call)?;
+
+    let value = match as_char {
+        true => item.iter().flat_map(|cell| cell.to_le_bytes()).nth(block.max(-1) as usize).map(|byte| byte as i32),
+        false => item.get(block.max(-1) as usize).copied(),
+    };
+
+    value.ok_or_else(|| trap(call, "Invalid block"))
+}
+
+fn array_get_string(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = unpack(array_item(state.array(call)?, call)?);
+
+    Ok(memory.write_string(arg(call, 2)?, &value, arg(call, 3)?.max(0) as usize)? as i32)
+}
+
+fn array_set(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = arg(call, 2)?;
+    let block = arg_or(call, 3, 0).max(-1) as usize;
+    let as_char = arg_or(call, 4, 0) != 0;
+    let item = array_item(state.array(call)?, call)?;
+
+    if as_char {
+        if block >= item.len() * 4 {
+            return Err(trap(call, "Invalid block"))
+        }
+
+        let mut bytes = item[block / 4].to_le_bytes();
+
+        bytes[block % 4] = value as u8;
+        item[block / 4] = i32::from_le_bytes(bytes);
+    } else {
+        match item.get_mut(block) {
+            Some(cell) => *cell = value,
+            None => return Err(trap(call, "Invalid block")),
+        }
+    }
+
+    Ok(0)
+}
+
+fn array_set_string(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = memory.read_string(arg(call, 2)?)?;
+    let item = array_item(state.array(call)?, call)?;
+
+    *item = pack(&value, item.len());
+
+    Ok(truncate(&value, item.len() * 4 - 1).len() as i32)
+}
+
+fn array_erase(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let index = arg(call, 1)?;
+    let array = state.array(call)?;
+
+    if index < 0 || index as usize >= array.items.len() {
+        return Err(trap(call, "Invalid index"))
+    }
+
+    array.items.remove(index as usize);
+
+    Ok(0)
+}
+
+fn array_clear(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    state.array(call)?.items.clear();
+
+    Ok(0)
+}
+
+fn array_find_value(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = arg(call, 1)?;
+    let block = arg_or(call, 2, 0).max(-1) as usize;
+    let array = state.array(call)?;
+
+    if block >= array.blocksize {
+        return Err(trap(call, "Invalid block"))
+    }
+
+    Ok(array.items.iter().position(|item| item[block] == value).map_or(-1, |index| index as i32))
+}
+
+fn array_find_string(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = memory.read_string(arg(call, 1)?)?;
+    let array = state.array(call)?;
+
+    Ok(array.items.iter().position(|item| unpack(item) == value).map_or(-1, |index| index as i32))
+}
+
+fn map_create(state: &mut NativeState, _: &mut Memory, _: &NativeCall) -> Result<i32> {
+    Ok(state.create(HandleObject::StringMap(HashMap::new())))
+}
+
+fn map_size(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    Ok(state.map(call)?.len() as i32)
+}
+
+fn map_insert(state: &mut NativeState, memory: &mut Memory, call: &NativeCall, value: MapValue) -> Result<i32> {
+    let key = memory.read_string(arg(call, 1)?)?;
+    let replace = arg_or(call, 3, 1) != 0;
+    let map = state.map(call)?;
+
+    if !replace && map.contains_key(&key) {
+        return Ok(0)
+    }
+
+    map.insert(key, value);
+
+    Ok(1)
+}
+
+fn map_set_value(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = MapValue::Cell(arg(call, 2)?);
+
+    map_insert(state, memory, call, value)
+}
+
+fn map_set_string(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let value = MapValue::String(memory.read_string(arg(call, 2)?)?);
+
+    map_insert(state, memory, call, value)
+}
+
+fn map_get_value(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let key = memory.read_string(arg(call, 1)?)?;
+
+    match state.map(call)?.get(&key) {
+        Some(MapValue::Cell(value)) => {
+            memory.write_cell(arg(call, 2)?, *value)?;
+            Ok(1)
+        },
+        _ => Ok(0),
+    }
+}
+
+fn map_get_string(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let key = memory.read_string(arg(call, 1)?)?;
+
+    match state.map(call)?.get(&key) {
+        Some(MapValue::String(value)) => {
+            let written = memory.write_string(arg(call, 2)?, value, arg(call, 3)?.max(0) as usize)?;
+
+            if let Some(&size) = call.args.get(4) {
+                memory.write_cell(size, written as i32)?;
+            }
+
+            Ok(1)
+        },
+        _ => Ok(0),
+    }
+}
+
+fn map_contains_key(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let key = memory.read_string(arg(call, 1)?)?;
+
+    Ok(state.map(call)?.contains_key(&key) as i32)
+}
+
+fn map_remove(state: &mut NativeState, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+    let key = memory.read_string(arg(call, 1)?)?;
+
+    Ok(state.map(call)?.remove(&key).is_some() as i32)
+}
+
+fn map_clear(state: &mut NativeState, _: &mut Memory, call: &NativeCall) -> Result<i32> {
+    state.map(call)?.clear();
+
+    Ok(0)
+}
@@ -1,15 +1,15 @@
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::sync::Arc;
+use std::fmt;
 use std::io::{Cursor, Seek, SeekFrom};
 use byteorder::{ReadBytesExt, LittleEndian};
 use crate::sections::{BaseSection, SMXNameTable};
 use crate::headers::{SMXHeader, SectionEntry};
 use crate::file::SMXFile;
-use crate::errors::Result;
+use crate::errors::{Result, Error};
 
 #[derive(Debug, Clone)]
-pub struct SMXRTTIListTable {
-    base: BaseSection,
+pub struct SMXRTTIListTable<'a> {
+    _base: BaseSection<'a>,
 
     header_size: u32,
 
@@ -18,17 +18,17 @@ pub struct SMXRTTIListTable {
     row_count: u32,
 }
 
-impl SMXRTTIListTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Self {
+impl<'a> SMXRTTIListTable<'a> {
+    pub fn new(header: Arc<SMXHeader<'a>>, section: Arc<SectionEntry>) -> Self {
         Self {
-            base: BaseSection::new(header, section),
+            _base: BaseSection::new(header, section),
             header_size: 0,
             row_size: 0,
             row_count: 0,
         }
     }
 
-    pub fn init(&mut self, data: &mut Cursor<Vec<u8>>) -> Result<&Self> {
+    pub fn init(&mut self, data: &mut Cursor<&[u8]>) -> Result<&Self> {
         self.header_size = data.read_u32::<LittleEndian>()?;
         self.row_size = data.read_u32::<LittleEndian>()?;
         self.row_count =data.read_u32::<LittleEndian>()?;
@@ -100,24 +100,177 @@ impl CB {
     }
 }
 
-#[derive(Clone)]
-pub struct SMXRTTIData {
-    smx_file: Rc<RefCell<SMXFile>>,
+bitflags! {
+    pub struct TypeFlags: u8 {
+        const CONST = 0x01;
+        const BYREF = 0x02;
+    }
+}
+
+// A type decoded from rtti.data. Named types carry their index into the
+// matching rtti.* table along with the resolved name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    Int,
+    Float,
+    Char,
+    Any,
+    // The untyped "Function" type.
+    TopFunction,
+    Function(Box<FunctionType>),
+    FixedArray(Box<Type>, i32),
+    Array(Box<Type>),
+    Enum { index: i32, name: String },
+    Typedef { index: i32, name: String },
+    Typeset { index: i32, name: String },
+    Struct { index: i32, name: String },
+    EnumStruct { index: i32, name: String },
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Bool => write!(f, "bool"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Char => write!(f, "char"),
+            Type::Any => write!(f, "any"),
+            Type::TopFunction => write!(f, "Function"),
+            Type::Function(signature) => write!(f, "{}", signature),
+            Type::FixedArray(inner, size) => write!(f, "{}[{}]", inner, size),
+            Type::Array(inner) => write!(f, "{}[]", inner),
+            Type::Enum { name, .. } |
+            Type::Typedef { name, .. } |
+            Type::Typeset { name, .. } |
+            Type::Struct { name, .. } |
+            Type::EnumStruct { name, .. } => write!(f, "{}", name),
+        }
+    }
+}
+
+// A type together with its const and by-ref qualifiers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualifiedType {
+    pub ty: Type,
+
+    pub flags: TypeFlags,
+}
+
+impl QualifiedType {
+    pub fn is_const(&self) -> bool {
+        self.flags.contains(TypeFlags::CONST)
+    }
+
+    pub fn is_byref(&self) -> bool {
+        self.flags.contains(TypeFlags::BYREF)
+    }
+}
+
+impl fmt::Display for QualifiedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_const() {
+            write!(f, "const ")?;
+        }
+
+        write!(f, "{}", self.ty)?;
+
+        if self.is_byref() {
+            write!(f, "&")?;
+        }
+
+        Ok(())
+    }
+}
 
-    bytes: Vec<u8>,
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionType {
+    // None for void.
+    pub return_type: Option<QualifiedType>,
+
+    pub args: Vec<QualifiedType>,
+
+    pub variadic: bool,
 }
 
-impl SMXRTTIData {
-    pub fn new(file: Rc<RefCell<SMXFile>>, header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Self {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
-        
+impl fmt::Display for FunctionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.return_type {
+            Some(t) => write!(f, "function {} (", t)?,
+            None => write!(f, "function void (")?,
+        }
+
+        let args: Vec<String> = self.args.iter().map(|arg| arg.to_string()).collect();
+
+        write!(f, "{}", args.join(", "))?;
+
+        if self.variadic {
+            write!(f, "...")?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+// Names of the rtti tables that types refer to by index.
+#[derive(Debug, Clone, Default)]
+struct RTTINames {
+    enums: Vec<String>,
+    typedefs: Vec<String>,
+    typesets: Vec<String>,
+    classdefs: Vec<String>,
+    enum_structs: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SMXRTTIData<'a> {
+    names: RTTINames,
+
+    base: BaseSection<'a>,
+}
+
+impl<'a> SMXRTTIData<'a> {
+    // The rtti tables of |file| must already be parsed; their names are
+    // copied so decoded types can be named without a reference to the file.
+    pub fn new(file: &SMXFile, header: Arc<SMXHeader<'a>>, section: Arc<SectionEntry>) -> Self {
+        SMXRTTIData::with_tables(
+            header,
+            section,
+            file.rtti_enums.as_deref(),
+            file.rtti_typedefs.as_deref(),
+            file.rtti_typesets.as_deref(),
+            file.rtti_classdefs.as_deref(),
+            file.rtti_enum_structs.as_deref(),
+        )
+    }
+
+    // Same as |new|, for callers that hold the tables outside an SMXFile.
+    pub fn with_tables(
+        header: Arc<SMXHeader<'a>>,
+        section: Arc<SectionEntry>,
+        enums: Option<&SMXRTTIEnumTable>,
+        typedefs: Option<&SMXRTTITypedefTable>,
+        typesets: Option<&SMXRTTITypesetTable>,
+        classdefs: Option<&SMXRTTIClassDefTable>,
+        enum_structs: Option<&SMXRTTIEnumStructTable>,
+    ) -> Self {
+        let base = BaseSection::new(header, section);
+
+        let names = RTTINames {
+            enums: enums.map(|t| t.enums()).unwrap_or_default(),
+            typedefs: typedefs.map(|t| t.typedefs().into_iter().map(|e| e.name).collect()).unwrap_or_default(),
+            typesets: typesets.map(|t| t.typesets().into_iter().map(|e| e.name).collect()).unwrap_or_default(),
+            classdefs: classdefs.map(|t| t.defs().into_iter().map(|e| e.name).collect()).unwrap_or_default(),
+            enum_structs: enum_structs.map(|t| t.entries().into_iter().map(|e| e.name).collect()).unwrap_or_default(),
+        };
+
         Self {
-            smx_file: Rc::clone(&file),
-            bytes: base.get_data(),
+            names,
+            base,
         }
     }
 
-    pub fn type_from_id(&self, type_id: i32) -> String {
+    pub fn type_from_id(&self, type_id: i32) -> Result<QualifiedType> {
         let kind: i32 = type_id & 0xf;
         let mut payload: i32 = (type_id >> 4) & 0x0fff_ffff;
 
@@ -129,189 +282,226 @@ impl SMXRTTIData {
                 (payload >> 24) as u8,
             ];
 
-            let mut builder: TypeBuilder = TypeBuilder::new(Rc::clone(&self.smx_file), temp.to_vec(), 0);
+            let mut builder: TypeBuilder = TypeBuilder::new(&self.names, &temp, 0);
 
             return builder.decode_new()
         }
 
-        //TODO: Consider convert to Result<String>
         if kind != CB::TYPEID_COMPLEX as i32 {
-            return format!("Unknown type_id kind: {}", kind);
+            return Err(Error::Other("Unknown type_id kind"));
         }
 
-        self.build_type_name(&mut payload)
+        self.build_type(&mut payload)
     }
 
-    pub fn function_type_from_offset(&self, offset: i32) -> String {
-        let mut builder: TypeBuilder = TypeBuilder::new(Rc::clone(&self.smx_file), self.bytes.clone(), offset);
+    pub fn function_type_from_offset(&self, offset: i32) -> Result<FunctionType> {
+        let mut builder: TypeBuilder = TypeBuilder::new(&self.names, self.base.get_data(), offset);
 
         builder.decode_function()
     }
 
-    pub fn typeset_types_from_offset(&self, offset: i32) -> Vec<String> {
-        let count: i32 = CB::decode_u32(&self.bytes, &mut offset.clone());
+    pub fn typeset_types_from_offset(&self, offset: i32) -> Result<Vec<QualifiedType>> {
+        let mut builder: TypeBuilder = TypeBuilder::new(&self.names, self.base.get_data(), offset);
+
+        let count: i32 = builder.decode_u32()?;
 
-        let mut types: Vec<String> = Vec::with_capacity(count as usize);
+        if count < 0 {
+            return Err(Error::InvalidIndex)
+        }
 
-        let mut builder: TypeBuilder = TypeBuilder::new(Rc::clone(&self.smx_file), self.bytes.clone(), offset);
+        // The count comes from the file, so don't preallocate for it.
+        let mut types: Vec<QualifiedType> = Vec::new();
 
         for _ in 0..count {
-            types.push(builder.decode_new())
+            types.push(builder.decode_new()?)
         }
 
-        types
+        Ok(types)
     }
 
-    fn build_type_name(&self, offset: &mut i32) -> String {
-        let mut builder: TypeBuilder = TypeBuilder::new(Rc::clone(&self.smx_file), self.bytes.clone(), *offset);
+    fn build_type(&self, offset: &mut i32) -> Result<QualifiedType> {
+        let mut builder: TypeBuilder = TypeBuilder::new(&self.names, self.base.get_data(), *offset);
 
-        let text: String = builder.decode_new();
+        let t: QualifiedType = builder.decode_new()?;
 
         *offset = builder.offset;
 
-        text
+        Ok(t)
     }
 }
 
-struct TypeBuilder {
-    file: Rc<RefCell<SMXFile>>,
-    bytes: Vec<u8>,
+struct TypeBuilder<'a> {
+    names: &'a RTTINames,
+    bytes: &'a [u8],
     offset: i32,
     is_const: bool,
 }
 
-impl TypeBuilder{
-    pub fn new(file: Rc<RefCell<SMXFile>>, bytes: Vec<u8>, offset: i32) -> Self {
+impl<'a> TypeBuilder<'a> {
+    pub fn new(names: &'a RTTINames, bytes: &'a [u8], offset: i32) -> Self {
         Self {
-            file,
+            names,
             bytes,
             offset,
             is_const: false,
         }
     }
 
+    fn peek(&self) -> Result<u8> {
+        if self.offset < 0 || self.offset as usize >= self.bytes.len() {
+            return Err(Error::InvalidOffset)
+        }
+
+        Ok(self.bytes[self.offset as usize])
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let b: u8 = self.peek()?;
+        self.offset += 1;
+        Ok(b)
+    }
+
+    pub fn decode_u32(&mut self) -> Result<i32> {
+        let mut value: u32 = 0;
+        let mut shift: u32 = 0;
+
+        loop {
+            let b: u8 = self.next()?;
+            value |= ((b & 0x7f) as u32).checked_shl(shift).unwrap_or(0);
+            if (b & 0x80) == 0 {
+                break;
+            }
+            shift += 7;
+        }
+
+        Ok(value as i32)
+    }
+
     // Decode a type, but reset the |is_const| indicator for non-
     // dependent type.
-    pub fn decode_new(&mut self) -> String {
+    pub fn decode_new(&mut self) -> Result<QualifiedType> {
         let was_const: bool = self.is_const;
         self.is_const = false;
 
-        let mut result: String = self.decode();
+        let ty: Type = self.decode()?;
+
+        let mut flags: TypeFlags = TypeFlags::empty();
 
         if self.is_const {
-            result = format!("const {}", result);
+            flags |= TypeFlags::CONST;
         }
 
         self.is_const = was_const;
 
-        result
+        Ok(QualifiedType {
+            ty,
+            flags,
+        })
     }
 
-    pub fn decode(&mut self) -> String {
-        self.is_const |= self.r#match(CB::CONST);
-        let b: u8 = self.bytes[self.offset as usize];
-        self.offset += 1;
+    pub fn decode(&mut self) -> Result<Type> {
+        self.is_const |= self.r#match(CB::CONST)?;
+        let b: u8 = self.next()?;
 
-        match b {
-            CB::BOOL => "bool".into(),
-            CB::INT32 => "int".into(),
-            CB::FLOAT32 => "float".into(),
-            CB::CHAR8 => "char".into(),
-            CB::ANY => "any".into(),
-            CB::TOPFUNCTION => "Function".into(),
+        let t = match b {
+            CB::BOOL => Type::Bool,
+            CB::INT32 => Type::Int,
+            CB::FLOAT32 => Type::Float,
+            CB::CHAR8 => Type::Char,
+            CB::ANY => Type::Any,
+            CB::TOPFUNCTION => Type::TopFunction,
             CB::FIXEDARRAY => {
-                let index = CB::decode_u32(&self.bytes, &mut self.offset);
-                let inner: String = self.decode();
+                let size = self.decode_u32()?;
+                let inner: Type = self.decode()?;
 
-                format!("{}[{}]", inner, index)
+                Type::FixedArray(Box::new(inner), size)
             },
             CB::ARRAY => {
-                let inner: String = self.decode();
-                
-                format!("{}[]", inner)
+                let inner: Type = self.decode()?;
+
+                Type::Array(Box::new(inner))
             },
             CB::ENUM => {
-                let index = CB::decode_u32(&self.bytes, &mut self.offset);
+                let index = self.decode_u32()?;
+
+                let name = self.names.enums.get(index as usize).cloned().ok_or(Error::InvalidIndex)?;
 
-                self.file.borrow_mut().rtti_enums.as_ref().unwrap().enums()[index as usize].clone()
+                Type::Enum { index, name }
             },
             CB::TYPEDEF => {
-                let index = CB::decode_u32(&self.bytes, &mut self.offset);
+                let index = self.decode_u32()?;
 
-                self.file.borrow_mut().rtti_typedefs.as_ref().unwrap().typedefs()[index as usize].name.clone()
+                let name = self.names.typedefs.get(index as usize).cloned().ok_or(Error::InvalidIndex)?;
+
+                Type::Typedef { index, name }
             }
             CB::TYPESET => {
-                let index = CB::decode_u32(&self.bytes, &mut self.offset);
+                let index = self.decode_u32()?;
+
+                let name = self.names.typesets.get(index as usize).cloned().ok_or(Error::InvalidIndex)?;
 
-                self.file.borrow_mut().rtti_typesets.as_ref().unwrap().typesets()[index as usize].name.clone()
+                Type::Typeset { index, name }
             },
             CB::STRUCT => {
-                let index = CB::decode_u32(&self.bytes, &mut self.offset);
+                let index = self.decode_u32()?;
 
-                self.file.borrow_mut().rtti_classdefs.as_ref().unwrap().defs()[index as usize].name.clone()
+                let name = self.names.classdefs.get(index as usize).cloned().ok_or(Error::InvalidIndex)?;
+
+                Type::Struct { index, name }
             },
-            CB::FUNCTION => self.decode_function(),
+            CB::FUNCTION => Type::Function(Box::new(self.decode_function()?)),
             CB::ENUMSTRUCT => {
-                let index = CB::decode_u32(&self.bytes, &mut self.offset);
+                let index = self.decode_u32()?;
 
-                self.file.borrow_mut().rtti_enum_structs.as_ref().unwrap().entries()[index as usize].name.clone()
-            },
-            _ => format!("unknown type code: {}", b),
-        }
-    }
+                let name = self.names.enum_structs.get(index as usize).cloned().ok_or(Error::InvalidIndex)?;
 
-    pub fn decode_function(&mut self) -> String {
-        let argc: u32 = self.bytes[self.offset as usize] as u32;
-        self.offset += 1;
+                Type::EnumStruct { index, name }
+            },
+            _ => return Err(Error::Other("Unknown type code")),
+        };
 
-        let mut variadic: bool = false;
+        Ok(t)
+    }
 
-        if self.bytes[self.offset as usize] == CB::VARIADIC {
-            variadic = true;
-            self.offset += 1;
-        }
+    pub fn decode_function(&mut self) -> Result<FunctionType> {
+        let argc: u32 = self.next()? as u32;
 
-        let return_type: String;
+        let variadic: bool = self.r#match(CB::VARIADIC)?;
 
-        if self.bytes[self.offset as usize] == CB::VOID {
-            return_type = "void".into();
-            self.offset += 1;
+        let return_type: Option<QualifiedType> = if self.r#match(CB::VOID)? {
+            None
         } else {
-            return_type = self.decode_new();
-        }
+            Some(self.decode_new()?)
+        };
 
-        let mut argv: Vec<String> = Vec::with_capacity(argc as usize);
+        let mut args: Vec<QualifiedType> = Vec::with_capacity(argc as usize);
 
         for _ in 0..argc {
-            let is_byref: bool = self.r#match(CB::BYREF);
-            let mut text: String = self.decode_new();
+            let is_byref: bool = self.r#match(CB::BYREF)?;
+            let mut arg: QualifiedType = self.decode_new()?;
 
             if is_byref {
-                text += "&";
+                arg.flags |= TypeFlags::BYREF;
             }
 
-            argv.push(text);
+            args.push(arg);
         }
 
-        let mut signature: String = format!("function {} ({}", return_type, argv.join(", "));
-
-        if variadic {
-            signature += "...";
-        }
-
-        signature += ")";
-
-        signature
+        Ok(FunctionType {
+            return_type,
+            args,
+            variadic,
+        })
     }
 
-    fn r#match(&mut self, b: u8) -> bool {
-        if self.bytes[self.offset as usize] != b {
-            return false
+    fn r#match(&mut self, b: u8) -> Result<bool> {
+        if self.peek()? != b {
+            return Ok(false)
         }
 
         self.offset += 1;
 
-        true
+        Ok(true)
     }
 }
 
@@ -321,7 +511,7 @@ pub struct SMXRTTIEnumTable {
 }
 
 impl SMXRTTIEnumTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
         let base = BaseSection::new(header.clone(), section.clone());    
         let mut rtti = SMXRTTIListTable::new(header, section);
 
@@ -334,7 +524,7 @@ impl SMXRTTIEnumTable {
         for _ in 0..rtti.row_count() {
             let index = data.read_i32::<LittleEndian>()?;
 
-            enums.push(names.borrow_mut().string_at(index)?);
+            enums.push(names.string_at(index)?);
 
             // reserved0-2.
             data.seek(SeekFrom::Current(3 * 4))?;
@@ -367,9 +557,9 @@ pub struct SMXRTTIMethodTable {
 }
 
 impl SMXRTTIMethodTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -381,7 +571,7 @@ impl SMXRTTIMethodTable {
             let index = data.read_i32::<LittleEndian>()?;
 
             methods.push(RTTIMethod {
-                name: names.borrow_mut().string_at(index)?,
+                name: names.string_at(index)?,
                 pcode_start: data.read_i32::<LittleEndian>()?,
                 pcode_end: data.read_i32::<LittleEndian>()?,
                 signature: data.read_i32::<LittleEndian>()?,
@@ -415,9 +605,9 @@ pub struct SMXRTTINativeTable {
 }
 
 impl SMXRTTINativeTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -429,7 +619,7 @@ impl SMXRTTINativeTable {
             let index = data.read_i32::<LittleEndian>()?;
 
             natives.push(RTTINative {
-                name: names.borrow_mut().string_at(index)?,
+                name: names.string_at(index)?,
                 signature: data.read_i32::<LittleEndian>()?,
             });
         }
@@ -457,9 +647,9 @@ pub struct SMXRTTITypedefTable {
 }
 
 impl SMXRTTITypedefTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -471,7 +661,7 @@ impl SMXRTTITypedefTable {
             let index = data.read_i32::<LittleEndian>()?;
 
             typedefs.push(RTTITypedef {
-                name: names.borrow_mut().string_at(index)?,
+                name: names.string_at(index)?,
                 type_id: data.read_i32::<LittleEndian>()?,
             });
         }
@@ -499,9 +689,9 @@ pub struct SMXRTTITypesetTable {
 }
 
 impl SMXRTTITypesetTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -513,7 +703,7 @@ impl SMXRTTITypesetTable {
             let index = data.read_i32::<LittleEndian>()?;
 
             typesets.push(RTTITypeset {
-                name: names.borrow_mut().string_at(index)?,
+                name: names.string_at(index)?,
                 signature: data.read_i32::<LittleEndian>()?,
             });
         }
@@ -545,9 +735,9 @@ pub struct SMXRTTIEnumStructTable {
 }
 
 impl SMXRTTIEnumStructTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -559,7 +749,7 @@ impl SMXRTTIEnumStructTable {
             let name_offset = data.read_i32::<LittleEndian>()?;
             let first_field = data.read_i32::<LittleEndian>()?;
             let size = data.read_i32::<LittleEndian>()?;
-            let name = names.borrow_mut().string_at(name_offset)?;
+            let name = names.string_at(name_offset)?;
 
             entries.push(RTTIEnumStruct {
                 name_offset,
@@ -596,9 +786,9 @@ pub struct SMXRTTIEnumStructFieldTable {
 }
 
 impl SMXRTTIEnumStructFieldTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -610,7 +800,7 @@ impl SMXRTTIEnumStructFieldTable {
             let name_offset = data.read_i32::<LittleEndian>()?;
             let type_id = data.read_i32::<LittleEndian>()?;
             let offset = data.read_i32::<LittleEndian>()?;
-            let name = names.borrow_mut().string_at(name_offset)?;
+            let name = names.string_at(name_offset)?;
 
             entries.push(RTTIEnumStructField {
                 name_offset,
@@ -647,9 +837,9 @@ pub struct SMXRTTIClassDefTable {
 }
 
 impl SMXRTTIClassDefTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -661,7 +851,7 @@ impl SMXRTTIClassDefTable {
             let flags = data.read_i32::<LittleEndian>()?;
             let name_offset = data.read_i32::<LittleEndian>()?;
             let first_field = data.read_i32::<LittleEndian>()?;
-            let name = names.borrow_mut().string_at(name_offset)?;
+            let name = names.string_at(name_offset)?;
 
             defs.push(RTTIClassDef {
                 flags,
@@ -701,9 +891,9 @@ pub struct SMXRTTIFieldTable {
 }
 
 impl SMXRTTIFieldTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));    
-        let mut rtti = SMXRTTIListTable::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));    
+        let mut rtti = SMXRTTIListTable::new(Arc::clone(&header), Arc::clone(&section));
 
         let mut data = Cursor::new(base.get_data());
 
@@ -715,7 +905,7 @@ impl SMXRTTIFieldTable {
             let flags = data.read_i16::<LittleEndian>()?;
             let name_offset = data.read_i32::<LittleEndian>()?;
             let type_id = data.read_i32::<LittleEndian>()?;
-            let name = names.borrow_mut().string_at(name_offset)?;
+            let name = names.string_at(name_offset)?;
 
             fields.push(RTTIField {
                 flags,
@@ -1,29 +1,30 @@
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::sync::Arc;
 use std::collections::HashMap;
 use std::io::Cursor;
+use byteorder::{ReadBytesExt, LittleEndian};
 use crate::headers::{SMXHeader, SectionEntry};
 use crate::v1types::*;
-use crate::rtti::{SMXRTTIListTable, RTTIMethod};
-use crate::file::SMXFile;
+use crate::v1opcodes::V1OPCode;
+use crate::v1disassembler::{V1Disassembler, V1Sweep};
+use crate::rtti::{SMXRTTIListTable, SMXRTTIMethodTable, RTTIMethod};
 use crate::errors::{Result, Error};
 
 #[derive(Debug, Clone)]
-pub struct BaseSection {
-    pub header: Rc<SMXHeader>,
-    pub section: Rc<SectionEntry>,
+pub struct BaseSection<'a> {
+    pub header: Arc<SMXHeader<'a>>,
+    pub section: Arc<SectionEntry>,
 }
 
-impl BaseSection {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Self {
+impl<'a> BaseSection<'a> {
+    pub fn new(header: Arc<SMXHeader<'a>>, section: Arc<SectionEntry>) -> Self {
         BaseSection {
             header,
             section,
         }
     }
 
-    pub fn get_data(&self) -> Vec<u8> {
-        self.header.data[self.section.data_offset as usize..(self.section.data_offset + self.section.size) as usize].to_vec()
+    pub fn get_data(&self) -> &[u8] {
+        &self.header.data[self.section.data_offset as usize..(self.section.data_offset + self.section.size) as usize]
     }
 }
 
@@ -31,21 +32,32 @@ impl BaseSection {
 //   .names
 //   .dbg.names
 #[derive(Debug, Clone)]
-pub struct SMXNameTable {
-    base: BaseSection,
+pub struct SMXNameTable<'a> {
+    base: BaseSection<'a>,
 
     names: HashMap<i32, String>,
 
     extends: Vec<i32>,
 }
 
-impl SMXNameTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Self {
-        Self {
+impl<'a> SMXNameTable<'a> {
+    pub fn new(header: Arc<SMXHeader<'a>>, section: Arc<SectionEntry>) -> Self {
+        let mut table = Self {
             base: BaseSection::new(header, section),
             names: HashMap::new(),
             extends: Vec::new(),
+        };
+
+        table.compute_extends();
+
+        // Every root string is cached up front so lookups never need to mutate.
+        for &index in &table.extends {
+            let name = table.read_string(index);
+
+            table.names.insert(index, name);
         }
+
+        table
     }
 
     fn compute_extends(&mut self) -> &Self {
@@ -61,12 +73,22 @@ impl SMXNameTable {
         self
     }
 
-    // Returns a list of all root indexes that map to strings.
-    pub fn get_extends(&mut self) -> Vec<i32> {
-        if self.extends.is_empty() {
-            self.compute_extends();
+    fn read_string(&self, index: i32) -> String {
+        let mut str_vec = Vec::with_capacity(256);
+
+        for i in index..self.base.section.size {
+            if self.base.header.data[(self.base.section.data_offset + i) as usize] == 0 {
+                break;
+            }
+
+            str_vec.push(self.base.header.data[(self.base.section.data_offset + i) as usize]);
         }
 
+        String::from_utf8_lossy(&str_vec[..]).into_owned()
+    }
+
+    // Returns a list of all root indexes that map to strings.
+    pub fn get_extends(&self) -> Vec<i32> {
         self.extends.clone()
     }
 
@@ -75,30 +97,17 @@ impl SMXNameTable {
     }
 
     // Returns a string at a given index.
-    pub fn string_at(&mut self, index: i32) -> Result<String> {
-        if self.names.contains_key(&index) {
-            return Ok(self.names.get(&index).unwrap().clone())
+    pub fn string_at(&self, index: i32) -> Result<String> {
+        if let Some(name) = self.names.get(&index) {
+            return Ok(name.clone())
         }
 
-        if index >= self.base.section.size {
+        if index < 0 || index >= self.base.section.size {
             return Err(Error::InvalidIndex)
         }
 
-        let mut str_vec = Vec::with_capacity(256);
-
-        for i in index..self.base.section.size {
-            if self.base.header.data[(self.base.section.data_offset + i) as usize] == 0 {
-                break;
-            }
-
-            str_vec.push(self.base.header.data[(self.base.section.data_offset + i) as usize]);
-        }
-
-        let s = String::from_utf8_lossy(&str_vec[..]).into_owned();
-
-        self.names.insert(index, s.clone());
-
-        Ok(s)
+        // Indexes into the middle of a string share its tail.
+        Ok(self.read_string(index))
     }
 }
 
@@ -109,9 +118,9 @@ pub struct SMXNativeTable {
 }
 
 impl SMXNativeTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
-        let natives = NativeEntry::new(&base.get_data(), section, names)?;
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
+        let natives = NativeEntry::new(base.get_data(), section, names)?;
 
         Ok(Self {
             natives,
@@ -140,8 +149,8 @@ pub struct SMXPublicTable {
 }
 
 impl SMXPublicTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let publics = PublicEntry::new(base.get_data(), section, names)?;
 
         Ok(Self {
@@ -181,9 +190,13 @@ impl SMXCalledFunctionsTable{
     }
 
     pub fn add_function(&mut self, addr: u32) {
+        self.add_named_function(addr, format!("sub_{:x}", addr))
+    }
+
+    pub fn add_named_function(&mut self, addr: u32, name: String) {
         self.functions.push(CalledFunctionEntry {
             address: addr,
-            name: format!("sub_{:x}", addr),
+            name,
         })
     }
 
@@ -213,8 +226,8 @@ pub struct SMXPubvarTable {
 }
 
 impl SMXPubvarTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let public_variables = PubvarEntry::new(base.get_data(), section, names)?;
 
         Ok(Self {
@@ -290,8 +303,8 @@ pub struct SMXTagTable {
 }
 
 impl SMXTagTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let tags = TagEntry::new(base.get_data(), section, names)?;
 
         let mut tt = Self {
@@ -300,31 +313,18 @@ impl SMXTagTable {
         };
 
         for tag in tags {
-            tt.tags.push(Tag::new(tag.to_owned()))
+            let tag = Tag::new(tag.to_owned());
+
+            // The first tag with a given id wins.
+            tt.cache.entry(tag.id() as u16).or_insert_with(|| tag.clone());
+            tt.tags.push(tag);
         }
 
         Ok(tt)
     }
 
-    pub fn find_tag(&mut self, tag: u16) -> Option<Tag> {
-        if self.cache.contains_key(&tag) {
-            return Some(self.cache.get(&tag).unwrap().clone());
-        }
-
-        let mut found: Option<Tag> = None;
-
-        for i in 0..self.tags.len() {
-            if self.tags[i].id() as u16 == tag {
-                found = Some(self.tags[i].clone());
-                break;
-            }
-        }
-
-        if let Some(v) = &found {
-            self.cache.insert(tag, v.clone());
-        }
-
-        found
+    pub fn find_tag(&self, tag: u16) -> Option<Tag> {
+        self.cache.get(&tag).cloned()
     }
 
 
@@ -349,17 +349,23 @@ impl SMXTagTable {
 
 // The .data section.
 #[derive(Debug, Clone)]
-pub struct SMXDataSection{
-    base: BaseSection,
+pub struct SMXDataSection<'a> {
+    base: BaseSection<'a>,
 
     data_header: DataHeader,
 }
 
-impl SMXDataSection {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+impl<'a> SMXDataSection<'a> {
+    pub fn new(header: Arc<SMXHeader<'a>>, section: Arc<SectionEntry>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let data_header = DataHeader::new(base.get_data())?;
 
+        // The blob must lie inside the section, so data() can't go out of
+        // bounds.
+        if data_header.data_offset as u64 + data_header.data_size as u64 > section.size as u64 {
+            return Err(Error::InvalidOffset)
+        }
+
         Ok(Self {
             base,
             data_header,
@@ -367,27 +373,66 @@ impl SMXDataSection {
     }
 
     pub fn get_data_vec(&self) -> Vec<u8> {
-        let start = self.base.section.data_offset as u32 + self.data_header.data_offset;
-
-        Vec::from(&self.base.header.data[start as usize..(start + self.data_header.data_size) as usize])
+        Vec::from(self.data())
     }
 
     pub fn header(&self) -> DataHeader {
         self.data_header.clone()
     }
+
+    // The data blob, without copying it.
+    pub fn data(&self) -> &[u8] {
+        let start = (self.base.section.data_offset as u32 + self.data_header.data_offset) as usize;
+
+        &self.base.header.data[start..start + self.data_header.data_size as usize]
+    }
+
+    // Reads the null-terminated string at |addr|. Strings are normally
+    // packed, one byte per character. An unpacked string stores one character
+    // per cell, so it is assumed when there are at least two characters
+    // before a null cell and every one of them is printable. One-character
+    // packed literals followed by an empty one look the same, e.g. ",", "a"
+    // and "", and are read as unpacked too.
+    pub fn read_string(&self, addr: i32) -> Option<String> {
+        let data = self.data();
+
+        if addr < 0 || addr % 4 != 0 || addr as usize >= data.len() {
+            return None
+        }
+
+        let bytes = &data[addr as usize..];
+
+        let cells: Vec<i32> = bytes.chunks_exact(4).map(|cell| i32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]])).collect();
+
+        let is_char = |&cell: &i32| {
+            let c = cell as u8 as char;
+
+            (1..0x100).contains(&cell) && (!c.is_control() || c.is_whitespace())
+        };
+
+        if let Some(len) = cells.iter().position(|&cell| cell == 0) {
+            if len >= 2 && cells[..len].iter().all(is_char) {
+                return Some(cells[..len].iter().map(|&cell| cell as u8 as char).collect())
+            }
+        }
+
+        let end = bytes.iter().position(|&byte| byte == 0)?;
+
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
 }
 
 // The .code section.
 #[derive(Debug, Clone)]
-pub struct SMXCodeV1Section{
-    base: BaseSection,
+pub struct SMXCodeV1Section<'a> {
+    base: BaseSection<'a>,
 
     code_header: CodeV1Header,
 }
 
-impl SMXCodeV1Section {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+impl<'a> SMXCodeV1Section<'a> {
+    pub fn new(header: Arc<SMXHeader<'a>>, section: Arc<SectionEntry>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let code_header = CodeV1Header::new(base.get_data())?;
 
         Ok(Self {
@@ -410,6 +455,26 @@ impl SMXCodeV1Section {
     pub fn code_start(&self) -> i32 {
         self.base.section.data_offset + self.code_header.code_offset
     }
+
+    // Returns true if |addr| is a cell-aligned code offset holding a PROC.
+    pub fn is_proc_at(&self, addr: i32) -> bool {
+        if addr < 0 || addr % 4 != 0 || addr + 4 > self.code_header.code_size {
+            return false
+        }
+
+        let start = (self.code_start() + addr) as usize;
+
+        match self.base.header.data.get(start..start + 4) {
+            Some(cell) => i32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]]) == V1OPCode::PROC as i32,
+            None => false,
+        }
+    }
+
+    // Linearly disassembles the whole code stream, including functions that
+    // nothing references.
+    pub fn sweep(&self) -> V1Sweep {
+        V1Disassembler::sweep(&self.base.header.data, self)
+    }
 }
 
 // The .dbg.info section.
@@ -419,8 +484,8 @@ pub struct SMXDebugInfoSection {
 }
 
 impl SMXDebugInfoSection {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let info = DebugInfoHeader::new(base.get_data())?;
 
         Ok(Self {
@@ -452,8 +517,8 @@ pub struct SMXDebugFilesTable {
 }
 
 impl SMXDebugFilesTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let entries = DebugFileEntry::new(base.get_data(), section, names)?;
 
         Ok(Self {
@@ -461,6 +526,7 @@ impl SMXDebugFilesTable {
         })
     }
 
+    // Returns the name of the file containing the code at |addr|.
     pub fn find_file(&self, addr: u32) -> Option<String> {
         let mut high = self.len() as i32;
         let mut low = -1;
@@ -508,8 +574,8 @@ pub struct SMXDebugLinesTable {
 }
 
 impl SMXDebugLinesTable {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let entries = DebugLineEntry::new(base.get_data(), section)?;
 
         Ok(Self {
@@ -517,7 +583,8 @@ impl SMXDebugLinesTable {
         })
     }
 
-    pub fn find_file(&self, addr: u32) -> Option<u32> {
+    // Returns the 1-based line of the code at |addr|.
+    pub fn find_line(&self, addr: u32) -> Option<u32> {
         let mut high = self.len() as i32;
         let mut low = -1;
 
@@ -563,8 +630,8 @@ pub struct SMXDebugMethods {
 }
 
 impl SMXDebugMethods {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let mut rtti = SMXRTTIListTable::new(header, section);
 
         let mut data = Cursor::new(base.get_data());
@@ -608,8 +675,8 @@ pub struct SMXDebugSymbols {
 }
 
 impl SMXDebugSymbols {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
-        let base = BaseSection::new(Rc::clone(&header), Rc::clone(&section));
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
         let mut rtti = SMXRTTIListTable::new(header, section);
 
         let mut data = Cursor::new(base.get_data());
@@ -622,22 +689,16 @@ impl SMXDebugSymbols {
             entries.push(DebugVarEntry::new(&mut data)?)
         }
 
+        let mut address_sorted = entries.clone();
+
+        address_sorted.sort_by_key(|a| a.address);
+
         Ok(Self {
             entries,
-            address_sorted: Vec::with_capacity(rtti.row_count() as usize),
+            address_sorted,
         })
     }
 
-    pub fn ensure_sorted_addresses(&mut self) -> &Self {
-        if !self.address_sorted.is_empty() {
-            return self
-        }
-
-        self.address_sorted.sort_by(|a, b| b.address.cmp(&a.address));
-
-        self
-    }
-
     pub fn entries(&self) -> Vec<DebugVarEntry> {
         self.entries.clone()
     }
@@ -665,15 +726,13 @@ pub struct SMXDebugGlobals {
 }
 
 impl SMXDebugGlobals {
-    pub fn new(header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>) -> Result<Self> {
         Ok(Self {
-            debug_symbols: SMXDebugSymbols::new(Rc::clone(&header), Rc::clone(&section))?,
+            debug_symbols: SMXDebugSymbols::new(Arc::clone(&header), Arc::clone(&section))?,
         })
     }
 
-    pub fn find_global(&mut self, addr: i32) -> Option<DebugVarEntry> {
-        self.debug_symbols.ensure_sorted_addresses();
-
+    pub fn find_global(&self, addr: i32) -> Option<DebugVarEntry> {
         for i in 0..self.debug_symbols.address_sorted.len() {
             let sym = &self.debug_symbols.address_sorted[i];
 
@@ -704,17 +763,30 @@ impl SMXDebugGlobals {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct SMXDebugLocals {
-    file: Rc<RefCell<SMXFile>>,
     debug_symbols: SMXDebugSymbols,
+
+    // Code range and first local of each method, from .dbg.methods and
+    // rtti.methods.
+    methods: Vec<(i32, i32, i32)>,
 }
 
 impl SMXDebugLocals {
-    pub fn new(file: Rc<RefCell<SMXFile>>, header: Rc<SMXHeader>, section: Rc<SectionEntry>) -> Result<Self> {
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, debug_methods: Option<&SMXDebugMethods>, rtti_methods: Option<&SMXRTTIMethodTable>) -> Result<Self> {
+        let mut methods: Vec<(i32, i32, i32)> = Vec::new();
+
+        if let (Some(debug_methods), Some(rtti_methods)) = (debug_methods, rtti_methods) {
+            for entry in debug_methods.entries_ref() {
+                let method: &RTTIMethod = rtti_methods.methods_ref().get(entry.method_index as usize).ok_or(Error::InvalidIndex)?;
+
+                methods.push((method.pcode_start, method.pcode_end, entry.first_local));
+            }
+        }
+
         Ok(Self {
-            file,
-            debug_symbols: SMXDebugSymbols::new(Rc::clone(&header), Rc::clone(&section))?,
+            debug_symbols: SMXDebugSymbols::new(Arc::clone(&header), Arc::clone(&section))?,
+            methods,
         })
     }
 
@@ -722,26 +794,13 @@ impl SMXDebugLocals {
         let mut start_at: i32 = 0;
         let mut stop_at: i32 = self.debug_symbols.entries_len() as i32;
 
-        if self.file.borrow().debug_methods.is_some() && self.file.borrow().rtti_methods.is_some() {
-            let mut index: Option<usize> = None;
-
-            for i in 0..self.file.borrow().debug_methods.as_ref().unwrap().len() {
-                let f = self.file.borrow();
-                let method_index: i32 = f.debug_methods.as_ref().unwrap().entries_ref()[i as usize].method_index;
-                let method: &RTTIMethod = &f.rtti_methods.as_ref().unwrap().methods_ref()[method_index as usize];
-
-                if code_addr > method.pcode_start && code_addr < method.pcode_end {
-                    index = Some(i as usize);
-                    break;
-                }
-            }
+        let index = self.methods.iter().position(|&(start, end, _)| code_addr > start && code_addr < end);
 
-            if let Some(i) = index {
-                start_at = self.file.borrow().debug_methods.as_ref().unwrap().entries_ref()[i].first_local;
+        if let Some(i) = index {
+            start_at = self.methods[i].2;
 
-                if i != self.file.borrow().debug_methods.as_ref().unwrap().len() - 1 {
-                    stop_at = self.file.borrow().debug_methods.as_ref().unwrap().entries_ref()[i + 1].first_local;
-                }
+            if let Some(next) = self.methods.get(i + 1) {
+                stop_at = next.2;
             }
         }
 
@@ -775,3 +834,154 @@ impl SMXDebugLocals {
     }
 }
 
+// The legacy .dbg.symbols table, written by compilers before .dbg.globals
+// and .dbg.locals existed.
+#[derive(Debug, Clone)]
+pub struct SMXDebugSymbolsTable {
+    entries: Vec<DebugSymbolEntry>,
+
+    // Globals and statics, sorted by address.
+    globals: Vec<DebugSymbolEntry>,
+
+    // Tag of char arrays, which are sized in bytes rather than cells.
+    string_tag: Option<u32>,
+}
+
+impl SMXDebugSymbolsTable {
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable, tags: Option<&SMXTagTable>) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
+        let data = base.get_data();
+
+        let mut cursor = Cursor::new(data);
+        let mut entries: Vec<DebugSymbolEntry> = Vec::new();
+
+        while (cursor.position() as usize) < data.len() {
+            entries.push(DebugSymbolEntry::new(&mut cursor, header.debug_packed, names)?);
+        }
+
+        let mut globals: Vec<DebugSymbolEntry> = entries.iter()
+            .filter(|sym| sym.ident != SymbolIdent::Function && sym.scope != SymbolScope::Local)
+            .cloned()
+            .collect();
+
+        globals.sort_by_key(|sym| sym.address);
+
+        // Legacy compilers tag char arrays "String".
+        let string_tag = tags.and_then(|tags| tags.tags.iter().find(|tag| tag.entry.name == "String").map(|tag| tag.id()));
+
+        Ok(Self {
+            entries,
+            globals,
+            string_tag,
+        })
+    }
+
+    // Returns the global or static containing data address |addr|.
+    pub fn find_global(&self, addr: i32) -> Option<DebugSymbolEntry> {
+        let index = self.globals.partition_point(|sym| sym.address <= addr);
+
+        if index == 0 {
+            return None
+        }
+
+        let sym = &self.globals[index - 1];
+
+        if sym.address == addr || (sym.ident == SymbolIdent::Array && addr < sym.address + self.array_size(sym)) {
+            return Some(sym.clone())
+        }
+
+        None
+    }
+
+    // Returns the local or argument at frame offset |addr| that is in scope
+    // at |code_addr|.
+    pub fn find_local(&self, code_addr: i32, addr: i32) -> Option<DebugSymbolEntry> {
+        self.entries.iter()
+            .find(|sym| {
+                sym.scope == SymbolScope::Local && sym.ident != SymbolIdent::Function && sym.address == addr
+                    && code_addr >= sym.code_start as i32 && code_addr < sym.code_end as i32
+            })
+            .cloned()
+    }
+
+    // Returns the function symbol starting at code address |addr|.
+    pub fn find_function(&self, addr: i32) -> Option<DebugSymbolEntry> {
+        self.entries.iter().find(|sym| sym.ident == SymbolIdent::Function && sym.address == addr).cloned()
+    }
+
+    pub fn entries(&self) -> Vec<DebugSymbolEntry> {
+        self.entries.clone()
+    }
+
+    pub fn entries_ref(&self) -> &Vec<DebugSymbolEntry> {
+        self.entries.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Size in bytes of the data of a global array, not counting indirection
+    // vectors. Char arrays are sized in bytes, rounded up to whole cells.
+    fn array_size(&self, sym: &DebugSymbolEntry) -> i32 {
+        let count = sym.dims.iter().fold(1i64, |size, dim| size.saturating_mul(dim.size as i64));
+
+        let size = match self.string_tag {
+            Some(tag) if sym.tag_id as u16 as u32 == tag => count.saturating_add(3) / 4 * 4,
+            _ => count.saturating_mul(4),
+        };
+
+        size.min(i32::MAX as i64) as i32
+    }
+}
+
+// The legacy .dbg.natives table.
+#[derive(Debug, Clone)]
+pub struct SMXDebugNativesTable {
+    entries: Vec<DebugNativeEntry>,
+}
+
+impl SMXDebugNativesTable {
+    pub fn new(header: Arc<SMXHeader>, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Self> {
+        let base = BaseSection::new(Arc::clone(&header), Arc::clone(&section));
+
+        let mut cursor = Cursor::new(base.get_data());
+
+        let count = cursor.read_u32::<LittleEndian>()?;
+
+        let mut entries: Vec<DebugNativeEntry> = Vec::new();
+
+        for _ in 0..count {
+            entries.push(DebugNativeEntry::new(&mut cursor, names)?);
+        }
+
+        Ok(Self {
+            entries,
+        })
+    }
+
+    // Returns the signature of the native at |index| in the .natives table.
+    pub fn find_native(&self, index: u32) -> Option<DebugNativeEntry> {
+        self.entries.iter().find(|native| native.index == index).cloned()
+    }
+
+    pub fn entries(&self) -> Vec<DebugNativeEntry> {
+        self.entries.clone()
+    }
+
+    pub fn entries_ref(&self) -> &Vec<DebugNativeEntry> {
+        self.entries.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use crate::file::SMXFile;
+use crate::rtti::{FunctionType, QualifiedType, Type};
+use crate::sections::SMXDataSection;
+use crate::v1disassembler::V1Function;
+use crate::v1opcodes::V1OPCode;
+use crate::listing::V1Listing;
+
+// String literals recovered from .data, keyed by data address.
+//
+// Literals are found by following the constants pushed as arguments of
+// native and function calls. An argument is taken as a string when the
+// callee's RTTI signature declares it as a char array. Without a signature,
+// or for variadic "any ..." arguments, it is taken as a string when the data
+// it points to reads as non-empty printable text. Addresses of named globals
+// are never literals.
+#[derive(Default)]
+pub struct StringLiterals {
+    strings: BTreeMap<i32, String>,
+
+    // Literal address loaded by each (instruction address, operand index).
+    operands: BTreeMap<(i32, usize), i32>,
+}
+
+// A constant operand and where it was loaded.
+#[derive(Clone, Copy)]
+struct Constant {
+    value: i32,
+
+    address: i32,
+
+    index: usize,
+}
+
+impl StringLiterals {
+    pub fn new(file: &SMXFile) -> Self {
+        let mut literals = Self::default();
+
+        let data = match &file.data {
+            Some(data) => data,
+            None => return literals,
+        };
+
+        for function in file.functions().values() {
+            literals.scan_function(file, data, function);
+        }
+
+        literals
+    }
+
+    pub fn get(&self, addr: i32) -> Option<&str> {
+        self.strings.get(&addr).map(|s| s.as_str())
+    }
+
+    // Returns the literal loaded by operand |index| of the instruction at
+    // |address|, if any.
+    pub fn operand(&self, address: i32, index: usize) -> Option<&str> {
+        let addr = self.operands.get(&(address, index))?;
+
+        self.get(*addr)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&i32, &String)> {
+        self.strings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    // Renders |s| as a SourcePawn string literal.
+    pub fn quote(s: &str) -> String {
+        let mut text = String::with_capacity(s.len() + 2);
+
+        text.push('"');
+
+        for c in s.chars() {
+            match c {
+                '"' => text.push_str("\\\""),
+                '\\' => text.push_str("\\\\"),
+                '\n' => text.push_str("\\n"),
+                '\r' => text.push_str("\\r"),
+                '\t' => text.push_str("\\t"),
+                c if c.is_control() => text.push_str(&format!("\\x{:02x}", c as u32)),
+                c => text.push(c),
+            }
+        }
+
+        text.push('"');
+
+        text
+    }
+
+    fn scan_function(&mut self, file: &SMXFile, data: &SMXDataSection, function: &V1Function) {
+        let labels = V1Listing::jump_targets(function);
+
+        // Values pushed since the last call. None is anything that is not a
+        // known constant.
+        let mut stack: Vec<Option<Constant>> = Vec::new();
+        let mut pri: Option<Constant> = None;
+        let mut alt: Option<Constant> = None;
+
+        for insn in &function.instructions {
+            // Arguments are never pushed across a branch.
+            if labels.contains(&insn.address) {
+                stack.clear();
+            }
+
+            let params = &insn.params;
+
+            match insn.info.opcode {
+                V1OPCode::PUSH_C | V1OPCode::PUSH2_C | V1OPCode::PUSH3_C | V1OPCode::PUSH4_C | V1OPCode::PUSH5_C => {
+                    stack.extend(params.iter().enumerate().map(|(index, &value)| Some(Constant {
+                        value,
+                        address: insn.address,
+                        index,
+                    })));
+                },
+                V1OPCode::PUSH | V1OPCode::PUSH2 | V1OPCode::PUSH3 | V1OPCode::PUSH4 | V1OPCode::PUSH5 |
+                V1OPCode::PUSH_S | V1OPCode::PUSH2_S | V1OPCode::PUSH3_S | V1OPCode::PUSH4_S | V1OPCode::PUSH5_S |
+                V1OPCode::PUSH_ADR | V1OPCode::PUSH2_ADR | V1OPCode::PUSH3_ADR | V1OPCode::PUSH4_ADR | V1OPCode::PUSH5_ADR => {
+                    stack.extend(params.iter().map(|_| None));
+                },
+                V1OPCode::PUSH_PRI => stack.push(pri),
+                V1OPCode::PUSH_ALT => stack.push(alt),
+                V1OPCode::POP_PRI | V1OPCode::POP_ALT => {
+                    stack.pop();
+                },
+                V1OPCode::SYSREQ_N => {
+                    let args = StringLiterals::pop_args(&mut stack, params[1]);
+                    let signature = StringLiterals::native_signature(file, params[0]);
+
+                    self.add_args(file, data, &args, signature.as_ref());
+                },
+                V1OPCode::SYSREQ_C | V1OPCode::CALL => {
+                    let count = stack.pop().flatten().map_or(0, |count| count.value);
+                    let args = StringLiterals::pop_args(&mut stack, count);
+
+                    let signature = match insn.info.opcode {
+                        V1OPCode::CALL => StringLiterals::method_signature(file, params[0]),
+                        _ => StringLiterals::native_signature(file, params[0]),
+                    };
+
+                    self.add_args(file, data, &args, signature.as_ref());
+                },
+                _ => (),
+            }
+
+            let constant = insn.params.first().map(|&value| Constant {
+                value,
+                address: insn.address,
+                index: 0,
+            });
+
+            pri = match insn.info.opcode {
+                V1OPCode::CONST_PRI => constant,
+                V1OPCode::BREAK | V1OPCode::PUSH_PRI | V1OPCode::PUSH_ALT => pri,
+                _ => None,
+            };
+
+            alt = match insn.info.opcode {
+                V1OPCode::CONST_ALT => constant,
+                V1OPCode::BREAK | V1OPCode::PUSH_PRI | V1OPCode::PUSH_ALT => alt,
+                _ => None,
+            };
+        }
+    }
+
+    // Pops |count| arguments, first argument first. Arguments that were not
+    // seen being pushed are left out.
+    fn pop_args(stack: &mut Vec<Option<Constant>>, count: i32) -> Vec<Option<Constant>> {
+        // |count| comes from the file, so it is clamped to what was pushed.
+        let count = (count.max(0) as usize).min(stack.len());
+
+        let mut args: Vec<Option<Constant>> = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            args.push(stack.pop().flatten());
+        }
+
+        args
+    }
+
+    fn native_signature(file: &SMXFile, index: i32) -> Option<FunctionType> {
+        let rtti = file.rtti_data.as_ref()?;
+        let name = file.natives.as_ref()?.entries().get(index as usize)?.name.clone();
+        let native = file.rtti_natives.as_ref()?.natives().into_iter().find(|native| native.name == name)?;
+
+        rtti.function_type_from_offset(native.signature).ok()
+    }
+
+    fn method_signature(file: &SMXFile, addr: i32) -> Option<FunctionType> {
+        let rtti = file.rtti_data.as_ref()?;
+        let methods = file.rtti_methods.as_ref()?;
+        let method = methods.methods_ref().iter().find(|method| method.pcode_start == addr)?;
+
+        rtti.function_type_from_offset(method.signature).ok()
+    }
+
+    fn add_args(&mut self, file: &SMXFile, data: &SMXDataSection, args: &[Option<Constant>], signature: Option<&FunctionType>) {
+        for (i, arg) in args.iter().enumerate() {
+            let constant = match arg {
+                Some(constant) => constant,
+                None => continue,
+            };
+
+            let addr = constant.value;
+
+            let declared = signature.and_then(|signature| {
+                match signature.args.get(i) {
+                    Some(t) if !(signature.variadic && i == signature.args.len() - 1) => Some(t),
+                    _ => None,
+                }
+            });
+
+            let is_global = match &file.debug_globals {
+                Some(globals) => globals.find_global(addr).is_some_and(|sym| sym.address == addr),
+                None => false,
+            };
+
+            if is_global {
+                continue;
+            }
+
+            let string = match data.read_string(addr) {
+                Some(string) => string,
+                None => continue,
+            };
+
+            let is_string = match declared {
+                Some(t) => StringLiterals::is_char_array(t),
+                None => !string.is_empty() && !string.chars().any(|c| c.is_control() && !c.is_whitespace()),
+            };
+
+            if is_string {
+                self.strings.insert(addr, string);
+                self.operands.insert((constant.address, constant.index), addr);
+            }
+        }
+    }
+
+    fn is_char_array(t: &QualifiedType) -> bool {
+        match &t.ty {
+            Type::Array(inner) | Type::FixedArray(inner, _) => matches!(**inner, Type::Char),
+            _ => false,
+        }
+    }
+}
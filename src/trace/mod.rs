@@ -0,0 +1,113 @@
+use std::fmt::Write;
+use crate::emulator::{MemoryWrite, Registers, Tracer};
+use crate::errors::Error;
+use crate::v1disassembler::V1Instruction;
+
+#[derive(Debug, Clone)]
+pub struct TraceStep {
+    // Address of the executed instruction.
+    pub address: i32,
+
+    // Mnemonic, e.g. "push.c".
+    pub name: String,
+
+    pub params: Vec<i32>,
+
+    // Registers after the instruction ran.
+    pub registers: Registers,
+
+    pub writes: Vec<MemoryWrite>,
+
+    // Why the instruction trapped, if it ended the run.
+    pub error: Option<String>,
+}
+
+impl TraceStep {
+    // Renders the step as a single line JSON object.
+    pub fn to_json(&self) -> String {
+        let r = &self.registers;
+
+        let params: Vec<String> = self.params.iter().map(|param| param.to_string()).collect();
+
+        let writes: Vec<String> = self.writes.iter().map(|write| {
+            let mut bytes = String::with_capacity(write.bytes.len() * 2);
+
+            for byte in &write.bytes {
+                let _ = write!(bytes, "{:02x}", byte);
+            }
+
+            format!("{{\"address\":{},\"bytes\":\"{}\"}}", write.address, bytes)
+        }).collect();
+
+        let error = match &self.error {
+            Some(error) => format!(",\"error\":\"{}\"", error.replace('\\', "\\\\").replace('"', "\\\"")),
+            None => String::new(),
+        };
+
+        format!(
+            "{{\"address\":{},\"name\":\"{}\",\"params\":[{}],\"pri\":{},\"alt\":{},\"frm\":{},\"stk\":{},\"hea\":{},\"writes\":[{}]{}}}",
+            self.address, self.name, params.join(","), r.pri, r.alt, r.frm, r.stk, r.hea, writes.join(","), error,
+        )
+    }
+}
+
+// Records every step of a traced call, in execution order.
+#[derive(Default)]
+pub struct Trace {
+    steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    // One JSON object per step, each on its own line.
+    pub fn to_json_lines(&self) -> String {
+        let mut text = String::new();
+
+        for step in &self.steps {
+            text.push_str(&step.to_json());
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+impl Tracer for Trace {
+    fn step(&mut self, insn: &V1Instruction, registers: &Registers, writes: &[MemoryWrite]) {
+        self.steps.push(TraceStep {
+            address: insn.address,
+            name: insn.info.name.clone(),
+            params: insn.params.clone(),
+            registers: *registers,
+            writes: writes.to_vec(),
+            error: None,
+        });
+    }
+
+    fn trap(&mut self, insn: &V1Instruction, registers: &Registers, writes: &[MemoryWrite], error: &Error) {
+        self.step(insn, registers, writes);
+
+        if let Some(step) = self.steps.last_mut() {
+            step.error = Some(error.to_string());
+        }
+    }
+}
@@ -1,11 +1,9 @@
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::sync::Arc;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::io::{Cursor, Seek, SeekFrom};
 use byteorder::{ReadBytesExt, LittleEndian};
-use std::convert::TryFrom;
 use crate::errors::{Result, Error};
-use crate::file::SMXFile;
 use crate::v1opcodes::*;
 use crate::sections::{SMXCodeV1Section};
 
@@ -33,6 +31,53 @@ pub struct V1Instruction {
     pub params: Vec<i32>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum V1FunctionKind {
+    // Listed in the .publics table.
+    Public,
+
+    // Discovered through a call, a function reference or rtti.methods, or
+    // found by a linear sweep.
+    Called,
+}
+
+#[derive(Clone)]
+pub struct V1Function {
+    pub name: String,
+
+    // Offset of the PROC opcode within the code section.
+    pub address: i32,
+
+    // Offset one past the last instruction of the function.
+    pub code_end: i32,
+
+    pub kind: V1FunctionKind,
+
+    pub instructions: Vec<V1Instruction>,
+}
+
+// Result of a linear sweep over a whole code section.
+#[derive(Clone, Default)]
+pub struct V1Sweep {
+    // A function for every PROC, in address order, named sub_<addr>.
+    pub functions: Vec<V1Function>,
+
+    // Code outside of any function, such as the HALT at address 0 and
+    // ENDPROC markers.
+    pub gaps: Vec<Range<i32>>,
+
+    // Cells inside functions that do not decode as instructions.
+    pub data: Vec<Range<i32>>,
+}
+
+// Appends |start..end|, merging it into the last range when they touch.
+fn add_range(ranges: &mut Vec<Range<i32>>, start: i32, end: i32) {
+    match ranges.last_mut() {
+        Some(last) if last.end == start => last.end = end,
+        _ => ranges.push(start..end),
+    }
+}
+
 lazy_static! {
     static ref OPCODE_LIST: HashMap<u32, V1OPCodeInfo> = {
         let mut m = HashMap::new();
@@ -179,113 +224,334 @@ lazy_static! {
         prep(V1OPCode::ZERO_PRI, &[]);
         prep(V1OPCode::ZERO_S, &[V1Param::Stack]);
         prep(V1OPCode::REBASE, &[V1Param::Address, V1Param::Constant, V1Param::Constant]);
+        prep(V1OPCode::ENDPROC, &[]);
+        prep(V1OPCode::LDGFN_PRI, &[V1Param::Function]);
+        prep(V1OPCode::FABS, &[]);
+        prep(V1OPCode::FLOAT, &[]);
+        prep(V1OPCode::FLOATADD, &[]);
+        prep(V1OPCode::FLOATSUB, &[]);
+        prep(V1OPCode::FLOATMUL, &[]);
+        prep(V1OPCode::FLOATDIV, &[]);
+        prep(V1OPCode::RND_TO_NEAREST, &[]);
+        prep(V1OPCode::RND_TO_FLOOR, &[]);
+        prep(V1OPCode::RND_TO_CEIL, &[]);
+        prep(V1OPCode::RND_TO_ZERO, &[]);
+        prep(V1OPCode::FLOATCMP, &[]);
+        prep(V1OPCode::FLOAT_GT, &[]);
+        prep(V1OPCode::FLOAT_GE, &[]);
+        prep(V1OPCode::FLOAT_LT, &[]);
+        prep(V1OPCode::FLOAT_LE, &[]);
+        prep(V1OPCode::FLOAT_NE, &[]);
+        prep(V1OPCode::FLOAT_EQ, &[]);
+        prep(V1OPCode::FLOAT_NOT, &[]);
+
+        // Mnemonics used by spcomp that don't follow the enum name.
+        let renames: &[(V1OPCode, &str)] = &[
+            (V1OPCode::FLOATADD, "float.add"),
+            (V1OPCode::FLOATSUB, "float.sub"),
+            (V1OPCode::FLOATMUL, "float.mul"),
+            (V1OPCode::FLOATDIV, "float.div"),
+            (V1OPCode::RND_TO_NEAREST, "round"),
+            (V1OPCode::RND_TO_FLOOR, "floor"),
+            (V1OPCode::RND_TO_CEIL, "ceil"),
+            (V1OPCode::RND_TO_ZERO, "rndtozero"),
+            (V1OPCode::FLOATCMP, "float.cmp"),
+        ];
+
+        for (op, name) in renames {
+            m.get_mut(&(op.clone() as u32)).unwrap().name = (*name).into();
+        }
 
         m
     };
 }
 
-pub struct V1Disassembler {
-    file: Rc<RefCell<SMXFile>>,
-    data: Vec<u8>,
+// Returns the operand metadata for an opcode, if the disassembler knows it.
+pub fn opcode_info(op: u32) -> Option<V1OPCodeInfo> {
+    OPCODE_LIST.get(&op).cloned()
+}
+
+// How the disassembler reacts to malformed code.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum DecodeMode {
+    // Return an error on the first invalid, truncated or out of range instruction.
+    #[default]
+    Strict,
+
+    // Emit an "unknown" pseudo-instruction holding the raw cell and keep going.
+    BestEffort,
+}
+
+impl V1Instruction {
+    // Pseudo-instruction emitted in best effort mode for a cell that does not
+    // decode. The raw cell value is kept as the only parameter.
+    pub fn unknown(address: i32, value: i32) -> Self {
+        Self {
+            address,
+            info: V1OPCodeInfo {
+                opcode: V1OPCode::TOTAL_OPCODES,
+                name: "unknown".into(),
+                params: vec![V1Param::Constant],
+            },
+            params: vec![value],
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.info.opcode == V1OPCode::TOTAL_OPCODES
+    }
+}
+
+pub struct V1Disassembler<'a> {
+    data: &'a [u8],
     code_start: i32,
-    _proc_offset: i32,
+    proc_offset: i32,
     cursor: i32,
     cursor_limit: i32,
+    code_end: i32,
+    mode: DecodeMode,
 }
 
-impl V1Disassembler {
-    pub fn new(file: Rc<RefCell<SMXFile>>, data: Vec<u8>, code: Rc<SMXCodeV1Section>, proc_offset: i32) -> Self {
+impl<'a> V1Disassembler<'a> {
+    pub fn new(data: &'a [u8], code: &SMXCodeV1Section, proc_offset: i32) -> Self {
         Self {
-            file: Rc::clone(&file),
             data,
             code_start: code.code_start(),
-            _proc_offset: proc_offset,
+            proc_offset,
             cursor: proc_offset,
             cursor_limit: code.header().code_size,
+            code_end: proc_offset,
+            mode: DecodeMode::Strict,
         }
     }
 
+    pub fn set_mode(&mut self, mode: DecodeMode) {
+        self.mode = mode;
+    }
+
     fn read_at(&self, offset: i32) -> Result<i32> {
-        let mut cursor = Cursor::new(&self.data);
+        let mut cursor = Cursor::new(self.data);
 
         cursor.seek(SeekFrom::Start((self.code_start + offset) as u64))?;
 
         Ok(cursor.read_i32::<LittleEndian>()?)
     }
 
-    fn read_next(&mut self) -> Result<i32> {
+    // Reads the next cell of the instruction starting at |address|.
+    fn read_next(&mut self, address: i32) -> Result<i32> {
+        if self.cursor < 0 || self.cursor + 4 > self.cursor_limit {
+            return Err(Error::TruncatedInstruction { address })
+        }
+
         let value: i32 = self.read_at(self.cursor)?;
         self.cursor += 4;
         Ok(value)
     }
 
-    fn read_next_op(&mut self) -> Result<V1OPCode> {
-        Ok(V1OPCode::try_from(self.read_next()? as u8).unwrap())
+    fn check_jump(&self, address: i32, target: i32) -> Result<()> {
+        // Bad targets don't stop decoding, so best effort mode keeps them as-is.
+        if self.mode == DecodeMode::BestEffort {
+            return Ok(())
+        }
+
+        if target < 0 || target >= self.cursor_limit || target % 4 != 0 {
+            return Err(Error::JumpOutOfRange { address, target })
+        }
+
+        Ok(())
+    }
+
+    fn decode_next(&mut self, address: i32, op: i32) -> Result<V1Instruction> {
+        let info = match OPCODE_LIST.get(&(op as u32)) {
+            Some(info) => info.clone(),
+            None => return Err(Error::InvalidOpcode { address, value: op }),
+        };
+
+        let mut insn: V1Instruction = V1Instruction {
+            address,
+            info,
+            params: Vec::new(),
+        };
+
+        if op == V1OPCode::CASETBL as i32 {
+            let ncases: i32 = self.read_next(address)?;
+
+            if ncases < 0 || (ncases as i64 * 8 + 4) > (self.cursor_limit - self.cursor) as i64 {
+                return Err(Error::TruncatedInstruction { address })
+            }
+
+            insn.params.resize(((ncases + 1) * 2) as usize, 0);
+
+            insn.params[0] = ncases;
+            insn.params[1] = self.read_next(address)?;
+
+            self.check_jump(address, insn.params[1])?;
+
+            for i in 0..ncases {
+                insn.params[(2 + i * 2) as usize] = self.read_next(address)?;
+                insn.params[(2 + i * 2 + 1) as usize] = self.read_next(address)?;
+
+                self.check_jump(address, insn.params[(2 + i * 2 + 1) as usize])?;
+            }
+
+            return Ok(insn);
+        }
+
+        insn.params.resize(insn.info.params.len(), 0);
+
+        for i in 0..insn.info.params.len() {
+            insn.params[i] = self.read_next(address)?;
+
+            if let V1Param::Jump = insn.info.params[i] {
+                self.check_jump(address, insn.params[i])?;
+            }
+        }
+
+        if op == V1OPCode::SWITCH as i32 {
+            self.check_jump(address, insn.params[0])?;
+        }
+
+        Ok(insn)
     }
 
     fn diassemble_internal(&mut self) -> Result<Vec<V1Instruction>> {
-        if self.read_next_op()? != V1OPCode::PROC {
+        if self.read_next(self.proc_offset)? != V1OPCode::PROC as i32 {
             return Err(Error::Other("Function does not start with PROC"))
         }
 
         let mut insns: Vec<V1Instruction> = Vec::new();
 
+        self.code_end = self.cursor_limit;
+
         while self.cursor < self.cursor_limit {
             let address: i32 = self.cursor;
 
-            let op: i32 = self.read_next()?;
+            let op: i32 = self.read_next(address)?;
 
             if op == V1OPCode::PROC as i32 || op == V1OPCode::ENDPROC as i32 {
+                self.code_end = address;
                 break;
             }
 
-            let mut insn: V1Instruction = V1Instruction {
-                address,
-                info: OPCODE_LIST.get(&(op as u32)).unwrap().clone(),
-                params: Vec::new(),
+            let insn = match self.decode_next(address, op) {
+                Ok(insn) => insn,
+                Err(Error::InvalidOpcode { .. }) if self.mode == DecodeMode::BestEffort => {
+                    self.cursor = address + 4;
+
+                    V1Instruction::unknown(address, op)
+                },
+                Err(Error::TruncatedInstruction { .. }) if self.mode == DecodeMode::BestEffort => {
+                    insns.push(V1Instruction::unknown(address, op));
+
+                    self.code_end = self.cursor_limit;
+                    break;
+                },
+                Err(e) => return Err(e),
             };
 
-            if op == V1OPCode::CASETBL as i32 {
-                let ncases: i32 = self.read_next()?;
+            insns.push(insn);
+        }
+
+        Ok(insns)
+    }
+
+    fn sweep_internal(&mut self) -> V1Sweep {
+        let mut sweep = V1Sweep::default();
+        let mut current: Option<V1Function> = None;
 
-                insn.params.resize(((ncases + 1) * 2) as usize, 0);
+        while self.cursor + 4 <= self.cursor_limit {
+            let address: i32 = self.cursor;
 
-                insn.params[0] = ncases;
-                insn.params[1] = self.read_next()?;
+            let op: i32 = match self.read_next(address) {
+                Ok(op) => op,
+                Err(_) => break,
+            };
 
-                for i in 0..ncases {
-                    insn.params[(2 + i * 2) as usize] = self.read_next()?;
-                    insn.params[(2 + i * 2 + 1) as usize] = self.read_next()?;
+            if op == V1OPCode::PROC as i32 || op == V1OPCode::ENDPROC as i32 {
+                if let Some(mut function) = current.take() {
+                    function.code_end = address;
+                    sweep.functions.push(function);
                 }
 
-                insns.push(insn);
+                if op == V1OPCode::PROC as i32 {
+                    current = Some(V1Function {
+                        name: format!("sub_{:x}", address),
+                        address,
+                        code_end: address,
+                        kind: V1FunctionKind::Called,
+                        instructions: Vec::new(),
+                    });
+                } else {
+                    add_range(&mut sweep.gaps, address, self.cursor);
+                }
 
                 continue;
             }
 
-            insn.params.resize(insn.info.params.len(), 0);
-
-            for i in 0..insn.info.params.len() {
-                insn.params[i] = self.read_next()?;
+            match (self.decode_next(address, op), &mut current) {
+                (Ok(insn), Some(function)) => function.instructions.push(insn),
+                (Ok(_), None) => add_range(&mut sweep.gaps, address, self.cursor),
+                (Err(_), current) => {
+                    // Resynchronize on the next cell.
+                    self.cursor = address + 4;
+
+                    match current {
+                        Some(function) => {
+                            function.instructions.push(V1Instruction::unknown(address, op));
+                            add_range(&mut sweep.data, address, self.cursor);
+                        },
+                        None => add_range(&mut sweep.gaps, address, self.cursor),
+                    }
+                },
             }
+        }
 
-            if op == V1OPCode::CALL as i32 {
-                let addr: i32 = insn.params[0];
+        match current.take() {
+            Some(mut function) => {
+                function.code_end = self.cursor_limit;
 
-                if !self.file.borrow().is_function_at_address(addr) {
-                    self.file.borrow_mut().called_functions.as_mut().unwrap().borrow_mut().add_function(addr as u32);
+                if self.cursor < self.cursor_limit {
+                    add_range(&mut sweep.data, self.cursor, self.cursor_limit);
                 }
-            }
 
-            insns.push(insn);
+                sweep.functions.push(function);
+            },
+            None if self.cursor < self.cursor_limit => add_range(&mut sweep.gaps, self.cursor, self.cursor_limit),
+            None => (),
         }
 
-        Ok(insns)
+        sweep
+    }
+
+    // Decodes the whole code section in order, starting a new function at
+    // every PROC. Undecodable cells are skipped one at a time.
+    pub fn sweep(data: &'a [u8], code: &SMXCodeV1Section) -> V1Sweep {
+        let mut disassembler: V1Disassembler = V1Disassembler::new(data, code, 0);
+
+        disassembler.set_mode(DecodeMode::BestEffort);
+
+        disassembler.sweep_internal()
     }
 
-    pub fn diassemble(file: Rc<RefCell<SMXFile>>, data: Vec<u8>, code: Rc<SMXCodeV1Section>, proc_offset: i32) -> Result<Vec<V1Instruction>> {
-        let mut disassembler: V1Disassembler = V1Disassembler::new(file, data, code, proc_offset);
+    pub fn diassemble(data: &'a [u8], code: Arc<SMXCodeV1Section>, proc_offset: i32) -> Result<Vec<V1Instruction>> {
+        let mut disassembler: V1Disassembler = V1Disassembler::new(data, &code, proc_offset);
 
         disassembler.diassemble_internal()
     }
-}
\ No newline at end of file
+
+    pub fn diassemble_function(data: &'a [u8], code: Arc<SMXCodeV1Section>, proc_offset: i32, name: String, kind: V1FunctionKind, mode: DecodeMode) -> Result<V1Function> {
+        let mut disassembler: V1Disassembler = V1Disassembler::new(data, &code, proc_offset);
+
+        disassembler.set_mode(mode);
+
+        let instructions = disassembler.diassemble_internal()?;
+
+        Ok(V1Function {
+            name,
+            address: disassembler.proc_offset,
+            code_end: disassembler.code_end,
+            kind,
+            instructions,
+        })
+    }
+}
@@ -1,5 +1,4 @@
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::sync::Arc;
 use std::fmt;
 use std::io::{Cursor};
 use byteorder::{ReadBytesExt, LittleEndian};
@@ -121,7 +120,7 @@ pub struct PublicEntry {
 impl PublicEntry {
     pub const SIZE: i32 = 8;
 
-    pub fn new<T>(data: T, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Vec<Self>>
+    pub fn new<T>(data: T, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Vec<Self>>
     where
         T: AsRef<[u8]>,
     {
@@ -142,7 +141,7 @@ impl PublicEntry {
             entries.push(Self {
                 address,
                 name_offset,
-                name: names.borrow_mut().string_at(name_offset)?,
+                name: names.string_at(name_offset)?,
             })
         }
 
@@ -170,7 +169,7 @@ pub struct NativeEntry {
 impl NativeEntry {
     pub const SIZE: i32 = 4;
 
-    pub fn new<T>(data: T, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Vec<Self>>
+    pub fn new<T>(data: T, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Vec<Self>>
     where
         T: AsRef<[u8]>,
     {
@@ -189,7 +188,7 @@ impl NativeEntry {
 
             entries.push(Self {
                 name_offset,
-                name: names.borrow_mut().string_at(name_offset)?,
+                name: names.string_at(name_offset)?,
             })
         }
 
@@ -213,7 +212,7 @@ pub struct PubvarEntry {
 impl PubvarEntry {
     pub const SIZE: i32 = 8;
 
-    pub fn new<T>(data: T, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Vec<Self>>
+    pub fn new<T>(data: T, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Vec<Self>>
     where
         T: AsRef<[u8]>,
     {
@@ -234,7 +233,7 @@ impl PubvarEntry {
             entries.push(Self {
                 address,
                 name_offset,
-                name: names.borrow_mut().string_at(name_offset)?,
+                name: names.string_at(name_offset)?,
             })
         }
 
@@ -273,15 +272,15 @@ impl TagEntry {
         Self::METHODMAP |
         Self::STRUCT;
 
-    pub fn new<T>(data: T, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Vec<Self>>
+    pub fn new<T>(data: T, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Vec<Self>>
     where
         T: AsRef<[u8]>,
     {
-        if section.size % NativeEntry::SIZE != 0 {
+        if section.size % Self::SIZE != 0 {
             return Err(Error::InvalidSize)
         }
 
-        let count: usize = (section.size / NativeEntry::SIZE) as usize;
+        let count: usize = (section.size / Self::SIZE) as usize;
 
         let mut entries: Vec<Self> = Vec::with_capacity(count);
 
@@ -294,7 +293,7 @@ impl TagEntry {
             entries.push(Self {
                 tag,
                 name_offset,
-                name: names.borrow_mut().string_at(name_offset)?,
+                name: names.string_at(name_offset)?,
             })
         }
 
@@ -346,7 +345,7 @@ pub struct DebugFileEntry {
 impl DebugFileEntry {
     pub const SIZE: i32 = 8;
 
-    pub fn new<T>(data: T, section: Rc<SectionEntry>, names: Rc<RefCell<SMXNameTable>>) -> Result<Vec<Self>>
+    pub fn new<T>(data: T, section: Arc<SectionEntry>, names: &SMXNameTable) -> Result<Vec<Self>>
     where
         T: AsRef<[u8]>,
     {
@@ -367,7 +366,7 @@ impl DebugFileEntry {
             entries.push(Self {
                 address,
                 name_offset,
-                name: names.borrow_mut().string_at(name_offset)?,
+                name: names.string_at(name_offset)?,
             })
         }
 
@@ -388,7 +387,7 @@ pub struct DebugLineEntry {
 impl DebugLineEntry {
     pub const SIZE: i32 = 8;
 
-    pub fn new<T>(data: T, section: Rc<SectionEntry>) -> Result<Vec<Self>>
+    pub fn new<T>(data: T, section: Arc<SectionEntry>) -> Result<Vec<Self>>
     where
         T: AsRef<[u8]>,
     {
@@ -416,7 +415,7 @@ impl DebugLineEntry {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SymbolScope {
     Global,
     Local,
@@ -458,7 +457,7 @@ pub struct DebugMethodEntry {
 }
 
 impl DebugMethodEntry {
-    pub fn new(cursor: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    pub fn new(cursor: &mut Cursor<&[u8]>) -> Result<Self> {
         Ok(Self {
             method_index: cursor.read_i32::<LittleEndian>()?,
             first_local: cursor.read_i32::<LittleEndian>()?,
@@ -483,7 +482,7 @@ pub struct DebugVarEntry {
 }
 
 impl DebugVarEntry {
-    pub fn new(cursor: &mut Cursor<Vec<u8>>) -> Result<Self>
+    pub fn new(cursor: &mut Cursor<&[u8]>) -> Result<Self>
     {
         Ok(Self {
             address: cursor.read_i32::<LittleEndian>()?,
@@ -495,3 +494,209 @@ impl DebugVarEntry {
         })
     }
 }
+
+// Kind of a legacy debug symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolIdent {
+    // A cell that can be fetched directly.
+    Variable,
+
+    // A variable that must be dereferenced.
+    Reference,
+
+    Array,
+
+    // An array passed by reference.
+    RefArray,
+
+    Function,
+
+    // Start of variadic arguments.
+    VarArgs,
+
+    Unknown(u8),
+}
+
+impl From<u8> for SymbolIdent {
+    fn from(ident: u8) -> Self {
+        match ident {
+            1 => Self::Variable,
+            2 => Self::Reference,
+            3 => Self::Array,
+            4 => Self::RefArray,
+            9 => Self::Function,
+            11 => Self::VarArgs,
+            _ => Self::Unknown(ident),
+        }
+    }
+}
+
+// A dimension of an array in the legacy debug tables.
+#[derive(Debug, Clone)]
+pub struct DebugArrayDim {
+    pub tag_id: i16,
+
+    pub size: u32,
+}
+
+impl DebugArrayDim {
+    // Packed files have no padding after |tag_id|.
+    pub fn new(cursor: &mut Cursor<&[u8]>, packed: bool) -> Result<Self> {
+        let tag_id = cursor.read_i16::<LittleEndian>()?;
+
+        if !packed {
+            cursor.read_u16::<LittleEndian>()?;
+        }
+
+        Ok(Self {
+            tag_id,
+            size: cursor.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+// The legacy ".dbg.symbols" section. Each symbol is followed by its array
+// dimensions.
+#[derive(Debug, Clone)]
+pub struct DebugSymbolEntry {
+    // Address relative to the data section or to the stack frame. Code
+    // address for functions.
+    pub address: i32,
+
+    pub tag_id: i16,
+
+    // Code range the symbol is valid in.
+    pub code_start: u32,
+
+    pub code_end: u32,
+
+    pub ident: SymbolIdent,
+
+    pub scope: SymbolScope,
+
+    // Offset into the .dbg.strings section.
+    pub name_offset: i32,
+
+    // Computed name.
+    pub name: String,
+
+    pub dims: Vec<DebugArrayDim>,
+}
+
+impl DebugSymbolEntry {
+    pub fn new(cursor: &mut Cursor<&[u8]>, packed: bool, names: &SMXNameTable) -> Result<Self> {
+        let address = cursor.read_i32::<LittleEndian>()?;
+        let tag_id = cursor.read_i16::<LittleEndian>()?;
+
+        if !packed {
+            cursor.read_u16::<LittleEndian>()?;
+        }
+
+        let code_start = cursor.read_u32::<LittleEndian>()?;
+        let code_end = cursor.read_u32::<LittleEndian>()?;
+        let ident = SymbolIdent::from(cursor.read_u8()?);
+        let scope = SymbolScope::from(cursor.read_u8()?);
+        let dim_count = cursor.read_u16::<LittleEndian>()?;
+        let name_offset = cursor.read_i32::<LittleEndian>()?;
+
+        let mut dims: Vec<DebugArrayDim> = Vec::with_capacity(dim_count as usize);
+
+        for _ in 0..dim_count {
+            dims.push(DebugArrayDim::new(cursor, packed)?);
+        }
+
+        Ok(Self {
+            address,
+            tag_id,
+            code_start,
+            code_end,
+            ident,
+            scope,
+            name_offset,
+            name: names.string_at(name_offset)?,
+            dims,
+        })
+    }
+}
+
+// An argument of a native in the legacy ".dbg.natives" section. Unlike the
+// symbols, these are byte-packed regardless of the header flag.
+#[derive(Debug, Clone)]
+pub struct DebugNativeArg {
+    pub ident: SymbolIdent,
+
+    pub tag_id: i16,
+
+    // Offset into the .dbg.strings section.
+    pub name_offset: i32,
+
+    // Computed name.
+    pub name: String,
+
+    pub dims: Vec<DebugArrayDim>,
+}
+
+impl DebugNativeArg {
+    pub fn new(cursor: &mut Cursor<&[u8]>, names: &SMXNameTable) -> Result<Self> {
+        let ident = SymbolIdent::from(cursor.read_u8()?);
+        let tag_id = cursor.read_i16::<LittleEndian>()?;
+        let dim_count = cursor.read_u16::<LittleEndian>()?;
+        let name_offset = cursor.read_i32::<LittleEndian>()?;
+
+        let mut dims: Vec<DebugArrayDim> = Vec::with_capacity(dim_count as usize);
+
+        for _ in 0..dim_count {
+            dims.push(DebugArrayDim::new(cursor, true)?);
+        }
+
+        Ok(Self {
+            ident,
+            tag_id,
+            name_offset,
+            name: names.string_at(name_offset)?,
+            dims,
+        })
+    }
+}
+
+// The legacy ".dbg.natives" section. It only exists in files that are not
+// debug packed, but the compiler always writes its arguments byte-packed.
+#[derive(Debug, Clone)]
+pub struct DebugNativeEntry {
+    // Index into the .natives table.
+    pub index: u32,
+
+    // Offset into the .dbg.strings section.
+    pub name_offset: i32,
+
+    // Computed name.
+    pub name: String,
+
+    // Return tag.
+    pub tag_id: i16,
+
+    pub args: Vec<DebugNativeArg>,
+}
+
+impl DebugNativeEntry {
+    pub fn new(cursor: &mut Cursor<&[u8]>, names: &SMXNameTable) -> Result<Self> {
+        let index = cursor.read_u32::<LittleEndian>()?;
+        let name_offset = cursor.read_i32::<LittleEndian>()?;
+        let tag_id = cursor.read_i16::<LittleEndian>()?;
+        let arg_count = cursor.read_u16::<LittleEndian>()?;
+
+        let mut args: Vec<DebugNativeArg> = Vec::with_capacity(arg_count as usize);
+
+        for _ in 0..arg_count {
+            args.push(DebugNativeArg::new(cursor, names)?);
+        }
+
+        Ok(Self {
+            index,
+            name_offset,
+            name: names.string_at(name_offset)?,
+            tag_id,
+            args,
+        })
+    }
+}
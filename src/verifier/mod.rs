@@ -0,0 +1,411 @@
+use std::fmt;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use crate::cfg::{BasicBlock, ControlFlowGraph};
+use crate::file::SMXFile;
+use crate::v1disassembler::{V1Function, V1Instruction};
+use crate::v1opcodes::V1OPCode;
+
+// Stack and heap usage of a function at some point, relative to its entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StackState {
+    // Bytes pushed or reserved on the stack.
+    pub stack: i32,
+
+    // Bytes allocated on the heap with HEAP.
+    pub heap: i32,
+
+    // Heap allocations of unknown size, from GENARRAY and TRACKER.PUSH.C,
+    // that TRACKER.POP.SETHEAP has not released yet.
+    pub trackers: i32,
+}
+
+impl fmt::Display for StackState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stack {}, heap {}, trackers {}", self.stack, self.heap, self.trackers)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    // Two paths reach the same instruction with different stack or heap usage.
+    UnbalancedPaths { expected: StackState, found: StackState },
+
+    // More was popped off the stack than the function pushed.
+    NegativeStack { depth: i32 },
+
+    // More heap was freed than the function allocated.
+    NegativeHeap { size: i32 },
+
+    // TRACKER.POP.SETHEAP without a matching allocation.
+    TrackerUnderflow,
+
+    // CALL not preceded by a constant argument count.
+    UnknownArgumentCount,
+
+    // RETN with values left on the stack.
+    StackNotEmpty { depth: i32 },
+
+    // RETN without freeing every heap allocation.
+    HeapNotEmpty { size: i32, trackers: i32 },
+
+    // Stack or heap usage that no longer fits in a cell.
+    UsageOverflow,
+
+    // CodeV1Header::cell_size is not 4.
+    InvalidCellSize { size: u8 },
+
+    // A cell that does not decode as an instruction.
+    InvalidInstruction { value: i32 },
+
+    // Jump or case target that is not an instruction of the same function.
+    InvalidJumpTarget { target: i32 },
+
+    // CALL or LDGFN.PRI target that is not a PROC.
+    InvalidCallTarget { target: i32 },
+
+    // Native index outside of the .natives table.
+    InvalidNativeIndex { index: i32 },
+
+    // Global address outside of DataHeader::memory_size.
+    InvalidDataAddress { address: i32 },
+
+    // SWITCH whose target is not a CASETBL of the same function.
+    MissingCaseTable { target: i32 },
+
+    // CASETBL that no SWITCH points to.
+    StrayCaseTable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    // Address of the offending instruction.
+    pub address: i32,
+
+    // Address of the function containing it, None for checks of the file
+    // itself.
+    pub function: Option<i32>,
+
+    pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}: ", self.address)?;
+
+        match &self.kind {
+            DiagnosticKind::UnbalancedPaths { expected, found } => write!(f, "paths disagree on stack usage ({} vs {})", expected, found),
+            DiagnosticKind::NegativeStack { depth } => write!(f, "stack depth is negative ({})", depth),
+            DiagnosticKind::NegativeHeap { size } => write!(f, "heap size is negative ({})", size),
+            DiagnosticKind::TrackerUnderflow => write!(f, "tracker.pop.setheap without an allocation"),
+            DiagnosticKind::UnknownArgumentCount => write!(f, "call without a constant argument count"),
+            DiagnosticKind::StackNotEmpty { depth } => write!(f, "retn with {} bytes left on the stack", depth),
+            DiagnosticKind::HeapNotEmpty { size, trackers } => write!(f, "retn with {} heap bytes and {} trackers left", size, trackers),
+            DiagnosticKind::UsageOverflow => write!(f, "stack or heap usage overflows a cell"),
+            DiagnosticKind::InvalidCellSize { size } => write!(f, "cell size is {}, not 4", size),
+            DiagnosticKind::InvalidInstruction { value } => write!(f, "invalid instruction {}", value),
+            DiagnosticKind::InvalidJumpTarget { target } => write!(f, "jump target {:#x} is not an instruction of this function", target),
+            DiagnosticKind::InvalidCallTarget { target } => write!(f, "call target {:#x} is not a function", target),
+            DiagnosticKind::InvalidNativeIndex { index } => write!(f, "native index {} is out of range", index),
+            DiagnosticKind::InvalidDataAddress { address } => write!(f, "data address {:#x} is out of range", address),
+            DiagnosticKind::MissingCaseTable { target } => write!(f, "switch target {:#x} is not a casetbl", target),
+            DiagnosticKind::StrayCaseTable => write!(f, "casetbl is not the target of a switch"),
+        }
+    }
+}
+
+// Tracks stack depth and heap usage through a function's control flow graph,
+// like the SourcePawn VM's method verifier. Every path must agree on the
+// usage where it joins another, and must be back to zero at RETN.
+pub struct StackAnalysis {
+    // State on entry to each block, None if it is unreachable.
+    states: Vec<Option<StackState>>,
+
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl StackAnalysis {
+    pub fn new(function: &V1Function) -> Self {
+        StackAnalysis::from_cfg(function, &ControlFlowGraph::new(function))
+    }
+
+    pub fn from_cfg(function: &V1Function, cfg: &ControlFlowGraph) -> Self {
+        let mut analysis = Self {
+            states: vec![None; cfg.len()],
+            diagnostics: Vec::new(),
+        };
+
+        if cfg.is_empty() {
+            return analysis
+        }
+
+        analysis.states[0] = Some(StackState::default());
+
+        let mut worklist: Vec<usize> = vec![0];
+
+        // Each block is simulated once, from the first state that reaches it.
+        // Later arrivals are only compared against that state.
+        while let Some(id) = worklist.pop() {
+            let block = &cfg.blocks()[id];
+
+            let out = match analysis.states[id] {
+                Some(state) => analysis.simulate(function, block, state),
+                None => continue,
+            };
+
+            for edge in &block.successors {
+                match analysis.states[edge.target] {
+                    None => {
+                        analysis.states[edge.target] = Some(out);
+                        worklist.push(edge.target);
+                    },
+                    Some(expected) if expected != out => {
+                        analysis.report(cfg.blocks()[edge.target].start, function, DiagnosticKind::UnbalancedPaths {
+                            expected,
+                            found: out,
+                        });
+                    },
+                    Some(_) => (),
+                }
+            }
+        }
+
+        analysis.diagnostics.sort_by_key(|diagnostic| diagnostic.address);
+
+        analysis
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    // State on entry to block |id|.
+    pub fn state(&self, id: usize) -> Option<StackState> {
+        self.states.get(id).copied().flatten()
+    }
+
+    fn report(&mut self, address: i32, function: &V1Function, kind: DiagnosticKind) {
+        let diagnostic = Diagnostic {
+            address,
+            function: Some(function.address),
+            kind,
+        };
+
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn simulate(&mut self, function: &V1Function, block: &BasicBlock, mut state: StackState) -> StackState {
+        let mut previous: Option<&V1Instruction> = None;
+
+        for insn in &block.instructions {
+            let params = &insn.params;
+            let cells = params.len() as i64 * 4;
+
+            // Operands come from the file, so the changes are computed wide
+            // and only applied if the result still fits in a cell.
+            let mut stack: i64 = 0;
+            let mut heap: i64 = 0;
+
+            match insn.info.opcode {
+                V1OPCode::PUSH_PRI | V1OPCode::PUSH_ALT => stack = 4,
+                V1OPCode::PUSH_C | V1OPCode::PUSH2_C | V1OPCode::PUSH3_C | V1OPCode::PUSH4_C | V1OPCode::PUSH5_C |
+                V1OPCode::PUSH | V1OPCode::PUSH2 | V1OPCode::PUSH3 | V1OPCode::PUSH4 | V1OPCode::PUSH5 |
+                V1OPCode::PUSH_S | V1OPCode::PUSH2_S | V1OPCode::PUSH3_S | V1OPCode::PUSH4_S | V1OPCode::PUSH5_S |
+                V1OPCode::PUSH_ADR | V1OPCode::PUSH2_ADR | V1OPCode::PUSH3_ADR | V1OPCode::PUSH4_ADR | V1OPCode::PUSH5_ADR => stack = cells,
+                V1OPCode::POP_PRI | V1OPCode::POP_ALT => stack = -4,
+                V1OPCode::STACK => stack = -(params[0] as i64),
+                V1OPCode::HEAP => heap = params[0] as i64,
+                V1OPCode::CALL => {
+                    // The argument count is pushed last, and popped by RETN
+                    // along with the arguments.
+                    let argc = previous.and_then(|previous| match previous.info.opcode {
+                        V1OPCode::PUSH_C | V1OPCode::PUSH2_C | V1OPCode::PUSH3_C | V1OPCode::PUSH4_C | V1OPCode::PUSH5_C => previous.params.last().copied(),
+                        _ => None,
+                    });
+
+                    match argc {
+                        Some(argc) => stack = -(argc as i64 + 1) * 4,
+                        None => self.report(insn.address, function, DiagnosticKind::UnknownArgumentCount),
+                    }
+                },
+                V1OPCode::SYSREQ_N => stack = -(params[1] as i64) * 4,
+                V1OPCode::GENARRAY | V1OPCode::GENARRAY_Z => {
+                    stack = -(params[0] as i64 - 1) * 4;
+                    state.trackers += 1;
+                },
+                V1OPCode::TRACKER_PUSH_C => state.trackers += 1,
+                V1OPCode::TRACKER_POP_SETHEAP => {
+                    if state.trackers == 0 {
+                        self.report(insn.address, function, DiagnosticKind::TrackerUnderflow);
+                    } else {
+                        state.trackers -= 1;
+                    }
+                },
+                V1OPCode::RETN => {
+                    if state.stack != 0 {
+                        self.report(insn.address, function, DiagnosticKind::StackNotEmpty { depth: state.stack });
+                    }
+
+                    if state.heap != 0 || state.trackers != 0 {
+                        self.report(insn.address, function, DiagnosticKind::HeapNotEmpty {
+                            size: state.heap,
+                            trackers: state.trackers,
+                        });
+                    }
+                },
+                _ => (),
+            }
+
+            let adjusted = (
+                i32::try_from(state.stack as i64 + stack),
+                i32::try_from(state.heap as i64 + heap),
+            );
+
+            match adjusted {
+                (Ok(stack), Ok(heap)) => {
+                    state.stack = stack;
+                    state.heap = heap;
+                },
+                _ => {
+                    // Nothing after this point in the block can be trusted.
+                    self.report(insn.address, function, DiagnosticKind::UsageOverflow);
+
+                    return state
+                },
+            }
+
+            if state.stack < 0 {
+                self.report(insn.address, function, DiagnosticKind::NegativeStack { depth: state.stack });
+            }
+
+            if state.heap < 0 {
+                self.report(insn.address, function, DiagnosticKind::NegativeHeap { size: state.heap });
+            }
+
+            previous = Some(insn);
+        }
+
+        state
+    }
+}
+
+// Checks the code section the way the SourcePawn VM validates a plugin before
+// running it: the cell size, instruction encoding, jump, call and switch
+// targets, native indices and global addresses, and the stack analysis of
+// every function.
+// Functions are found by a linear sweep, so unreferenced code is checked too.
+pub fn verify(file: &SMXFile) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    let code = match &file.codev1 {
+        Some(code) => code,
+        None => return diagnostics,
+    };
+
+    let cell_size = code.header().cell_size;
+
+    if cell_size != 4 {
+        diagnostics.push(Diagnostic {
+            address: 0,
+            function: None,
+            kind: DiagnosticKind::InvalidCellSize { size: cell_size },
+        });
+    }
+
+    let natives = file.natives.as_ref().map_or(0, |natives| natives.size() as i32);
+    let memory_size = file.data.as_ref().map_or(0, |data| data.header().memory_size as i32);
+
+    for function in &code.sweep().functions {
+        let addresses: HashSet<i32> = function.instructions.iter().map(|insn| insn.address).collect();
+
+        // The compiler puts a switch's case table after the code of its
+        // cases, so a SWITCH and its CASETBL are not always adjacent. Rather
+        // than requiring the CASETBL to follow the SWITCH, each SWITCH must
+        // target a CASETBL of its own function, and each CASETBL must be the
+        // target of a SWITCH.
+        let casetbls: HashSet<i32> = function.instructions.iter()
+            .filter(|insn| insn.info.opcode == V1OPCode::CASETBL)
+            .map(|insn| insn.address)
+            .collect();
+
+        let switches: HashSet<i32> = function.instructions.iter()
+            .filter(|insn| insn.info.opcode == V1OPCode::SWITCH)
+            .map(|insn| insn.params[0])
+            .collect();
+
+        for insn in &function.instructions {
+            let params = &insn.params;
+
+            let mut report = |kind: DiagnosticKind| diagnostics.push(Diagnostic {
+                address: insn.address,
+                function: Some(function.address),
+                kind,
+            });
+
+            let mut check_jump = |target: i32| {
+                if !addresses.contains(&target) {
+                    report(DiagnosticKind::InvalidJumpTarget { target });
+                }
+            };
+
+            if insn.is_unknown() {
+                report(DiagnosticKind::InvalidInstruction { value: params[0] });
+                continue;
+            }
+
+            match insn.info.opcode {
+                V1OPCode::JUMP | V1OPCode::JZER | V1OPCode::JNZ | V1OPCode::JEQ | V1OPCode::JNEQ |
+                V1OPCode::JSLESS | V1OPCode::JSLEQ | V1OPCode::JSGRTR | V1OPCode::JSGEQ => check_jump(params[0]),
+                V1OPCode::CASETBL => {
+                    check_jump(params[1]);
+
+                    for case in params[2..].chunks(2) {
+                        check_jump(case[1]);
+                    }
+
+                    if !switches.contains(&insn.address) {
+                        report(DiagnosticKind::StrayCaseTable);
+                    }
+                },
+                V1OPCode::SWITCH if !casetbls.contains(&params[0]) => {
+                    report(DiagnosticKind::MissingCaseTable { target: params[0] });
+                },
+                V1OPCode::CALL | V1OPCode::LDGFN_PRI if !code.is_proc_at(params[0]) => {
+                    report(DiagnosticKind::InvalidCallTarget { target: params[0] });
+                },
+                V1OPCode::SYSREQ_C | V1OPCode::SYSREQ_N if params[0] < 0 || params[0] >= natives => {
+                    report(DiagnosticKind::InvalidNativeIndex { index: params[0] });
+                },
+                V1OPCode::LOAD_PRI | V1OPCode::LOAD_ALT | V1OPCode::LOAD_BOTH | V1OPCode::STOR_PRI | V1OPCode::STOR_ALT |
+                V1OPCode::ZERO | V1OPCode::INC | V1OPCode::DEC | V1OPCode::CONST | V1OPCode::REBASE |
+                V1OPCode::PUSH | V1OPCode::PUSH2 | V1OPCode::PUSH3 | V1OPCode::PUSH4 | V1OPCode::PUSH5 => {
+                    // Only CONST and REBASE have other operands after the
+                    // address.
+                    let count = match insn.info.opcode {
+                        V1OPCode::CONST | V1OPCode::REBASE => 1,
+                        _ => params.len(),
+                    };
+
+                    for &address in &params[..count] {
+                        if address < 0 || address as i64 + 4 > memory_size as i64 {
+                            report(DiagnosticKind::InvalidDataAddress { address });
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        diagnostics.extend(StackAnalysis::new(function).diagnostics().iter().cloned());
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.address);
+
+    diagnostics
+}
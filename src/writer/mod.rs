@@ -0,0 +1,181 @@
+use std::io::Write;
+use byteorder::{WriteBytesExt, LittleEndian};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use crate::headers::{SMXHeader, CompressionType};
+use crate::errors::{Result, Error};
+
+// Size of one entry in the section table.
+const SECTION_ENTRY_SIZE: usize = 12;
+
+// Serializes an SMX container. The layout matches spcomp: the header, the
+// section table, the string table and then each section payload in order,
+// with compression starting at the first payload. Writing the sections of an
+// unmodified file reproduces it byte-for-byte.
+#[derive(Debug, Clone)]
+pub struct SMXWriter {
+    version: u16,
+
+    compression_type: CompressionType,
+
+    sections: Vec<(String, Vec<u8>)>,
+}
+
+impl Default for SMXWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SMXWriter {
+    pub fn new() -> Self {
+        Self {
+            version: SMXHeader::SP1_VERSION_MAX,
+            compression_type: CompressionType::CompressionGZ,
+            sections: Vec::new(),
+        }
+    }
+
+    // Copies the version, compression type and every section of a parsed file.
+    pub fn from_header(header: &SMXHeader) -> Result<Self> {
+        let mut writer = Self {
+            version: header.version,
+            compression_type: header.compression_type.clone(),
+            sections: Vec::with_capacity(header.sections.len()),
+        };
+
+        for section in &header.sections {
+            let start = section.data_offset as usize;
+            let end = start + section.size as usize;
+
+            if end > header.data.len() {
+                return Err(Error::SizeOverflow);
+            }
+
+            writer.sections.push((section.name.clone(), header.data[start..end].to_vec()));
+        }
+
+        Ok(writer)
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: u16) {
+        self.version = version;
+    }
+
+    pub fn compression_type(&self) -> CompressionType {
+        self.compression_type.clone()
+    }
+
+    pub fn set_compression_type(&mut self, compression_type: CompressionType) {
+        self.compression_type = compression_type;
+    }
+
+    pub fn sections(&self) -> &Vec<(String, Vec<u8>)> {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&[u8]> {
+        self.sections.iter().find(|(n, _)| n == name).map(|(_, data)| data.as_slice())
+    }
+
+    // Replaces the payload of an existing section, or appends a new one.
+    pub fn set_section(&mut self, name: &str, data: Vec<u8>) {
+        match self.sections.iter_mut().find(|(n, _)| n == name) {
+            Some(section) => section.1 = data,
+            None => self.sections.push((name.into(), data)),
+        }
+    }
+
+    // Returns true if the section existed.
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        let len = self.sections.len();
+
+        self.sections.retain(|(n, _)| n != name);
+
+        self.sections.len() != len
+    }
+
+    pub fn write(&self) -> Result<Vec<u8>> {
+        if self.sections.len() > u8::MAX as usize {
+            return Err(Error::Other("Too many sections"));
+        }
+
+        let mut strings: Vec<u8> = Vec::new();
+        let mut name_offsets: Vec<i32> = Vec::with_capacity(self.sections.len());
+
+        for (name, _) in &self.sections {
+            name_offsets.push(strings.len() as i32);
+            strings.extend(name.as_bytes());
+            strings.push(0);
+        }
+
+        let string_table_offset = SMXHeader::HEADER_SIZE as usize + self.sections.len() * SECTION_ENTRY_SIZE;
+        let data_offset = string_table_offset + strings.len();
+
+        let image_size = data_offset + self.sections.iter().map(|(_, data)| data.len()).sum::<usize>();
+
+        if image_size > i32::MAX as usize {
+            return Err(Error::SizeOverflow);
+        }
+
+        let mut image: Vec<u8> = Vec::with_capacity(image_size);
+
+        // The header is filled in once the disk size is known.
+        image.resize(SMXHeader::HEADER_SIZE as usize, 0);
+
+        let mut offset = data_offset;
+
+        for ((_, data), name_offset) in self.sections.iter().zip(name_offsets) {
+            image.write_i32::<LittleEndian>(name_offset)?;
+            image.write_i32::<LittleEndian>(offset as i32)?;
+            image.write_i32::<LittleEndian>(data.len() as i32)?;
+
+            offset += data.len();
+        }
+
+        image.extend(&strings);
+
+        for (_, data) in &self.sections {
+            image.extend(data);
+        }
+
+        let (compression_byte, mut out) = match self.compression_type {
+            CompressionType::CompressionNone => (0u8, image),
+            CompressionType::CompressionGZ => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+
+                encoder.write_all(&image[data_offset..])?;
+
+                let mut out = image[..data_offset].to_vec();
+
+                out.extend(encoder.finish()?);
+
+                (1u8, out)
+            },
+            CompressionType::CompressionUnknown => {
+                return Err(Error::Other("Unknown compression"))
+            },
+        };
+
+        let disk_size = out.len() as i32;
+
+        let mut header: Vec<u8> = Vec::with_capacity(SMXHeader::HEADER_SIZE as usize);
+
+        header.write_u32::<LittleEndian>(SMXHeader::FILE_MAGIC)?;
+        header.write_u16::<LittleEndian>(self.version)?;
+        header.write_u8(compression_byte)?;
+        header.write_i32::<LittleEndian>(disk_size)?;
+        header.write_i32::<LittleEndian>(image_size as i32)?;
+        header.write_u8(self.sections.len() as u8)?;
+        header.write_i32::<LittleEndian>(string_table_offset as i32)?;
+        header.write_i32::<LittleEndian>(data_offset as i32)?;
+
+        out[..SMXHeader::HEADER_SIZE as usize].copy_from_slice(&header);
+
+        Ok(out)
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use crate::file::SMXFile;
+use crate::sections::SMXCodeV1Section;
+use crate::v1disassembler::{V1Function, V1Instruction, V1Param};
+use crate::v1opcodes::V1OPCode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XRefKind {
+    // CALL to a function.
+    Call,
+
+    // Function address taken with LDGFN.PRI or loaded as a constant, e.g. a
+    // callback.
+    Reference,
+
+    // SYSREQ.C or SYSREQ.N.
+    Native,
+
+    // Global loaded or pushed.
+    Read,
+
+    // Global stored, zeroed, incremented or decremented.
+    Write,
+
+    // Constant that points into .data, e.g. the address of a global array.
+    Address,
+
+    // J* branch target.
+    Jump,
+
+    // SWITCH to a case table, or a case (or the default) of that table.
+    Switch,
+}
+
+#[derive(Debug, Clone)]
+pub struct XRef {
+    // Address of the referencing instruction.
+    pub address: i32,
+
+    // Address of the function containing that instruction.
+    pub function: i32,
+
+    pub kind: XRefKind,
+}
+
+// Cross-references between the disassembled functions and the functions,
+// natives, globals and labels they use. Each list is in address order.
+#[derive(Default)]
+pub struct XRefIndex {
+    functions: BTreeMap<i32, Vec<XRef>>,
+
+    natives: BTreeMap<i32, Vec<XRef>>,
+
+    globals: BTreeMap<i32, Vec<XRef>>,
+
+    labels: BTreeMap<i32, Vec<XRef>>,
+
+    native_names: Vec<String>,
+}
+
+impl XRefIndex {
+    pub fn new(file: &SMXFile) -> Self {
+        let mut index = Self::default();
+
+        let data_size = match &file.data {
+            Some(data) => data.header().data_size as i32,
+            None => 0,
+        };
+
+        if let Some(natives) = &file.natives {
+            index.native_names = natives.entries().into_iter().map(|native| native.name).collect();
+        }
+
+        let code = file.codev1.as_deref();
+
+        for function in file.functions().values() {
+            index.add_function(function, code, data_size);
+        }
+
+        index
+    }
+
+    // Instructions that call, or take the address of, the function at |addr|.
+    pub fn function_refs(&self, addr: i32) -> &[XRef] {
+        XRefIndex::lookup(&self.functions, addr)
+    }
+
+    // Instructions that call the function at |addr|.
+    pub fn callers(&self, addr: i32) -> Vec<&XRef> {
+        self.function_refs(addr).iter().filter(|xref| xref.kind == XRefKind::Call).collect()
+    }
+
+    // Instructions that invoke the native at |index| in the .natives table.
+    pub fn native_refs(&self, index: i32) -> &[XRef] {
+        XRefIndex::lookup(&self.natives, index)
+    }
+
+    // Instructions that invoke the native called |name|.
+    pub fn native_refs_by_name(&self, name: &str) -> &[XRef] {
+        match self.native_names.iter().position(|native| native == name) {
+            Some(index) => self.native_refs(index as i32),
+            None => &[],
+        }
+    }
+
+    // Instructions that read, write or take the address of the global at
+    // |addr|.
+    pub fn global_refs(&self, addr: i32) -> &[XRef] {
+        XRefIndex::lookup(&self.globals, addr)
+    }
+
+    // Instructions that jump or switch to |addr|.
+    pub fn label_refs(&self, addr: i32) -> &[XRef] {
+        XRefIndex::lookup(&self.labels, addr)
+    }
+
+    // Every referenced function address.
+    pub fn functions(&self) -> impl Iterator<Item = &i32> {
+        self.functions.keys()
+    }
+
+    // Every referenced native index.
+    pub fn natives(&self) -> impl Iterator<Item = &i32> {
+        self.natives.keys()
+    }
+
+    // Every referenced global address.
+    pub fn globals(&self) -> impl Iterator<Item = &i32> {
+        self.globals.keys()
+    }
+
+    // Every jump or switch target.
+    pub fn labels(&self) -> impl Iterator<Item = &i32> {
+        self.labels.keys()
+    }
+
+    fn lookup(map: &BTreeMap<i32, Vec<XRef>>, key: i32) -> &[XRef] {
+        match map.get(&key) {
+            Some(xrefs) => xrefs,
+            None => &[],
+        }
+    }
+
+    fn add(map: &mut BTreeMap<i32, Vec<XRef>>, key: i32, insn: &V1Instruction, function: &V1Function, kind: XRefKind) {
+        map.entry(key).or_default().push(XRef {
+            address: insn.address,
+            function: function.address,
+            kind,
+        });
+    }
+
+    fn add_function(&mut self, function: &V1Function, code: Option<&SMXCodeV1Section>, data_size: i32) {
+        let is_global = |addr: i32| addr >= 0 && addr < data_size;
+
+        for insn in &function.instructions {
+            let params = &insn.params;
+
+            match insn.info.opcode {
+                V1OPCode::CALL => XRefIndex::add(&mut self.functions, params[0], insn, function, XRefKind::Call),
+                V1OPCode::LDGFN_PRI => XRefIndex::add(&mut self.functions, params[0], insn, function, XRefKind::Reference),
+                V1OPCode::SYSREQ_C | V1OPCode::SYSREQ_N => XRefIndex::add(&mut self.natives, params[0], insn, function, XRefKind::Native),
+                V1OPCode::CASETBL => {
+                    XRefIndex::add(&mut self.labels, params[1], insn, function, XRefKind::Switch);
+
+                    for case in params[2..].chunks(2) {
+                        XRefIndex::add(&mut self.labels, case[1], insn, function, XRefKind::Switch);
+                    }
+                },
+                V1OPCode::SWITCH => XRefIndex::add(&mut self.labels, params[0], insn, function, XRefKind::Switch),
+                V1OPCode::LOAD_PRI | V1OPCode::LOAD_ALT | V1OPCode::LOAD_BOTH |
+                V1OPCode::PUSH | V1OPCode::PUSH2 | V1OPCode::PUSH3 | V1OPCode::PUSH4 | V1OPCode::PUSH5 => {
+                    for &addr in params {
+                        XRefIndex::add(&mut self.globals, addr, insn, function, XRefKind::Read);
+                    }
+                },
+                V1OPCode::STOR_PRI | V1OPCode::STOR_ALT | V1OPCode::ZERO | V1OPCode::INC | V1OPCode::DEC |
+                V1OPCode::CONST => XRefIndex::add(&mut self.globals, params[0], insn, function, XRefKind::Write),
+                V1OPCode::CONST_PRI | V1OPCode::CONST_ALT |
+                V1OPCode::PUSH_C | V1OPCode::PUSH2_C | V1OPCode::PUSH3_C | V1OPCode::PUSH4_C | V1OPCode::PUSH5_C => {
+                    for &value in params {
+                        // Code and data addresses both start at 0, so a
+                        // constant landing on a PROC may still be the address
+                        // of a global. Record both.
+                        if code.is_some_and(|code| code.is_proc_at(value)) {
+                            XRefIndex::add(&mut self.functions, value, insn, function, XRefKind::Reference);
+                        }
+
+                        if is_global(value) {
+                            XRefIndex::add(&mut self.globals, value, insn, function, XRefKind::Address);
+                        }
+                    }
+                },
+                _ => {
+                    for (param, &value) in insn.info.params.iter().zip(params.iter()) {
+                        if let V1Param::Jump = param {
+                            XRefIndex::add(&mut self.labels, value, insn, function, XRefKind::Jump);
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
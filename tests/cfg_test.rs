@@ -0,0 +1,49 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::cfg::ControlFlowGraph;
+
+#[test]
+fn test_cfg() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let mut loops = 0;
+
+    for function in f.functions().values() {
+        let cfg = ControlFlowGraph::new(function);
+
+        let count: usize = cfg.blocks().iter().map(|block| block.instructions.len()).sum();
+
+        assert_eq!(count, function.instructions.len());
+
+        for block in cfg.blocks() {
+            for edge in &block.successors {
+                assert!(cfg.blocks()[edge.target].predecessors.contains(&block.id));
+            }
+
+            if cfg.is_reachable(block.id) {
+                assert!(cfg.dominates(0, block.id));
+            }
+
+            assert_eq!(cfg.block_at(block.start).unwrap().id, block.id);
+        }
+
+        for &header in cfg.loop_headers() {
+            assert!(cfg.blocks()[header].predecessors.iter().any(|&pred| cfg.dominates(header, pred)));
+        }
+
+        loops += cfg.loop_headers().len();
+    }
+
+    assert!(loops > 0);
+}
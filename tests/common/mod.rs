@@ -0,0 +1,50 @@
+// Helpers for the tests that patch a copy of the fixture. Not every test uses
+// all of them.
+#![allow(dead_code)]
+
+use smxdasm::v1disassembler::V1Function;
+use smxdasm::v1opcodes::V1OPCode;
+use smxdasm::writer::SMXWriter;
+
+// Overwrites |bytes| at |offset| from the start of section |name|.
+pub fn patch_section(writer: &mut SMXWriter, name: &str, offset: usize, bytes: &[u8]) {
+    let mut section = writer.section(name).unwrap().to_vec();
+
+    section[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+    writer.set_section(name, section);
+}
+
+// Overwrites the cells at code address |addr|.
+pub fn patch_code(writer: &mut SMXWriter, addr: i32, cells: &[i32]) {
+    let code = writer.section(".code").unwrap();
+
+    // CodeV1Header::code_offset.
+    let code_offset = i32::from_le_bytes([code[12], code[13], code[14], code[15]]) as usize;
+
+    patch_section(writer, ".code", code_offset + addr as usize, &to_bytes(cells));
+}
+
+// Overwrites the cells at data address |addr|.
+pub fn patch_data(writer: &mut SMXWriter, addr: i32, cells: &[i32]) {
+    let data = writer.section(".data").unwrap();
+
+    // DataHeader::data_offset.
+    let data_offset = u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize;
+
+    patch_section(writer, ".data", data_offset + addr as usize, &to_bytes(cells));
+}
+
+// Replaces the body of |function|, after its PROC, with |body|. The rest of
+// the old body becomes NOPs.
+pub fn patch_function(writer: &mut SMXWriter, function: &V1Function, body: &[i32]) {
+    let mut cells = body.to_vec();
+
+    cells.resize(((function.code_end - function.address) / 4 - 1) as usize, V1OPCode::NOP as i32);
+
+    patch_code(writer, function.address + 4, &cells);
+}
+
+fn to_bytes(cells: &[i32]) -> Vec<u8> {
+    cells.iter().flat_map(|cell| cell.to_le_bytes()).collect()
+}
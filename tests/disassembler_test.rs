@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::errors::Error;
+use smxdasm::file::SMXFile;
+use smxdasm::v1disassembler::DecodeMode;
+
+// Returns the decompressed image of the test plugin, marked as uncompressed
+// so it can be patched and parsed again.
+fn uncompressed_image() -> Vec<u8> {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let header = smxdasm::headers::SMXHeader::new(data).unwrap();
+
+    let mut image = header.data.to_vec();
+
+    image[6] = 0;
+    image[7..11].copy_from_slice(&header.image_size.to_le_bytes());
+
+    image
+}
+
+#[test]
+fn test_invalid_opcode() {
+    let mut image = uncompressed_image();
+
+    let (code_start, address) = {
+        let p = SMXFile::new(&image).unwrap();
+        let f = &*p;
+        let function = f.functions().values().next().unwrap();
+
+        (f.codev1.as_ref().unwrap().code_start(), function.instructions[0].address)
+    };
+
+    let offset = (code_start + address) as usize;
+
+    image[offset..offset + 4].copy_from_slice(&0xffi32.to_le_bytes());
+
+    match SMXFile::new(&image) {
+        Err(Error::InvalidOpcode { address: a, value }) => {
+            assert_eq!(a, address);
+            assert_eq!(value, 0xff);
+        },
+        _ => panic!("expected an invalid opcode error"),
+    }
+
+    let p = SMXFile::new_with_mode(&image, DecodeMode::BestEffort).unwrap();
+    let f = &*p;
+    let insn = f.functions().values().next().unwrap().instructions[0].clone();
+
+    assert!(insn.is_unknown());
+    assert_eq!(insn.address, address);
+    assert_eq!(insn.params, vec![0xff]);
+}
+
+#[test]
+fn test_jump_out_of_range() {
+    let mut image = uncompressed_image();
+
+    let (code_start, address) = {
+        let p = SMXFile::new(&image).unwrap();
+        let f = &*p;
+
+        let insn = f.functions().values()
+            .flat_map(|fun| fun.instructions.iter())
+            .find(|insn| insn.info.name == "jump")
+            .unwrap()
+            .clone();
+
+        (f.codev1.as_ref().unwrap().code_start(), insn.address)
+    };
+
+    let offset = (code_start + address + 4) as usize;
+
+    image[offset..offset + 4].copy_from_slice(&0x7fff_fff0i32.to_le_bytes());
+
+    match SMXFile::new(&image) {
+        Err(Error::JumpOutOfRange { address: a, target }) => {
+            assert_eq!(a, address);
+            assert_eq!(target, 0x7fff_fff0);
+        },
+        _ => panic!("expected a jump out of range error"),
+    }
+
+    assert!(SMXFile::new_with_mode(&image, DecodeMode::BestEffort).is_ok());
+}
+
+#[test]
+fn test_sweep_data() {
+    let mut image = uncompressed_image();
+
+    let (code_start, address) = {
+        let p = SMXFile::new(&image).unwrap();
+        let f = &*p;
+        let function = f.functions().values().next().unwrap();
+
+        (f.codev1.as_ref().unwrap().code_start(), function.instructions[0].address)
+    };
+
+    let offset = (code_start + address) as usize;
+
+    image[offset..offset + 4].copy_from_slice(&0xffi32.to_le_bytes());
+
+    let p = SMXFile::new_with_mode(&image, DecodeMode::BestEffort).unwrap();
+    let sweep = p.codev1.as_ref().unwrap().sweep();
+
+    assert_eq!(sweep.data, vec![address..address + 4]);
+
+    let function = sweep.functions.iter().find(|function| function.address == address - 4).unwrap();
+
+    assert!(function.instructions[0].is_unknown());
+}
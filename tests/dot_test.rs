@@ -0,0 +1,50 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::dot::{cfg_to_dot, CallGraph, CallTarget};
+use smxdasm::listing::{V1Listing, OperandStyle};
+
+#[test]
+fn test_dot() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let listing = V1Listing::new(p.clone(), OperandStyle::Symbolic);
+
+    let f = &*p;
+
+    for function in f.functions().values() {
+        let text = cfg_to_dot(function, &listing);
+
+        assert!(text.starts_with("digraph "));
+        assert!(text.contains("b0 [label="));
+        assert!(text.ends_with("}\n"));
+    }
+
+    let graph = CallGraph::new(f);
+
+    assert_eq!(graph.functions().len(), f.functions().len());
+
+    for function in f.functions().values() {
+        for insn in &function.instructions {
+            if insn.info.name == "call" {
+                assert!(graph.callers(&CallTarget::Function(insn.params[0])).contains(&function.address));
+            }
+        }
+    }
+
+    let text = graph.to_dot();
+
+    for name in graph.natives().values() {
+        assert!(text.contains(&format!("label=\"{}\"", name)));
+    }
+
+    println!("{}", text);
+}
@@ -0,0 +1,254 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+extern crate smxdasm;
+
+mod common;
+
+use smxdasm::emulator::{Emulator, Memory, NativeCall, NativeHandler};
+use smxdasm::errors::{Result, Error};
+use smxdasm::file::SMXFile;
+use smxdasm::v1opcodes::V1OPCode;
+use smxdasm::writer::SMXWriter;
+
+struct TestNatives {
+    calls: Vec<String>,
+}
+
+impl NativeHandler for TestNatives {
+    fn invoke(&mut self, memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+        self.calls.push(call.name.into());
+
+        match call.name {
+            "strcmp" => {
+                let mut a = memory.read_string(call.args[0])?;
+                let mut b = memory.read_string(call.args[1])?;
+
+                if call.args[2] == 0 {
+                    a = a.to_lowercase();
+                    b = b.to_lowercase();
+                }
+
+                Ok(a.cmp(&b) as i32)
+            },
+            "strlen" => Ok(memory.read_string(call.args[0])?.len() as i32),
+            "IsCharUpper" => Ok((call.args[0] as u8).is_ascii_uppercase() as i32),
+            _ => Err(Error::Other("Unexpected native")),
+        }
+    }
+}
+
+fn load() -> Vec<u8> {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    data
+}
+
+// Replaces the body of CharToLower with |body|. Returns the patched file and
+// the address of the function.
+fn with_body(f: &SMXFile, body: &[i32]) -> (Arc<SMXFile<'static>>, i32) {
+    let function = f.functions().values().find(|function| function.name == ".3044.CharToLower").unwrap();
+
+    let mut writer = SMXWriter::from_header(&f.header).unwrap();
+
+    common::patch_function(&mut writer, function, body);
+
+    (SMXFile::new(writer.write().unwrap()).unwrap(), function.address)
+}
+
+#[test]
+fn test_emulator() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut natives = TestNatives { calls: Vec::new() };
+
+    let registers = emulator.registers();
+
+    assert_eq!(registers.stk, emulator.memory().size());
+    assert_eq!(registers.hea, f.data.as_ref().unwrap().header().data_size as i32);
+
+    // Public and RTTI names both resolve.
+    let addr = emulator.find_function("CharToLower").unwrap();
+
+    assert_eq!(emulator.find_function(".3044.CharToLower"), Some(addr));
+    assert_eq!(emulator.find_function("NotAFunction"), None);
+
+    assert_eq!(emulator.call(addr, &['A' as i32], &mut natives).unwrap(), 'a' as i32);
+    assert_eq!(emulator.call(addr, &['b' as i32], &mut natives).unwrap(), 'b' as i32);
+
+    let hello = emulator.alloc_string("Hello", 16).unwrap();
+    let lower = emulator.alloc_string("hello", 16).unwrap();
+
+    assert_eq!(emulator.call_function("StrEqual", &[hello, lower, 0], &mut natives).unwrap(), 1);
+    assert_eq!(emulator.call_function("StrEqual", &[hello, lower, 1], &mut natives).unwrap(), 0);
+
+    // Loops, array indexing and calls between plugin functions.
+    let buffer = emulator.alloc_string("Hello World", 32).unwrap();
+
+    emulator.call_function("CStrToLower", &[buffer], &mut natives).unwrap();
+
+    assert_eq!(emulator.memory().read_string(buffer).unwrap(), "hello world");
+
+    assert!(natives.calls.contains(&"strlen".to_string()));
+    assert!(natives.calls.contains(&"IsCharUpper".to_string()));
+
+    // The stack is unwound after every call.
+    assert_eq!(emulator.registers().stk, registers.stk);
+
+    emulator.set_instruction_limit(Some(10));
+
+    match emulator.call_function("CStrToLower", &[buffer], &mut natives) {
+        Err(Error::Trap { reason, .. }) => assert_eq!(reason, "Instruction limit reached"),
+        _ => panic!("expected a trap"),
+    }
+
+    assert_eq!(emulator.registers().stk, registers.stk);
+
+    emulator.reset();
+
+    assert_eq!(emulator.registers().hea, registers.hea);
+}
+
+#[test]
+fn test_emulator_errors() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut natives = TestNatives { calls: Vec::new() };
+
+    assert!(matches!(emulator.call(4, &[], &mut natives), Err(Error::Trap { address: 4, .. })));
+
+    // Errors from natives reach the caller.
+    let addr = emulator.find_function("OnPluginStart").unwrap();
+
+    assert!(matches!(emulator.call(addr, &[], &mut natives), Err(Error::Other("Unexpected native"))));
+
+    let memory = emulator.memory_mut();
+    let end = memory.size();
+
+    assert!(matches!(memory.read_cell(end - 2), Err(Error::InvalidAddress { .. })));
+    assert!(matches!(memory.write_cell(-4, 0), Err(Error::InvalidAddress { .. })));
+
+    assert_eq!(memory.write_string(0, "truncated", 5).unwrap(), 4);
+    assert_eq!(memory.read_string(0).unwrap(), "trun");
+
+    // DataHeader::memory_size past i32::MAX, and below data_size.
+    for memory_size in [0xffff_fff0u32, 4] {
+        let mut writer = SMXWriter::from_header(&f.header).unwrap();
+
+        common::patch_section(&mut writer, ".data", 4, &memory_size.to_le_bytes());
+
+        let patched = SMXFile::new(writer.write().unwrap()).unwrap();
+
+        assert!(matches!(Emulator::new(&patched), Err(Error::InvalidSize)));
+    }
+}
+
+#[test]
+fn test_emulator_genarray() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    // push.c 3; push.c 5; genarray 2 is what the compiler emits for
+    // `new int[3][5]`.
+    let (patched, addr) = with_body(f, &[
+        V1OPCode::PUSH_C as i32, 3,
+        V1OPCode::PUSH_C as i32, 5,
+        V1OPCode::GENARRAY as i32, 2,
+        V1OPCode::POP_PRI as i32,
+        V1OPCode::RETN as i32,
+    ]);
+
+    let mut emulator = Emulator::new(&patched).unwrap();
+    let mut natives = TestNatives { calls: Vec::new() };
+
+    let hea = emulator.registers().hea;
+    let base = emulator.call(addr, &[], &mut natives).unwrap();
+
+    assert_eq!(base, hea);
+
+    let memory = emulator.memory();
+
+    // 3 row offsets, followed by 3 rows of 5 cells.
+    for row in 0..3 {
+        let cell = base + row * 4;
+
+        assert_eq!(memory.read_cell(cell).unwrap() + cell, base + 3 * 4 + row * 5 * 4);
+    }
+
+    // a[2][4] is the last cell of the array.
+    let row = base + 2 * 4 + memory.read_cell(base + 2 * 4).unwrap();
+
+    assert_eq!(row + 4 * 4, base + (3 + 2 * 5 + 4) * 4);
+
+    // Nine levels of 64M cells each fit in the largest memory, but not
+    // together.
+    let (patched, addr) = with_body(f, &[
+        V1OPCode::PUSH5_C as i32, 0x0400_0000, 1, 1, 1, 1,
+        V1OPCode::PUSH4_C as i32, 1, 1, 1, 1,
+        V1OPCode::GENARRAY as i32, 9,
+        V1OPCode::POP_PRI as i32,
+        V1OPCode::RETN as i32,
+    ]);
+
+    let mut writer = SMXWriter::from_header(&patched.header).unwrap();
+
+    common::patch_section(&mut writer, ".data", 4, &0x0400_0000u32.to_le_bytes());
+
+    let patched = SMXFile::new(writer.write().unwrap()).unwrap();
+
+    let mut emulator = Emulator::new(&patched).unwrap();
+
+    match emulator.call(addr, &[], &mut natives) {
+        Err(Error::Trap { reason, .. }) => assert_eq!(reason, "Invalid array size"),
+        result => panic!("expected a trap, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_emulator_overflow() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    let mut natives = TestNatives { calls: Vec::new() };
+
+    // Operands that overflow an address trap instead of panicking.
+    let bodies: [&[i32]; 5] = [
+        &[V1OPCode::LOAD_S_PRI as i32, i32::MAX, V1OPCode::RETN as i32],
+        &[V1OPCode::ADDR_PRI as i32, i32::MAX, V1OPCode::LOAD_I as i32, V1OPCode::RETN as i32],
+        &[V1OPCode::CONST_ALT as i32, i32::MAX, V1OPCode::FILL as i32, 8, V1OPCode::RETN as i32],
+        &[V1OPCode::TRACKER_PUSH_C as i32, i32::MIN / 4, V1OPCode::TRACKER_POP_SETHEAP as i32, V1OPCode::RETN as i32],
+        // Argument count of the frame.
+        &[V1OPCode::CONST_PRI as i32, 0x1000_0000, V1OPCode::STOR_S_PRI as i32, 8, V1OPCode::RETN as i32],
+    ];
+
+    for body in bodies {
+        let (patched, addr) = with_body(f, body);
+
+        let mut emulator = Emulator::new(&patched).unwrap();
+
+        assert!(emulator.call(addr, &[], &mut natives).is_err());
+    }
+
+    let (patched, addr) = with_body(f, &[
+        V1OPCode::CONST_PRI as i32, i32::MAX,
+        V1OPCode::STRADJUST_PRI as i32,
+        V1OPCode::RETN as i32,
+    ]);
+
+    let mut emulator = Emulator::new(&patched).unwrap();
+
+    assert_eq!(emulator.call(addr, &[], &mut natives).unwrap(), i32::MAX.wrapping_add(4) >> 2);
+}
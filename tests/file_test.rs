@@ -3,9 +3,11 @@ use std::io::Read;
 
 extern crate smxdasm;
 
+mod common;
+
 #[test]
 fn test_file() {
-    let mut file = File::open("F:\\Github\\smx-dasm-rs\\tests\\Source-Chat-Relay.smx").unwrap();
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
 
     let mut data = Vec::new();
 
@@ -21,7 +23,7 @@ fn test_file() {
         Ok(ce) => ce,
     };
 
-    let f = p.borrow();
+    let f = &*p;
 
     println!("========== HEADER ==========");
     println!("Magic: {}", f.header.magic);
@@ -36,18 +38,16 @@ fn test_file() {
     println!("========== HEADER ==========");
 
     if let Some(opt) = &f.names {
-        let names = opt.borrow();
         println!("========== Name Table Names ==========");
-        for (_, name) in &names.names() {
+        for name in opt.names().values() {
             println!("{}", name);
         }
         println!("========== Name Table Names ==========");
     }
 
     if let Some(opt) = &f.debug_names {
-        let names = opt.borrow();
         println!("========== Debug Name Table Names ==========");
-        for (_, name) in &names.names() {
+        for name in opt.names().values() {
             println!("{}", name);
         }
         println!("========== Debug Name Table Names ==========");
@@ -126,9 +126,8 @@ fn test_file() {
     }
 
     if let Some(opt) = &f.called_functions {
-        let functions = opt.borrow();
         println!("========== Called Function Entries ==========");
-        for func in &functions.entries() {
+        for func in &opt.entries() {
             println!("======");
             println!("Address: {}", func.address);
             println!("Name: {}", func.name);
@@ -287,9 +286,8 @@ fn test_file() {
     }
 
     if let Some(opt) = &f.debug_globals {
-        let globals = opt.borrow();
         println!("========== Debug Globals ==========");
-        for g in &globals.symbol_entries() {
+        for g in &opt.symbol_entries() {
             println!("======");
             println!("Address: {}", g.address);
             println!("Scope: {}", g.scope);
@@ -316,4 +314,231 @@ fn test_file() {
         }
         println!("========== Debug Locals ==========");
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_functions() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    for pubfun in f.publics.as_ref().unwrap().entries_ref() {
+        let function = f.function_at(pubfun.address as i32).unwrap();
+
+        assert_eq!(function.name, pubfun.name);
+        assert_eq!(function.kind, smxdasm::v1disassembler::V1FunctionKind::Public);
+        assert!(function.code_end > function.address);
+        assert!(!function.instructions.is_empty());
+    }
+
+    for fun in f.called_functions.as_ref().unwrap().entries_ref() {
+        let function = f.function_at(fun.address as i32).unwrap();
+
+        assert_eq!(function.kind, smxdasm::v1disassembler::V1FunctionKind::Called);
+    }
+
+    println!("Functions: {}", f.functions().len());
+}
+
+#[test]
+fn test_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<smxdasm::file::SMXFile>();
+
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let workers: Vec<_> = (0..4).map(|_| {
+        let f = std::sync::Arc::clone(&p);
+
+        std::thread::spawn(move || {
+            let listing = smxdasm::listing::V1Listing::new(std::sync::Arc::clone(&f), Default::default());
+
+            f.functions().values().map(|function| listing.render_function(function).len()).sum::<usize>()
+        })
+    }).collect();
+
+    let sizes: Vec<usize> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+
+    assert!(sizes.iter().all(|&size| size > 0 && size == sizes[0]));
+}
+
+#[test]
+fn test_borrowed() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let owned = smxdasm::file::SMXFile::new(&data).unwrap();
+
+    assert!(!owned.header.is_borrowed());
+
+    let mut writer = smxdasm::writer::SMXWriter::from_header(&owned.header).unwrap();
+
+    writer.set_compression_type(smxdasm::headers::CompressionType::CompressionNone);
+
+    let image = writer.write().unwrap();
+
+    let borrowed = smxdasm::file::SMXFile::from_slice(&image).unwrap();
+
+    assert!(borrowed.header.is_borrowed());
+    assert_eq!(borrowed.header.data.as_ptr(), image.as_ptr());
+
+    assert_eq!(borrowed.functions().len(), owned.functions().len());
+
+    for (addr, function) in owned.functions() {
+        assert_eq!(borrowed.function_at(*addr).unwrap().instructions.len(), function.instructions.len());
+    }
+
+    let rtti = borrowed.rtti_data.as_ref().unwrap();
+
+    for method in borrowed.rtti_methods.as_ref().unwrap().methods_ref() {
+        assert!(rtti.function_type_from_offset(method.signature).is_ok());
+    }
+
+    // Compressed input still parses, into an owned image.
+    assert!(!smxdasm::file::SMXFile::from_slice(&data).unwrap().header.is_borrowed());
+}
+
+#[test]
+fn test_reachability() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let full = smxdasm::file::SMXFile::new(&data).unwrap();
+
+    // Without .publics, every function has to be found through rtti.methods,
+    // calls and function references.
+    let mut writer = smxdasm::writer::SMXWriter::from_header(&full.header).unwrap();
+
+    assert!(writer.remove_section(".publics"));
+
+    let p = smxdasm::file::SMXFile::new(writer.write().unwrap()).unwrap();
+
+    assert_eq!(p.functions().len(), full.functions().len());
+
+    let start = p.functions().values().find(|function| function.name == "OnPluginStart").unwrap();
+
+    assert_eq!(start.kind, smxdasm::v1disassembler::V1FunctionKind::Called);
+
+    let code = p.codev1.as_ref().unwrap();
+
+    assert!(code.is_proc_at(start.address));
+    assert!(!code.is_proc_at(start.address + 4));
+    assert!(!code.is_proc_at(-4));
+
+    // Keep only two publics and drop rtti.methods and the debug methods, so
+    // nothing else seeds the function table. Timer_Reconnect is then only
+    // reachable once a const.pri of its address is patched into
+    // OnPluginStart. Other constant operands, like add.c's, don't count.
+    let timer = full.functions().values().find(|function| function.name == "Timer_Reconnect").unwrap().address;
+
+    let build = |patch: Option<smxdasm::v1opcodes::V1OPCode>| {
+        let mut writer = smxdasm::writer::SMXWriter::from_header(&full.header).unwrap();
+
+        let mut publics = Vec::new();
+
+        for name in ["AskPluginLoad2", "OnPluginStart"] {
+            let i = full.publics.as_ref().unwrap().entries_ref().iter().position(|public| public.name == name).unwrap();
+
+            publics.extend_from_slice(&writer.section(".publics").unwrap()[i * 8..i * 8 + 8]);
+        }
+
+        writer.set_section(".publics", publics);
+
+        assert!(writer.remove_section("rtti.methods"));
+        assert!(writer.remove_section(".dbg.methods"));
+        assert!(writer.remove_section(".dbg.locals"));
+
+        if let Some(opcode) = patch {
+            let start = full.functions().values().find(|function| function.name == "OnPluginStart").unwrap();
+            let insn = start.instructions.iter().find(|insn| insn.info.opcode == smxdasm::v1opcodes::V1OPCode::PUSH_C).unwrap();
+
+            common::patch_code(&mut writer, insn.address, &[opcode as i32, timer]);
+        }
+
+        smxdasm::file::SMXFile::new(writer.write().unwrap()).unwrap()
+    };
+
+    assert!(build(None).function_at(timer).is_none());
+    assert!(build(Some(smxdasm::v1opcodes::V1OPCode::ADD_C)).function_at(timer).is_none());
+
+    let p = build(Some(smxdasm::v1opcodes::V1OPCode::CONST_PRI));
+    let function = p.function_at(timer).unwrap();
+
+    assert_eq!(function.kind, smxdasm::v1disassembler::V1FunctionKind::Called);
+    assert!(p.publics.as_ref().unwrap().entries_ref().iter().all(|public| public.address != timer as u32));
+    assert!(p.rtti_methods.is_none());
+}
+
+#[test]
+fn test_sweep() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(&data).unwrap();
+
+    let sweep = p.codev1.as_ref().unwrap().sweep();
+
+    assert_eq!(sweep.functions.len(), p.functions().len());
+    assert!(sweep.data.is_empty());
+    assert_eq!(sweep.gaps[0], 0..8);
+
+    for function in &sweep.functions {
+        let found = p.function_at(function.address).unwrap();
+
+        assert_eq!(function.code_end, found.code_end);
+        assert_eq!(function.instructions.len(), found.instructions.len());
+    }
+
+    assert!(p.unreachable_functions().is_empty());
+}
+
+#[test]
+fn test_source_location() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(&data).unwrap();
+
+    let start = p.functions().values().find(|function| function.name == "OnPluginStart").unwrap();
+
+    let (path, line) = p.source_location(start.instructions[0].address).unwrap();
+
+    assert!(path.ends_with("Source-Chat-Relay.sp"));
+    assert_eq!(smxdasm::file::SMXFile::file_name(&path), "Source-Chat-Relay.sp");
+
+    let addresses = p.addresses_for_line("Source-Chat-Relay.sp", line);
+
+    assert!(addresses.contains(&start.instructions[0].address));
+    assert_eq!(addresses, p.addresses_for_line(&path, line));
+
+    for &addr in &addresses {
+        assert_eq!(p.source_location(addr).unwrap(), (path.clone(), line));
+    }
+
+    assert!(p.addresses_for_line("missing.sp", line).is_empty());
+    assert!(p.source_location(-4).is_none());
+}
@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+mod common;
+
+use smxdasm::globals::Value;
+
+#[test]
+fn test_globals() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let globals = p.globals().unwrap();
+
+    assert_eq!(globals.len(), p.debug_globals.as_ref().unwrap().symbol_entries().len());
+    assert!(globals.windows(2).all(|pair| pair[0].address < pair[1].address));
+
+    let find = |name: &str| globals.iter().find(|global| global.name == name).unwrap();
+
+    assert_eq!(find("g_iPort").value, Value::Int(57452));
+    assert_eq!(find("g_bFlag").value, Value::Bool(false));
+    assert_eq!(find("g_sHost").value, Value::String("127.0.0.1".into()));
+    assert_eq!(find("g_sHost").ty.to_string(), "char[64]");
+    assert_eq!(find("NULL_VECTOR").value, Value::Array(vec![Value::Float(0.0); 3]));
+    assert_eq!(find("localIPRanges").value.to_string(), "{167772160, 2130706433, 2131755008, -1062731776}");
+
+    // Two-dimensional arrays go through the indirection vector.
+    assert_eq!(find("CTeamColors").value, Value::Array(vec![Value::Array(vec![
+        Value::Int(0xCCCCCC),
+        Value::Int(0x4D7942),
+        Value::Int(0xFF4040),
+    ])]));
+
+    match &find("g_Buffer").value {
+        Value::Array(rows) => {
+            assert_eq!(rows.len(), 32);
+            assert!(rows.iter().all(|row| *row == Value::String(String::new())));
+        },
+        _ => panic!("expected an array"),
+    }
+
+    // Struct strings are stored by address.
+    match &find("myinfo").value {
+        Value::Struct(fields) => {
+            assert_eq!(fields[0], ("name".to_string(), Value::String("Source Chat Relay".into())));
+            assert_eq!(fields.len(), 5);
+        },
+        _ => panic!("expected a struct"),
+    }
+}
+
+#[test]
+fn test_globals_malformed() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let full = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let symbols = full.debug_globals.as_ref().unwrap().symbol_entries();
+    let names = full.names.as_ref().unwrap();
+
+    let find = |name: &str| symbols.iter().position(|sym| names.string_at(sym.name_offset).unwrap() == name).unwrap();
+
+    let host = &symbols[find("g_sHost")];
+    let port = find("g_iPort");
+
+    let mut writer = smxdasm::writer::SMXWriter::from_header(&full.header).unwrap();
+
+    // End the plugin's memory right after g_sHost, a char[64]. Its 64 bytes
+    // still fit, even though 64 cells would not.
+    common::patch_section(&mut writer, ".data", 4, &(host.address as u32 + 64).to_le_bytes());
+
+    // A type_id of an unknown kind.
+    let section = writer.section(".dbg.globals").unwrap();
+
+    let header_size = u32::from_le_bytes([section[0], section[1], section[2], section[3]]) as usize;
+    let row_size = u32::from_le_bytes([section[4], section[5], section[6], section[7]]) as usize;
+
+    common::patch_section(&mut writer, ".dbg.globals", header_size + port * row_size + 17, &0xfi32.to_le_bytes());
+
+    let p = smxdasm::file::SMXFile::new(writer.write().unwrap()).unwrap();
+
+    let globals = p.globals().unwrap();
+
+    assert_eq!(globals.len(), symbols.len());
+
+    let find = |name: &str| globals.iter().find(|global| global.name == name).unwrap();
+
+    assert_eq!(find("g_sHost").value, Value::String("127.0.0.1".into()));
+    assert_eq!(find("g_iPort").value, Value::Unknown);
+    assert_eq!(find("g_iPort").ty.to_string(), "any");
+}
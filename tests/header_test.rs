@@ -5,7 +5,7 @@ extern crate smxdasm;
 
 #[test]
 fn test_header() {
-    let mut file = File::open("F:\\Github\\smx-dasm-rs\\tests\\Source-Chat-Relay.smx").unwrap();
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
 
     let mut data = Vec::new();
 
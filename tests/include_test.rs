@@ -0,0 +1,31 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::include::IncludeGenerator;
+
+#[test]
+fn test_include() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let generator = IncludeGenerator::new(f);
+
+    let text = generator.generate().unwrap();
+
+    assert!(text.contains("native void MarkNativeAsOptional(const char[] arg0);\n"));
+    assert!(text.contains("typedef SocketConnectCB = function void (Handle arg0, any arg1);\n"));
+    assert!(text.contains("typeset Timer\n{\n"));
+    assert!(text.contains("struct Plugin\n{\n    public const char[] name;\n"));
+    assert!(text.contains("forward void OnPluginStart();\n"));
+
+    println!("{}", text);
+}
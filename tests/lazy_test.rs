@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+mod common;
+
+use smxdasm::errors::Error;
+use smxdasm::file::SMXFile;
+use smxdasm::lazy::SMXLazyFile;
+use smxdasm::writer::SMXWriter;
+use smxdasm::headers::CompressionType;
+
+fn plugin() -> Vec<u8> {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    data
+}
+
+#[test]
+fn test_lazy() {
+    let f = SMXLazyFile::new(plugin()).unwrap();
+
+    let natives = f.natives().unwrap().unwrap();
+
+    assert!(natives.entries().iter().any(|native| native.name == "MarkNativeAsOptional"));
+    assert!(f.loaded_functions().is_empty());
+
+    let function = f.public_function("OnPluginStart").unwrap().unwrap();
+
+    assert_eq!(function.kind, smxdasm::v1disassembler::V1FunctionKind::Public);
+    assert_eq!(f.loaded_functions(), vec![function.address]);
+
+    let eager = SMXFile::new(plugin()).unwrap();
+
+    assert_eq!(function.instructions.len(), eager.function_at(function.address).unwrap().instructions.len());
+
+    assert!(f.public_function("NoSuchFunction").unwrap().is_none());
+    assert!(f.debug_names().unwrap().is_some());
+}
+
+#[test]
+fn test_bad_code() {
+    let eager = SMXFile::new(plugin()).unwrap();
+
+    let mut writer = SMXWriter::from_header(&eager.header).unwrap();
+
+    writer.set_compression_type(CompressionType::CompressionNone);
+
+    let mut image = writer.write().unwrap();
+
+    // Break the PROC of the first public.
+    let code_start = eager.codev1.as_ref().unwrap().code_start() as usize;
+    let address = eager.publics.as_ref().unwrap().get_entry(0).address as usize;
+
+    image[code_start + address..code_start + address + 4].copy_from_slice(&[0xff; 4]);
+
+    assert!(SMXFile::from_slice(&image).is_err());
+
+    let f = SMXLazyFile::from_slice(&image).unwrap();
+
+    assert_eq!(f.natives().unwrap().unwrap().size(), eager.natives.as_ref().unwrap().size());
+    assert!(f.rtti_data().unwrap().is_some());
+    assert!(f.function_at(address as i32).is_err());
+
+    let other = eager.publics.as_ref().unwrap().get_entry(1);
+
+    assert_eq!(f.function_at(other.address as i32).unwrap().name, other.name);
+}
+
+#[test]
+fn test_bad_data() {
+    let eager = SMXFile::new(plugin()).unwrap();
+
+    let mut writer = SMXWriter::from_header(&eager.header).unwrap();
+
+    // DataHeader::data_size, past the end of the section.
+    common::patch_section(&mut writer, ".data", 0, &0x7fff_fff0u32.to_le_bytes());
+
+    let image = writer.write().unwrap();
+
+    assert!(matches!(SMXFile::new(image.clone()), Err(Error::InvalidOffset)));
+
+    let f = SMXLazyFile::new(image).unwrap();
+
+    assert!(matches!(f.data(), Err(Error::InvalidOffset)));
+    assert!(f.natives().unwrap().is_some());
+}
@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::file::SMXFile;
+use smxdasm::headers::SMXHeader;
+use smxdasm::v1types::SymbolIdent;
+use smxdasm::writer::SMXWriter;
+
+fn load() -> Vec<u8> {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    data
+}
+
+#[allow(clippy::too_many_arguments)]
+fn symbol(packed: bool, address: i32, tag: i16, start: i32, end: i32, ident: u8, scope: u8, dims: &[u32], name: i32) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    out.extend(address.to_le_bytes());
+    out.extend(tag.to_le_bytes());
+
+    if !packed {
+        out.extend([0, 0]);
+    }
+
+    out.extend(start.to_le_bytes());
+    out.extend(end.to_le_bytes());
+    out.push(ident);
+    out.push(scope);
+    out.extend((dims.len() as u16).to_le_bytes());
+    out.extend(name.to_le_bytes());
+
+    for size in dims {
+        out.extend(0i16.to_le_bytes());
+
+        if !packed {
+            out.extend([0, 0]);
+        }
+
+        out.extend(size.to_le_bytes());
+    }
+
+    out
+}
+
+const STRING_TAG: u32 = 5;
+
+// Rewrites the test plugin with only legacy debug symbols: a global, a
+// global array, a local and a function, named with the strings of the
+// tables they replace.
+fn legacy_image(packed: bool) -> Vec<u8> {
+    let data = load();
+    let full = SMXFile::new(&data).unwrap();
+
+    let find = |name: &str| full.debug_globals.as_ref().unwrap().symbol_entries().into_iter()
+        .find(|sym| full.names.as_ref().unwrap().string_at(sym.name_offset).unwrap() == name)
+        .unwrap();
+
+    let port = find("g_iPort");
+    let host = find("g_sHost");
+    let local = full.debug_locals.as_ref().unwrap().symbol_entries()[0].clone();
+    let start = full.publics.as_ref().unwrap().entries().into_iter().find(|p| p.name == "OnPluginStart").unwrap();
+    let end = full.function_at(start.address as i32).unwrap().code_end;
+
+    let mut symbols = Vec::new();
+
+    symbols.extend(symbol(packed, port.address, 0, 0, 0x7fff_ffff, 1, 0, &[], port.name_offset));
+    symbols.extend(symbol(packed, host.address, STRING_TAG as i16, 0, 0x7fff_ffff, 3, 0, &[64], host.name_offset));
+    symbols.extend(symbol(packed, local.address, 0, local.code_start, local.code_end, 1, 1, &[], local.name_offset));
+    symbols.extend(symbol(packed, start.address as i32, 0, start.address as i32, end, 9, 0, &[], start.name_offset));
+
+    let mut writer = SMXWriter::from_header(&full.header).unwrap();
+
+    for name in [".dbg.globals", ".dbg.locals", ".dbg.methods", ".publics", "rtti.methods"] {
+        writer.remove_section(name);
+    }
+
+    writer.set_section(".dbg.symbols", symbols);
+
+    // Legacy compilers tag char arrays "String".
+    let mut names = writer.section(".names").unwrap().to_vec();
+    let mut tags = Vec::new();
+
+    tags.extend(STRING_TAG.to_le_bytes());
+    tags.extend((names.len() as i32).to_le_bytes());
+    names.extend(b"String\0");
+
+    writer.set_section(".names", names);
+    writer.set_section(".tags", tags);
+
+    if packed {
+        writer.set_version(SMXHeader::SP1_VERSION_1_0);
+    } else {
+        let native = full.natives.as_ref().unwrap().get_entry(0);
+
+        let mut natives = Vec::new();
+
+        natives.extend(1u32.to_le_bytes());
+        natives.extend(0u32.to_le_bytes());
+        natives.extend(native.name_offset.to_le_bytes());
+        natives.extend(0i16.to_le_bytes());
+        natives.extend(2u16.to_le_bytes());
+
+        // A char[64] argument and a plain one, byte-packed.
+        natives.push(3);
+        natives.extend(0i16.to_le_bytes());
+        natives.extend(1u16.to_le_bytes());
+        natives.extend(port.name_offset.to_le_bytes());
+        natives.extend(0i16.to_le_bytes());
+        natives.extend(64u32.to_le_bytes());
+        natives.push(1);
+        natives.extend(0i16.to_le_bytes());
+        natives.extend(0u16.to_le_bytes());
+        natives.extend(port.name_offset.to_le_bytes());
+
+        writer.set_section(".dbg.natives", natives);
+    }
+
+    writer.write().unwrap()
+}
+
+fn check(p: &SMXFile) {
+    let symbols = p.debug_symbols.as_ref().unwrap();
+
+    assert_eq!(symbols.len(), 4);
+
+    let port = &symbols.entries_ref()[0];
+    let host = &symbols.entries_ref()[1];
+    let local = &symbols.entries_ref()[2];
+
+    assert_eq!(port.ident, SymbolIdent::Variable);
+    assert_eq!(p.find_global_name(port.address).unwrap(), "g_iPort");
+    assert_eq!(host.dims[0].size, 64);
+    assert_eq!(p.find_global_name(host.address + 8).unwrap(), "g_sHost");
+    assert_eq!(p.find_global_name(host.address + 60).unwrap(), "g_sHost");
+    assert_ne!(p.find_global_name(host.address + 64).as_deref(), Some("g_sHost"));
+    assert_eq!(p.find_local_name(local.code_start as i32, local.address).unwrap(), local.name);
+    assert!(p.find_local_name(local.code_end as i32, local.address).is_none());
+
+    // The function symbol names a function no public or rtti.methods entry
+    // describes anymore.
+    let function = p.functions().values().find(|function| function.name == "OnPluginStart").unwrap();
+
+    assert!(p.is_function_at_address(function.address));
+    assert_eq!(p.find_function_name(function.address), "OnPluginStart");
+}
+
+#[test]
+fn test_legacy_symbols() {
+    let p = SMXFile::new(legacy_image(false)).unwrap();
+
+    assert!(!p.header.debug_packed);
+
+    check(&p);
+
+    let natives = p.debug_natives.as_ref().unwrap();
+    let native = natives.find_native(0).unwrap();
+
+    assert_eq!(native.name, p.natives.as_ref().unwrap().get_entry(0).name);
+    assert_eq!(native.args.len(), 2);
+    assert_eq!(native.args[0].ident, SymbolIdent::Array);
+    assert_eq!(native.args[0].name, "g_iPort");
+    assert_eq!(native.args[0].dims.len(), 1);
+    assert_eq!(native.args[0].dims[0].size, 64);
+    assert_eq!(native.args[1].ident, SymbolIdent::Variable);
+    assert_eq!(native.args[1].name, "g_iPort");
+    assert!(native.args[1].dims.is_empty());
+}
+
+#[test]
+fn test_legacy_symbols_packed() {
+    let p = SMXFile::new(legacy_image(true)).unwrap();
+
+    assert!(p.header.debug_packed);
+    assert!(p.debug_natives.is_none());
+
+    check(&p);
+}
+
+#[test]
+fn test_legacy_symbols_lazy() {
+    let lazy = smxdasm::lazy::SMXLazyFile::new(legacy_image(false)).unwrap();
+
+    let symbols = lazy.debug_symbols().unwrap().unwrap();
+    let address = symbols.entries_ref()[3].address;
+
+    assert_eq!(lazy.function_at(address).unwrap().name, "OnPluginStart");
+    assert_eq!(lazy.debug_natives().unwrap().unwrap().len(), 1);
+}
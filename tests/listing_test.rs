@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::listing::{V1Listing, OperandStyle};
+
+#[test]
+fn test_listing() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let symbolic = V1Listing::new(p.clone(), OperandStyle::Symbolic);
+    let raw = V1Listing::new(p.clone(), OperandStyle::Raw);
+
+    let f = &*p;
+
+    for function in f.functions().values() {
+        let text = symbolic.render_function(function);
+
+        assert!(text.starts_with(&format!("{}:\n", function.name)));
+
+        println!("{}", text);
+    }
+
+    let function = f.functions().values().find(|fun| {
+        fun.instructions.iter().any(|insn| insn.info.name == "sysreq.n")
+    }).unwrap();
+
+    let insn = function.instructions.iter().find(|insn| insn.info.name == "sysreq.n").unwrap();
+    let native = f.natives.as_ref().unwrap().get_entry(insn.params[0] as usize);
+
+    assert!(symbolic.render_instruction(insn).contains(&native.name));
+
+    // Source lines are interleaved when .dbg.lines is present.
+    let start = f.functions().values().find(|fun| fun.name == "OnPluginStart").unwrap();
+    let (_, line) = f.source_location(start.instructions[0].address).unwrap();
+
+    assert!(symbolic.render_function(start).contains(&format!("  // Source-Chat-Relay.sp:{}\n", line)));
+    assert!(!raw.render_instruction(insn).contains(&native.name));
+}
@@ -0,0 +1,228 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::emulator::{Emulator, NativeCall, NativeHandler};
+use smxdasm::errors::Error;
+use smxdasm::natives::NativeLibrary;
+
+fn load() -> Vec<u8> {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    data
+}
+
+fn call(natives: &mut NativeLibrary, emulator: &mut Emulator, name: &str, args: &[i32]) -> i32 {
+    natives.invoke(emulator.memory_mut(), &NativeCall {
+        name,
+        index: 0,
+        address: 0,
+        args,
+    }).unwrap()
+}
+
+#[test]
+fn test_plugin_natives() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut natives = NativeLibrary::core();
+
+    let unbound = natives.unbound(f);
+
+    assert!(unbound.contains(&"CreateConVar".to_string()));
+    assert!(!unbound.contains(&"strcmp".to_string()));
+
+    let hello = emulator.alloc_string("Hello", 16).unwrap();
+    let lower = emulator.alloc_string("hello", 16).unwrap();
+
+    assert_eq!(emulator.call_function("StrEqual", &[hello, lower, 0], &mut natives).unwrap(), 1);
+    assert_eq!(emulator.call_function("StrEqual", &[hello, lower, 1], &mut natives).unwrap(), 0);
+
+    let buffer = emulator.alloc_string("MiXeD", 16).unwrap();
+
+    emulator.call_function("CStrToLower", &[buffer], &mut natives).unwrap();
+
+    assert_eq!(emulator.memory().read_string(buffer).unwrap(), "mixed");
+
+    // InitColorTrie fills a StringMap through CreateTrie and SetTrieValue.
+    let trie = emulator.call_function("InitColorTrie", &[], &mut natives).unwrap();
+    let key = emulator.alloc_string("aliceblue", 16).unwrap();
+    let value = emulator.alloc(1).unwrap();
+
+    assert_eq!(call(&mut natives, &mut emulator, "GetTrieValue", &[trie, key, value]), 1);
+    assert_eq!(emulator.memory().read_cell(value).unwrap(), 0xf0f8ff);
+    assert_eq!(natives.state().open_handles(), 1);
+
+    call(&mut natives, &mut emulator, "CloseHandle", &[trie]);
+
+    assert_eq!(natives.state().open_handles(), 0);
+
+    // Unbound natives name the caller.
+    let start = emulator.find_function("OnPluginStart").unwrap();
+
+    match emulator.call(start, &[], &mut natives) {
+        Err(Error::UnboundNative { name, address }) => {
+            let function = f.function_at(start).unwrap();
+
+            assert!(!natives.is_bound(&name));
+            assert!(function.instructions.iter().any(|insn| insn.address == address));
+        },
+        _ => panic!("expected an unbound native"),
+    }
+}
+
+#[test]
+fn test_core_natives() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut natives = NativeLibrary::core();
+
+    let buffer = emulator.alloc(16).unwrap();
+    let fmt = emulator.alloc_string("%d|%5s|%-4d|%05.1f|%x|%c|%%", 64).unwrap();
+    let name = emulator.alloc_string("abc", 8).unwrap();
+
+    let mut refs = Vec::new();
+
+    for value in [-42, 7, 2.5f32.to_bits() as i32, 255, 'z' as i32] {
+        let cell = emulator.alloc(1).unwrap();
+
+        emulator.memory_mut().write_cell(cell, value).unwrap();
+        refs.push(cell);
+    }
+
+    let args = [buffer, 64, fmt, refs[0], name, refs[1], refs[2], refs[3], refs[4]];
+
+    assert_eq!(call(&mut natives, &mut emulator, "Format", &args), 27);
+    assert_eq!(emulator.memory().read_string(buffer).unwrap(), "-42|  abc|7   |002.5|ff|z|%");
+
+    // Truncated to the buffer size.
+    let args = [buffer, 4, fmt, refs[0], name, refs[1], refs[2], refs[3], refs[4]];
+
+    assert_eq!(call(&mut natives, &mut emulator, "Format", &args), 3);
+    assert_eq!(emulator.memory().read_string(buffer).unwrap(), "-42");
+
+    let number = emulator.alloc_string("  -0x1F!", 16).unwrap();
+
+    assert_eq!(call(&mut natives, &mut emulator, "StringToInt", &[number, 16]), -31);
+    assert_eq!(call(&mut natives, &mut emulator, "StringToInt", &[number, 10]), 0);
+
+    let float = emulator.alloc_string("3.75e1xyz", 16).unwrap();
+
+    assert_eq!(call(&mut natives, &mut emulator, "StringToFloat", &[float]), 37.5f32.to_bits() as i32);
+
+    let a = 1.5f32.to_bits() as i32;
+    let b = 2.0f32.to_bits() as i32;
+
+    assert_eq!(call(&mut natives, &mut emulator, "FloatMul", &[a, b]), 3.0f32.to_bits() as i32);
+    assert_eq!(call(&mut natives, &mut emulator, "__FLOAT_LE__", &[a, b]), 1);
+    assert_eq!(call(&mut natives, &mut emulator, "RoundToFloor", &[a]), 1);
+    assert_eq!(call(&mut natives, &mut emulator, "RoundToNearest", &[a]), 2);
+    assert_eq!(call(&mut natives, &mut emulator, "RoundToNearest", &[(-2.5f32).to_bits() as i32]), -2);
+
+    let text = emulator.alloc_string("a-b-A", 16).unwrap();
+    let search = emulator.alloc_string("a", 4).unwrap();
+    let replace = emulator.alloc_string("xy", 4).unwrap();
+
+    assert_eq!(call(&mut natives, &mut emulator, "ReplaceString", &[text, 16, search, replace, 0]), 2);
+    assert_eq!(emulator.memory().read_string(text).unwrap(), "xy-b-xy");
+    assert_eq!(call(&mut natives, &mut emulator, "StrContains", &[text, replace, 1]), 0);
+
+    // ArrayList, through both the methodmap and the old names.
+    let list = call(&mut natives, &mut emulator, "ArrayList.ArrayList", &[4, 0]);
+
+    assert_eq!(call(&mut natives, &mut emulator, "ArrayList.Push", &[list, 10]), 0);
+    assert_eq!(call(&mut natives, &mut emulator, "PushArrayString", &[list, name]), 1);
+    assert_eq!(call(&mut natives, &mut emulator, "ArrayList.Length.get", &[list]), 2);
+    assert_eq!(call(&mut natives, &mut emulator, "ArrayList.Get", &[list, 0, 0, 0]), 10);
+    assert_eq!(call(&mut natives, &mut emulator, "ArrayList.Get", &[list, 1, 1, 1]), 'b' as i32);
+    assert_eq!(call(&mut natives, &mut emulator, "ArrayList.FindString", &[list, name]), 1);
+    assert_eq!(call(&mut natives, &mut emulator, "ArrayList.GetString", &[list, 1, buffer, 64]), 3);
+    assert_eq!(emulator.memory().read_string(buffer).unwrap(), "abc");
+
+    call(&mut natives, &mut emulator, "ArrayList.Erase", &[list, 0]);
+
+    assert_eq!(call(&mut natives, &mut emulator, "FindValueInArray", &[list, 10, 0]), -1);
+
+    // StringMap values and strings don't mix.
+    let map = call(&mut natives, &mut emulator, "StringMap.StringMap", &[]);
+
+    assert_eq!(call(&mut natives, &mut emulator, "StringMap.SetString", &[map, name, text, 1]), 1);
+    assert_eq!(call(&mut natives, &mut emulator, "StringMap.SetString", &[map, name, name, 0]), 0);
+    assert_eq!(call(&mut natives, &mut emulator, "StringMap.GetValue", &[map, name, refs[0]]), 0);
+    assert_eq!(call(&mut natives, &mut emulator, "StringMap.GetString", &[map, name, buffer, 64, refs[0]]), 1);
+    assert_eq!(emulator.memory().read_string(buffer).unwrap(), "xy-b-xy");
+    assert_eq!(emulator.memory().read_cell(refs[0]).unwrap(), 7);
+
+    let print = emulator.alloc_string("value %d", 16).unwrap();
+
+    call(&mut natives, &mut emulator, "PrintToServer", &[print, refs[1]]);
+
+    assert_eq!(natives.state().output(), ["value 7"]);
+
+    assert!(matches!(natives.invoke(emulator.memory_mut(), &NativeCall {
+        name: "CloseHandle",
+        index: 0,
+        address: 0x40,
+        args: &[1234],
+    }), Err(Error::Trap { address: 0x40, .. })));
+
+    // Custom natives can replace the stubs.
+    natives.bind("GetEngineTime", |_, _, _| Ok(1.0f32.to_bits() as i32));
+
+    assert_eq!(call(&mut natives, &mut emulator, "GetEngineTime", &[]), 1.0f32.to_bits() as i32);
+
+    natives.unbind("strlen");
+
+    assert!(matches!(natives.invoke(emulator.memory_mut(), &NativeCall {
+        name: "strlen",
+        index: 32,
+        address: 0x80,
+        args: &[name],
+    }), Err(Error::UnboundNative { address: 0x80, .. })));
+}
+
+#[test]
+fn test_native_limits() {
+    let p = smxdasm::file::SMXFile::new(load()).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut natives = NativeLibrary::core();
+
+    let buffer = emulator.alloc(16).unwrap();
+    let value = emulator.alloc(1).unwrap();
+
+    let mut trap = |emulator: &mut Emulator, name: &str, args: &[i32]| {
+        matches!(natives.invoke(emulator.memory_mut(), &NativeCall {
+            name,
+            index: 0,
+            address: 0x40,
+            args,
+        }), Err(Error::Trap { address: 0x40, .. }))
+    };
+
+    // Widths and precisions past the cap trap instead of padding, even when
+    // they overflow.
+    for fmt in ["%2000000000d", "%.70000f", "%99999999999999999999999d"] {
+        let fmt = emulator.alloc_string(fmt, 8).unwrap();
+
+        assert!(trap(&mut emulator, "Format", &[buffer, 64, fmt, value]));
+    }
+
+    // ArrayLists too large to preallocate.
+    assert!(trap(&mut emulator, "CreateArray", &[i32::MAX, 0]));
+    assert!(trap(&mut emulator, "CreateArray", &[0x1000, 0x1001]));
+    assert!(!trap(&mut emulator, "CreateArray", &[0x1000, 0x100]));
+}
@@ -0,0 +1,31 @@
+use std::convert::TryFrom;
+
+extern crate smxdasm;
+
+use smxdasm::v1opcodes::V1OPCode;
+use smxdasm::v1disassembler::opcode_info;
+
+#[test]
+fn test_opcode_coverage() {
+    for i in 1..V1OPCode::TOTAL_OPCODES as u8 {
+        let op = V1OPCode::try_from(i).unwrap();
+        let name = op.to_string();
+
+        // Opcodes the compiler never generates.
+        if name.starts_with("UNGEN_") || name.starts_with("UNGEB_") {
+            continue;
+        }
+
+        let info = opcode_info(i as u32).unwrap_or_else(|| panic!("missing opcode info for {}", name));
+
+        assert_eq!(info.opcode, op);
+    }
+}
+
+#[test]
+fn test_float_opcodes() {
+    assert_eq!(opcode_info(V1OPCode::FLOATADD as u32).unwrap().name, "float.add");
+    assert_eq!(opcode_info(V1OPCode::FLOAT_GT as u32).unwrap().name, "float.gt");
+    assert_eq!(opcode_info(V1OPCode::RND_TO_NEAREST as u32).unwrap().name, "round");
+    assert_eq!(opcode_info(V1OPCode::LDGFN_PRI as u32).unwrap().params.len(), 1);
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::errors::Error;
+use smxdasm::rtti::{Type, TypeFlags};
+
+#[test]
+fn test_rtti_types() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let rtti = f.rtti_data.as_ref().unwrap();
+
+    let native = f.rtti_natives.as_ref().unwrap().natives().into_iter().find(|n| n.name == "MarkNativeAsOptional").unwrap();
+    let signature = rtti.function_type_from_offset(native.signature).unwrap();
+
+    assert_eq!(signature.return_type, None);
+    assert_eq!(signature.args.len(), 1);
+    assert_eq!(signature.args[0].ty, Type::Array(Box::new(Type::Char)));
+    assert_eq!(signature.args[0].flags, TypeFlags::CONST);
+    assert_eq!(signature.to_string(), "function void (const char[])");
+
+    for typedef in &f.rtti_typedefs.as_ref().unwrap().typedefs() {
+        let t = rtti.type_from_id(typedef.type_id).unwrap();
+
+        match t.ty {
+            Type::Function(_) => (),
+            _ => panic!("typedef {} is not a function", typedef.name),
+        }
+    }
+
+    for field in &f.rtti_fields.as_ref().unwrap().fields() {
+        assert!(!rtti.type_from_id(field.type_id).unwrap().to_string().is_empty());
+    }
+}
+
+#[test]
+fn test_rtti_typeset_count() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let full = smxdasm::file::SMXFile::new(data).unwrap();
+
+    // Typeset counts of -1 and 0x7ffffffe appended to rtti.data.
+    let mut writer = smxdasm::writer::SMXWriter::from_header(&full.header).unwrap();
+    let mut section = writer.section("rtti.data").unwrap().to_vec();
+
+    let negative = section.len() as i32;
+
+    section.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+
+    let huge = section.len() as i32;
+
+    section.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0x07]);
+
+    writer.set_section("rtti.data", section);
+
+    let p = smxdasm::file::SMXFile::new(writer.write().unwrap()).unwrap();
+
+    let rtti = p.rtti_data.as_ref().unwrap();
+
+    assert!(matches!(rtti.typeset_types_from_offset(negative), Err(Error::InvalidIndex)));
+    assert!(rtti.typeset_types_from_offset(huge).is_err());
+}
@@ -0,0 +1,41 @@
+use std::process::Command;
+
+#[test]
+fn test_smxdump() {
+    let output = Command::new(env!("CARGO_BIN_EXE_smxdump"))
+        .arg("--natives")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+
+    assert!(text.contains("========== Natives =========="));
+    assert!(text.contains("MarkNativeAsOptional"));
+    assert!(!text.contains("========== Disassembly =========="));
+}
+
+#[test]
+fn test_smxdump_missing_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_smxdump")).output().unwrap();
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_smxdump_globals() {
+    let output = Command::new(env!("CARGO_BIN_EXE_smxdump"))
+        .arg("--globals")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+
+    assert!(text.contains("========== Globals =========="));
+    assert!(text.contains("char g_sHost[64] = \"127.0.0.1\""));
+}
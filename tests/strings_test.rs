@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+mod common;
+
+use smxdasm::listing::{V1Listing, OperandStyle};
+use smxdasm::strings::StringLiterals;
+use smxdasm::writer::SMXWriter;
+
+#[test]
+fn test_strings() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let strings = StringLiterals::new(f);
+
+    assert!(!strings.is_empty());
+
+    let (&addr, _) = strings.iter().find(|(_, s)| s.as_str() == "rf_scr_host").unwrap();
+
+    assert_eq!(f.data.as_ref().unwrap().read_string(addr).unwrap(), "rf_scr_host");
+
+    // The empty default value of rf_scr_prefix is a literal too.
+    assert!(strings.iter().any(|(_, s)| s.is_empty()));
+
+    // Named globals are not literals.
+    let globals = f.debug_globals.as_ref().unwrap();
+
+    for (&addr, _) in strings.iter() {
+        assert!(globals.find_global(addr).is_none_or(|sym| sym.address != addr));
+    }
+
+    let start = f.functions().values().find(|function| function.name == "OnPluginStart").unwrap();
+
+    let insn = start.instructions.iter().find(|insn| insn.info.name == "push3.c").unwrap();
+
+    assert_eq!(strings.operand(insn.address, 2), Some("rf_scr_version"));
+    assert_eq!(strings.operand(insn.address + 4, 0), None);
+
+    let listing = V1Listing::new(p.clone(), OperandStyle::Symbolic);
+    let text = listing.render_function(start);
+
+    assert!(text.contains("\"Source Chat Relay Version\", \"$SCRVER\", \"rf_scr_version\""));
+
+    let listing = V1Listing::new(p.clone(), OperandStyle::Raw);
+
+    assert!(!listing.render_function(start).contains('"'));
+}
+
+#[test]
+fn test_read_string() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let strings = StringLiterals::new(f);
+    let (&addr, _) = strings.iter().find(|(_, s)| s.as_str() == "rf_scr_version").unwrap();
+
+    let mut writer = SMXWriter::from_header(&f.header).unwrap();
+
+    // "," and "a" next to each other, then "x" followed by a small int global,
+    // then the unpacked strings "\u{e9}A" and "Hi!".
+    common::patch_data(&mut writer, addr, &[
+        ',' as i32, 'a' as i32, 'x' as i32, 7,
+        0xe9, 'A' as i32, 0,
+        'H' as i32, 'i' as i32, '!' as i32, 0,
+    ]);
+
+    let patched = smxdasm::file::SMXFile::new(writer.write().unwrap()).unwrap();
+    let data = patched.data.as_ref().unwrap();
+
+    assert_eq!(data.read_string(addr).unwrap(), ",");
+    assert_eq!(data.read_string(addr + 4).unwrap(), "a");
+    assert_eq!(data.read_string(addr + 8).unwrap(), "x");
+    assert_eq!(data.read_string(addr + 16).unwrap(), "\u{e9}A");
+    assert_eq!(data.read_string(addr + 28).unwrap(), "Hi!");
+
+    // A single character is a packed literal.
+    assert_eq!(data.read_string(addr + 36).unwrap(), "!");
+}
+
+#[test]
+fn test_argument_count() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let start = f.functions().values().find(|function| function.name == "OnPluginStart").unwrap();
+    let sysreq = start.instructions.iter().find(|insn| insn.info.name == "sysreq.n").unwrap();
+
+    // An argument count far beyond what was pushed.
+    let mut writer = SMXWriter::from_header(&f.header).unwrap();
+
+    common::patch_code(&mut writer, sysreq.address + 8, &[i32::MAX]);
+
+    let patched = smxdasm::file::SMXFile::new(writer.write().unwrap()).unwrap();
+
+    let strings = StringLiterals::new(&patched);
+
+    assert!(strings.iter().any(|(_, s)| s.as_str() == "rf_scr_host"));
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(StringLiterals::quote("\x01%s \"x\"\n"), "\"\\x01%s \\\"x\\\"\\n\"");
+}
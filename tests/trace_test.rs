@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::emulator::{Emulator, Memory, NativeCall, NativeHandler};
+use smxdasm::errors::{Result, Error};
+use smxdasm::trace::Trace;
+
+struct TestNatives;
+
+impl NativeHandler for TestNatives {
+    fn invoke(&mut self, _memory: &mut Memory, call: &NativeCall) -> Result<i32> {
+        match call.name {
+            "IsCharUpper" => Ok((call.args[0] as u8).is_ascii_uppercase() as i32),
+            _ => Err(Error::Other("Unexpected native")),
+        }
+    }
+}
+
+#[test]
+fn test_trace() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut trace = Trace::new();
+
+    let addr = emulator.find_function("CharToLower").unwrap();
+
+    assert_eq!(emulator.call_traced(addr, &['A' as i32], &mut TestNatives, &mut trace).unwrap(), 'a' as i32);
+
+    let names: Vec<&str> = trace.steps().iter().map(|step| step.name.as_str()).collect();
+
+    assert_eq!(names, ["break", "break", "push.s", "sysreq.n", "jzer", "break", "load.s.pri", "const.alt", "or", "retn"]);
+
+    assert_eq!(trace.steps()[0].address, addr + 4);
+
+    // push.s stores the argument just below the frame.
+    let push = &trace.steps()[2];
+
+    assert_eq!(push.writes.len(), 1);
+    assert_eq!(push.writes[0].address, push.registers.stk);
+    assert_eq!(push.writes[0].bytes, ('A' as i32).to_le_bytes());
+    assert_eq!(trace.steps()[6].registers.pri, 'A' as i32);
+    assert_eq!(trace.steps()[7].registers.alt, 32);
+
+    let last = trace.steps().last().unwrap();
+
+    assert_eq!(last.registers.pri, 'a' as i32);
+    assert_eq!(last.registers.stk, emulator.memory().size());
+
+    let json = trace.to_json_lines();
+    let lines: Vec<&str> = json.lines().collect();
+
+    assert_eq!(lines.len(), trace.len());
+    assert_eq!(lines[2], format!(
+        "{{\"address\":{},\"name\":\"push.s\",\"params\":[12],\"pri\":0,\"alt\":0,\"frm\":{},\"stk\":{},\"hea\":{},\"writes\":[{{\"address\":{},\"bytes\":\"41000000\"}}]}}",
+        push.address, push.registers.frm, push.registers.stk, push.registers.hea, push.registers.stk,
+    ));
+
+    // Untraced calls don't record anything.
+    trace.clear();
+
+    emulator.call(addr, &['A' as i32], &mut TestNatives).unwrap();
+
+    assert!(trace.is_empty());
+}
+
+struct FailingNatives;
+
+impl NativeHandler for FailingNatives {
+    fn invoke(&mut self, _memory: &mut Memory, _call: &NativeCall) -> Result<i32> {
+        Err(Error::Other("Native failed"))
+    }
+}
+
+#[test]
+fn test_trace_trap() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let mut emulator = Emulator::new(f).unwrap();
+    let mut trace = Trace::new();
+
+    let addr = emulator.find_function("CharToLower").unwrap();
+
+    assert!(emulator.call_traced(addr, &['A' as i32], &mut FailingNatives, &mut trace).is_err());
+
+    let names: Vec<&str> = trace.steps().iter().map(|step| step.name.as_str()).collect();
+
+    assert_eq!(names, ["break", "break", "push.s", "sysreq.n"]);
+
+    // The failing step is recorded, with the error that ended the run.
+    let last = trace.steps().last().unwrap();
+
+    assert_eq!(last.error.as_deref(), Some("Native failed"));
+    assert!(trace.steps()[..3].iter().all(|step| step.error.is_none()));
+    assert!(trace.to_json_lines().lines().last().unwrap().ends_with(",\"error\":\"Native failed\"}"));
+}
@@ -0,0 +1,175 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+mod common;
+
+use smxdasm::file::SMXFile;
+use smxdasm::v1disassembler::{opcode_info, DecodeMode, V1Function, V1FunctionKind, V1Instruction};
+use smxdasm::v1opcodes::V1OPCode;
+use smxdasm::verifier::{DiagnosticKind, StackAnalysis, StackState};
+use smxdasm::writer::SMXWriter;
+
+// Builds a function at address 0 from |code|, laid out after its PROC.
+fn function(code: &[(V1OPCode, &[i32])]) -> V1Function {
+    let mut address = 4;
+    let mut instructions = Vec::new();
+
+    for (op, params) in code {
+        instructions.push(V1Instruction {
+            address,
+            info: opcode_info(op.clone() as u32).unwrap(),
+            params: params.to_vec(),
+        });
+
+        address += 4 + params.len() as i32 * 4;
+    }
+
+    V1Function {
+        name: "test".into(),
+        address: 0,
+        code_end: address,
+        kind: V1FunctionKind::Called,
+        instructions,
+    }
+}
+
+fn kinds(code: &[(V1OPCode, &[i32])]) -> Vec<DiagnosticKind> {
+    StackAnalysis::new(&function(code)).diagnostics().iter().map(|diagnostic| diagnostic.kind.clone()).collect()
+}
+
+#[test]
+fn test_stack_analysis() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    for function in f.functions().values() {
+        let analysis = StackAnalysis::new(function);
+
+        assert!(analysis.is_balanced(), "{}: {:?}", function.name, analysis.diagnostics());
+        assert_eq!(analysis.state(0), Some(StackState::default()));
+    }
+}
+
+#[test]
+fn test_stack_diagnostics() {
+    // Arguments, the argument count and locals are all accounted for.
+    assert!(kinds(&[
+        (V1OPCode::STACK, &[-8]),
+        (V1OPCode::PUSH2_C, &[1, 2]),
+        (V1OPCode::PUSH_C, &[2]),
+        (V1OPCode::CALL, &[0]),
+        (V1OPCode::PUSH_PRI, &[]),
+        (V1OPCode::SYSREQ_N, &[0, 1]),
+        (V1OPCode::HEAP, &[4]),
+        (V1OPCode::HEAP, &[-4]),
+        (V1OPCode::STACK, &[8]),
+        (V1OPCode::RETN, &[]),
+    ]).is_empty());
+
+    assert_eq!(kinds(&[(V1OPCode::PUSH_C, &[1]), (V1OPCode::RETN, &[])]), [DiagnosticKind::StackNotEmpty { depth: 4 }]);
+    assert_eq!(kinds(&[(V1OPCode::HEAP, &[4]), (V1OPCode::RETN, &[])]), [DiagnosticKind::HeapNotEmpty { size: 4, trackers: 0 }]);
+    assert_eq!(kinds(&[(V1OPCode::POP_PRI, &[]), (V1OPCode::STACK, &[-4]), (V1OPCode::RETN, &[])]), [DiagnosticKind::NegativeStack { depth: -4 }]);
+    assert_eq!(kinds(&[(V1OPCode::HEAP, &[-4]), (V1OPCode::HEAP, &[4]), (V1OPCode::RETN, &[])]), [DiagnosticKind::NegativeHeap { size: -4 }]);
+    assert_eq!(kinds(&[(V1OPCode::TRACKER_POP_SETHEAP, &[]), (V1OPCode::RETN, &[])]), [DiagnosticKind::TrackerUnderflow]);
+    assert_eq!(kinds(&[(V1OPCode::PUSH_PRI, &[]), (V1OPCode::CALL, &[0]), (V1OPCode::RETN, &[])]), [
+        DiagnosticKind::UnknownArgumentCount,
+        DiagnosticKind::StackNotEmpty { depth: 4 },
+    ]);
+
+    // Extreme operands are reported instead of overflowing.
+    assert_eq!(kinds(&[(V1OPCode::STACK, &[i32::MIN]), (V1OPCode::RETN, &[])]), [DiagnosticKind::UsageOverflow]);
+    assert_eq!(kinds(&[(V1OPCode::PUSH_C, &[i32::MAX]), (V1OPCode::CALL, &[0]), (V1OPCode::RETN, &[])]), [DiagnosticKind::UsageOverflow]);
+    assert_eq!(kinds(&[(V1OPCode::SYSREQ_N, &[0, i32::MIN]), (V1OPCode::RETN, &[])]), [DiagnosticKind::UsageOverflow]);
+    assert_eq!(kinds(&[(V1OPCode::GENARRAY, &[i32::MIN]), (V1OPCode::RETN, &[])]), [DiagnosticKind::UsageOverflow]);
+    assert_eq!(kinds(&[(V1OPCode::HEAP, &[i32::MAX]), (V1OPCode::HEAP, &[1]), (V1OPCode::RETN, &[])]), [DiagnosticKind::UsageOverflow]);
+
+    // GENARRAY allocates, TRACKER.POP.SETHEAP frees.
+    assert!(kinds(&[
+        (V1OPCode::PUSH_C, &[4]),
+        (V1OPCode::GENARRAY, &[1]),
+        (V1OPCode::TRACKER_POP_SETHEAP, &[]),
+        (V1OPCode::STACK, &[4]),
+        (V1OPCode::RETN, &[]),
+    ]).is_empty());
+
+    // Only one side of the branch pushes.
+    let analysis = StackAnalysis::new(&function(&[
+        (V1OPCode::JZER, &[16]),
+        (V1OPCode::PUSH_PRI, &[]),
+        (V1OPCode::RETN, &[]),
+    ]));
+
+    let unbalanced = analysis.diagnostics().iter()
+        .find(|diagnostic| matches!(diagnostic.kind, DiagnosticKind::UnbalancedPaths { .. }))
+        .unwrap();
+
+    assert_eq!(unbalanced.address, 16);
+    assert_eq!(unbalanced.function, Some(0));
+    assert!(unbalanced.to_string().starts_with("00000010: paths disagree"));
+}
+
+#[test]
+fn test_verify() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(&data).unwrap();
+
+    let f = &*p;
+
+    assert!(f.verify().is_empty(), "{:?}", f.verify());
+
+    let mut writer = SMXWriter::from_header(&f.header).unwrap();
+
+    // sysreq.n strcmp in StrEqual.
+    common::patch_code(&mut writer, 0xbd0, &[999]);
+
+    // jzer in CharToLower, off an instruction boundary.
+    common::patch_code(&mut writer, 0xc08, &[0xc2a]);
+
+    // call StrEqual, into the middle of it.
+    common::patch_code(&mut writer, 0xfb8, &[0xbb4]);
+
+    // load.pri of a global.
+    common::patch_code(&mut writer, 0x1360, &[0x7fff_fff0]);
+
+    // switch in HandlePackets, to itself rather than its casetbl, which is
+    // then left without a switch.
+    common::patch_code(&mut writer, 0x54fc, &[0x54f8]);
+
+    // CodeV1Header::cell_size.
+    common::patch_section(&mut writer, ".code", 4, &[8]);
+
+    let image = writer.write().unwrap();
+    let patched = SMXFile::new_with_mode(image, DecodeMode::BestEffort).unwrap();
+
+    let diagnostics = patched.verify();
+
+    let kinds: Vec<(i32, DiagnosticKind)> = diagnostics.iter().map(|d| (d.address, d.kind.clone())).collect();
+
+    assert_eq!(kinds, [
+        (0, DiagnosticKind::InvalidCellSize { size: 8 }),
+        (0xbcc, DiagnosticKind::InvalidNativeIndex { index: 999 }),
+        (0xc04, DiagnosticKind::InvalidJumpTarget { target: 0xc2a }),
+        (0xfb4, DiagnosticKind::InvalidCallTarget { target: 0xbb4 }),
+        (0x135c, DiagnosticKind::InvalidDataAddress { address: 0x7fff_fff0 }),
+        (0x54f8, DiagnosticKind::MissingCaseTable { target: 0x54f8 }),
+        (0x5c00, DiagnosticKind::StrayCaseTable),
+    ]);
+
+    assert_eq!(diagnostics[0].function, None);
+    assert_eq!(diagnostics[1].function, Some(0xbb0));
+    assert_eq!(diagnostics[2].to_string(), "00000c04: jump target 0xc2a is not an instruction of this function");
+}
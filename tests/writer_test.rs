@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::headers::{SMXHeader, CompressionType};
+use smxdasm::writer::SMXWriter;
+
+fn plugin() -> Vec<u8> {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    data
+}
+
+#[test]
+fn test_round_trip() {
+    let data = plugin();
+
+    let header = SMXHeader::new(&data).unwrap();
+
+    let writer = SMXWriter::from_header(&header).unwrap();
+
+    assert_eq!(writer.write().unwrap(), data);
+}
+
+#[test]
+fn test_uncompressed() {
+    let data = plugin();
+
+    let header = SMXHeader::new(&data).unwrap();
+
+    let mut writer = SMXWriter::from_header(&header).unwrap();
+
+    writer.set_compression_type(CompressionType::CompressionNone);
+
+    let written = writer.write().unwrap();
+
+    assert_eq!(written.len(), header.image_size as usize);
+    assert_eq!(written[24..], header.data[24..]);
+
+    let p = smxdasm::file::SMXFile::new(written).unwrap();
+
+    assert_eq!(p.functions().len(), 64);
+}
+
+#[test]
+fn test_strip() {
+    let data = plugin();
+
+    let header = SMXHeader::new(&data).unwrap();
+
+    let mut writer = SMXWriter::from_header(&header).unwrap();
+
+    let names: Vec<String> = writer.sections().iter().map(|(name, _)| name.clone()).collect();
+
+    for name in names.iter().filter(|name| name.starts_with(".dbg.")) {
+        assert!(writer.remove_section(name));
+    }
+
+    assert!(!writer.remove_section(".dbg.files"));
+
+    let written = writer.write().unwrap();
+
+    assert!(written.len() < data.len());
+
+    let p = smxdasm::file::SMXFile::new(written).unwrap();
+
+    let f = &*p;
+
+    assert!(f.debug_lines.is_none());
+    assert!(f.rtti_methods.is_some());
+    assert_eq!(f.header.sections.len(), names.len() - 6);
+}
@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io::Read;
+
+extern crate smxdasm;
+
+use smxdasm::v1opcodes::V1OPCode;
+use smxdasm::xrefs::{XRefIndex, XRefKind};
+
+#[test]
+fn test_xrefs() {
+    let mut file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/Source-Chat-Relay.smx")).unwrap();
+
+    let mut data = Vec::new();
+
+    file.read_to_end(&mut data).unwrap();
+
+    let p = smxdasm::file::SMXFile::new(data).unwrap();
+
+    let f = &*p;
+
+    let xrefs = XRefIndex::new(f);
+
+    let insns: Vec<_> = f.functions().values().flat_map(|function| function.instructions.iter()).collect();
+
+    for insn in &insns {
+        match insn.info.opcode {
+            V1OPCode::CALL => {
+                assert!(xrefs.callers(insn.params[0]).iter().any(|xref| xref.address == insn.address));
+            },
+            V1OPCode::SYSREQ_C | V1OPCode::SYSREQ_N => {
+                assert!(xrefs.native_refs(insn.params[0]).iter().any(|xref| xref.address == insn.address));
+            },
+            V1OPCode::STOR_PRI => {
+                let xref = xrefs.global_refs(insn.params[0]).iter().find(|xref| xref.address == insn.address).unwrap();
+
+                assert_eq!(xref.kind, XRefKind::Write);
+            },
+            V1OPCode::JUMP | V1OPCode::JZER | V1OPCode::JNZ => {
+                assert!(xrefs.label_refs(insn.params[0]).iter().any(|xref| xref.kind == XRefKind::Jump && xref.address == insn.address));
+            },
+            _ => (),
+        }
+    }
+
+    let sysreqs = insns.iter().filter(|insn| matches!(insn.info.opcode, V1OPCode::SYSREQ_C | V1OPCode::SYSREQ_N)).count();
+    let native_refs: usize = xrefs.natives().map(|&index| xrefs.native_refs(index).len()).sum();
+
+    assert_eq!(sysreqs, native_refs);
+
+    // Every function other than publics and main is reachable through a call
+    // or a reference.
+    for function in f.functions().values() {
+        if function.kind == smxdasm::v1disassembler::V1FunctionKind::Called && function.name != "main" {
+            assert!(!xrefs.function_refs(function.address).is_empty(), "{}", function.name);
+        }
+    }
+
+    let natives = f.natives.as_ref().unwrap().entries();
+    let used = natives.iter().find(|native| !xrefs.native_refs_by_name(&native.name).is_empty()).unwrap();
+
+    for xref in xrefs.native_refs_by_name(&used.name) {
+        assert_eq!(xref.kind, XRefKind::Native);
+        assert!(f.function_at(xref.function).is_some());
+    }
+
+    assert!(xrefs.native_refs_by_name("NotANative").is_empty());
+
+    // InitColorTrie pushes the address of a string at 0xc5c in .data, which is
+    // also the offset of a PROC in .code. Both xrefs are kept.
+    let insn = insns.iter().find(|insn| insn.address == 0x1d5c).unwrap();
+
+    assert_eq!(insn.info.opcode, V1OPCode::PUSH3_C);
+    assert!(insn.params.contains(&0xc5c));
+    assert!(f.codev1.as_ref().unwrap().is_proc_at(0xc5c));
+
+    assert!(xrefs.global_refs(0xc5c).iter().any(|xref| xref.kind == XRefKind::Address && xref.address == 0x1d5c));
+    assert!(xrefs.function_refs(0xc5c).iter().any(|xref| xref.kind == XRefKind::Reference && xref.address == 0x1d5c));
+}